@@ -13,6 +13,9 @@ const char* password = "YOUR_WIFI_PASSWORD"; // Thay thế bằng mật khẩu W
 #define FIREBASE_AUTH "your-firebase-database-secret"
 #define FIREBASE_PATH "/sensor_readings"
 
+// Mã định danh của thiết bị, mỗi node ESP32 cần một mã riêng
+#define DEVICE_ID "greenhouse-1"
+
 // Cảm biến DHT
 #define DHTPIN 4
 #define DHTTYPE DHT11 // Thay thế bằng loại cảm biến khác, tại thầy m bảo dùng custom nên t đéo biết
@@ -114,6 +117,7 @@ void sendSensorDataToFirebase() {
   sensorJson.clear();
   
   // Thêm giá trị vào JSON
+  sensorJson.add("device_id", DEVICE_ID);
   sensorJson.add("temperature", isnan(temperature) ? 0 : temperature);
   sensorJson.add("humidity", isnan(humidity) ? 0 : humidity);
   sensorJson.add("water_level", waterLevel);
@@ -1,77 +1,573 @@
 use anyhow::{Result, anyhow};
-use firebase_rs::*;
-use serde_json::{Value, json};
-use tokio::runtime::Runtime;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::api::firebase_auth::{self, FirebaseAuth};
+use crate::api::http_client;
+use crate::api::retry::RetryPolicy;
 use crate::model::sensor_data::{ESP32SensorData, SensorReading};
-use std::collections::HashMap;
+use crate::util::preferences;
+use once_cell::sync::Lazy;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::time;
 
-const FIREBASE_URL: &str = "https://your-project-id.firebaseio.com";
-const DATABASE_PATH: &str = "sensor_readings";
+/// Readings fetched when nothing has been synced from a database yet
+const INITIAL_READINGS: &str = "5";
 
-pub fn initialize() -> Result<Firebase> {
-    let firebase = Firebase::new(FIREBASE_URL)?;
-    Ok(firebase)
+/// Firebase sends `keep-alive` every 30 seconds, so a stream silent for longer than this is dead
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Woken when the Firebase settings change so the stream reconnects with them
+static SETTINGS_CHANGED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Which Realtime Database to use, where the readings live in it and how to authenticate
+#[derive(Clone, PartialEq)]
+pub struct FirebaseConfig {
+    pub url: String,
+    pub path: String,
+    pub auth: FirebaseAuth,
 }
 
-pub fn fetch_latest_readings() -> Result<Vec<ESP32SensorData>> {
-    let firebase = initialize()?;
-    let db_path = firebase.at(DATABASE_PATH);
+impl FirebaseConfig {
+    pub fn from_preferences() -> Result<Self> {
+        Ok(Self {
+            url: preferences::load_firebase_url()?,
+            path: preferences::load_firebase_path()?,
+            auth: FirebaseAuth::from_settings(
+                &preferences::load_firebase_auth_mode()?,
+                &preferences::load_firebase_secret()?,
+                &preferences::load_firebase_service_account_file()?,
+            ),
+        })
+    }
     
-    // Create a new tokio runtime for async calls
-    let rt = Runtime::new()?;
+    /// Identifies the database and path that readings are synced from
+    pub fn source(&self) -> String {
+        format!("{}/{}", self.url.trim_end_matches('/'), self.path.trim_matches('/'))
+    }
     
-    // Execute the async function in the runtime
-    rt.block_on(async {
-        // Fetch only the last 5 readings ordered by timestamp
-        let result = db_path
-            .order_by("timestamp")
-            .limit_to_last(5)
-            .get::<HashMap<String, ESP32SensorData>>()
-            .await
-            .map_err(|e| anyhow!("Failed to fetch data from Firebase: {}", e))?;
-        
-        // Convert the HashMap to a Vec
-        let mut readings: Vec<ESP32SensorData> = result.values().cloned().collect();
-        
-        // Sort by timestamp in descending order
-        readings.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    /// Firebase is only used once a database URL has been entered
+    pub fn is_configured(&self) -> bool {
+        !self.url.is_empty()
+    }
+    
+    /// REST endpoint of the readings path
+    fn endpoint(&self) -> String {
+        format!("{}/{}.json", self.url.trim_end_matches('/'), self.path.trim_matches('/'))
+    }
+    
+    /// Request to the readings path with the credentials attached
+    async fn request(&self, client: &Client, method: Method) -> Result<RequestBuilder> {
+        if !self.is_configured() {
+            return Err(anyhow!("Firebase database URL is not set"));
+        }
         
-        Ok(readings)
-    })
+        let request = client.request(method, self.endpoint());
+        Ok(match self.auth.query_param().await? {
+            Some(param) => request.query(&[param]),
+            None => request,
+        })
+    }
+}
+
+/// Where streamed readings are stored, and how far each source has been synced
+pub trait FirebaseSink: Send + Sync + 'static {
+    /// Key of the last child stored from `source`
+    fn last_key(&self, source: &str) -> Result<Option<String>>;
+    
+    /// Store the reading found under child `key` of `source`
+    fn store(&self, source: &str, key: &str, data: ESP32SensorData) -> Result<()>;
+}
+
+/// Make a running stream pick up changed Firebase settings
+pub fn reload_settings() {
+    SETTINGS_CHANGED.notify_one();
+}
+
+// Request errors print the URL, which can carry the credentials, so it is left out
+fn request_error(context: &str, error: reqwest::Error) -> anyhow::Error {
+    anyhow!("{}: {}", context, error.without_url())
+}
+
+pub async fn push_sensor_reading_async(config: &FirebaseConfig, reading: &SensorReading) -> Result<()> {
+    let json_data = json!({
+        "device_id": reading.device_id,
+        "sensor_type": reading.sensor_type,
+        "value": reading.value,
+        "timestamp": reading.timestamp,
+        "is_alert": reading.is_alert
+    });
+    
+    let response = config
+        .request(&http_client::client(), Method::POST)
+        .await?
+        .json(&json_data)
+        .send()
+        .await
+        .map_err(|e| request_error("Failed to push data to Firebase", e))?;
+    
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("Firebase returned error status: {}", status));
+    }
+    
+    Ok(())
 }
 
 pub fn push_sensor_reading(reading: &SensorReading) -> Result<()> {
-    let firebase = initialize()?;
-    let db_path = firebase.at(DATABASE_PATH);
-    
-    // Create a new tokio runtime for async calls
-    let rt = Runtime::new()?;
-    
-    // Execute the async function in the runtime
-    rt.block_on(async {
-        let json_data = json!({
-            "sensor_type": reading.sensor_type,
-            "value": reading.value,
-            "timestamp": reading.timestamp,
-            "is_alert": reading.is_alert
-        });
+    let config = FirebaseConfig::from_preferences()?;
+    http_client::block_on(push_sensor_reading_async(&config, reading))
+}
+
+/// Stream new readings from the Firebase database in preferences to `sink` until `shutdown`
+/// turns true; waits while no database is configured
+pub async fn setup_realtime_updates<S: FirebaseSink>(shutdown: watch::Receiver<bool>, sink: S) {
+    let load_config = || match FirebaseConfig::from_preferences() {
+        Ok(config) => config.is_configured().then_some(config),
+        Err(e) => {
+            log::warn!("Failed to load Firebase settings: {}", e);
+            None
+        }
+    };
+    listen(load_config, RetryPolicy::from_preferences(), shutdown, sink).await
+}
+
+/// Follow the readings path of the database returned by `load_config` with the REST streaming
+/// protocol, reconnecting with `policy`'s backoff whenever the stream drops.
+///
+/// Readings are handed to `sink` one at a time in key order, off the async executor; when the
+/// sink fails the stream is reopened from the last reading it stored.
+/// Streaming starts after the last key the sink stored and a reconnect resumes after the last
+/// key seen, so readings pushed while the app or the stream was down are backfilled and earlier
+/// ones are not delivered again. `load_config` is asked again before every connection and after
+/// `reload_settings`; `None` means Firebase is not in use.
+pub async fn listen<C, S>(load_config: C, policy: RetryPolicy, mut shutdown: watch::Receiver<bool>, sink: S)
+where
+    C: Fn() -> Option<FirebaseConfig>,
+    S: FirebaseSink,
+{
+    let sink = Arc::new(sink);
+    let mut last_key: Option<String> = None;
+    let mut listening_to: Option<String> = None;
+    let mut failures = 0;
+    
+    while !*shutdown.borrow() {
+        let Some(config) = load_config() else {
+            log::debug!("No Firebase database configured");
+            tokio::select! {
+                _ = SETTINGS_CHANGED.notified() => continue,
+                _ = shutdown.changed() => break,
+            }
+        };
+        
+        // Keys seen in another database or path say nothing about this one
+        let source = config.source();
+        if listening_to.as_ref() != Some(&source) {
+            let (lookup, lookup_source) = (Arc::clone(&sink), source.clone());
+            let stored = tokio::task::spawn_blocking(move || lookup.last_key(&lookup_source))
+                .await
+                .map_err(|e| anyhow!(e))
+                .and_then(|key| key);
+            last_key = stored.unwrap_or_else(|e| {
+                log::warn!("Failed to load Firebase sync position, starting from the newest readings: {}", e);
+                None
+            });
+            listening_to = Some(source);
+        }
+        
+        let mut connected = false;
+        let result = tokio::select! {
+            result = stream_once(&config, &mut last_key, &sink, &mut connected) => result,
+            _ = SETTINGS_CHANGED.notified() => {
+                log::info!("Firebase settings changed, reconnecting");
+                failures = 0;
+                continue;
+            }
+            _ = shutdown.changed() => break,
+        };
+        
+        if connected {
+            failures = 0;
+        }
+        failures += 1;
+        let delay = policy.delay_for_attempt(failures);
         
-        db_path
-            .push(&json_data)
+        match result {
+            Ok(()) => log::info!("Firebase stream closed, reconnecting in {:?}", delay),
+            Err(e) => log::warn!("Firebase stream failed, reconnecting in {:?}: {}", delay, e),
+        }
+        
+        tokio::select! {
+            _ = time::sleep(delay) => {}
+            _ = shutdown.changed() => break,
+        }
+    }
+    
+    log::info!("Stopped listening for Firebase updates");
+}
+
+/// One streaming connection; returns when the server ends the stream or it fails
+async fn stream_once<S: FirebaseSink>(
+    config: &FirebaseConfig,
+    last_key: &mut Option<String>,
+    sink: &Arc<S>,
+    connected: &mut bool,
+) -> Result<()> {
+    let source = config.source();
+    let mut response = config
+        .request(&http_client::streaming_client()?, Method::GET)
+        .await?
+        .header(ACCEPT, "text/event-stream")
+        .query(&key_window(last_key.as_deref())?)
+        .send()
+        .await
+        .map_err(|e| request_error("Failed to connect to Firebase", e))?;
+    
+    let status = response.status();
+    if !status.is_success() {
+        if status == StatusCode::UNAUTHORIZED {
+            firebase_auth::invalidate_token().await;
+        }
+        return Err(anyhow!("Firebase returned error status: {}", status));
+    }
+    *connected = true;
+    log::info!("Listening for Firebase updates on {}", config.path);
+    
+    let mut parser = EventParser::default();
+    loop {
+        let chunk = time::timeout(STREAM_IDLE_TIMEOUT, response.chunk())
             .await
-            .map_err(|e| anyhow!("Failed to push data to Firebase: {}", e))?;
+            .map_err(|_| anyhow!("No data from Firebase for {:?}", STREAM_IDLE_TIMEOUT))?
+            .map_err(|e| request_error("Failed to read Firebase stream", e))?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
         
-        Ok(())
-    })
+        for (event, data) in parser.feed(&chunk) {
+            match event.as_str() {
+                "put" | "patch" => {
+                    for (key, reading) in readings_in(&data)? {
+                        if last_key.as_ref().is_some_and(|last| key <= *last) {
+                            continue;
+                        }
+                        
+                        // A reading that was not stored ends the stream, so the reconnect
+                        // starts again from the last one that was
+                        let (sink, source, stored_key) = (Arc::clone(sink), source.clone(), key.clone());
+                        tokio::task::spawn_blocking(move || sink.store(&source, &stored_key, reading))
+                            .await?
+                            .map_err(|e| anyhow!("Failed to process Firebase reading {}: {}", key, e))?;
+                        *last_key = Some(key);
+                    }
+                }
+                "keep-alive" => {}
+                "cancel" => return Err(anyhow!("Firebase cancelled the stream: {}", data)),
+                "auth_revoked" => {
+                    // Access tokens expire while a stream is open; the next connection gets a new one
+                    firebase_auth::invalidate_token().await;
+                    return Err(anyhow!("Firebase revoked the stream credentials"));
+                }
+                other => log::debug!("Ignoring Firebase stream event '{}'", other),
+            }
+        }
+    }
 }
 
-pub fn setup_realtime_updates<F>(callback: F) -> Result<()>
-where
-    F: Fn(ESP32SensorData) + Send + 'static,
-{
-    // This is a placeholder for setting up Firebase realtime updates
-    // Firebase's Rust SDK doesn't have direct support for realtime updates
-    // In a real implementation, you might use a different approach or library
+/// Body of a `put` or `patch` event
+#[derive(Deserialize)]
+struct StreamPayload {
+    path: String,
+    data: Value,
+}
+
+/// Readings carried by a `put` or `patch` event with their child keys, in key order
+fn readings_in(payload: &str) -> Result<Vec<(String, ESP32SensorData)>> {
+    let payload: StreamPayload = serde_json::from_str(payload)
+        .map_err(|e| anyhow!("Failed to parse Firebase event: {}", e))?;
+    let path = payload.path.trim_matches('/');
     
-    Ok(())
-} 
\ No newline at end of file
+    let children: Vec<(String, Value)> = if path.is_empty() {
+        // The whole listened path: child key to reading, or null when it is empty
+        match payload.data {
+            Value::Object(children) => children.into_iter().collect(),
+            _ => Vec::new(),
+        }
+    } else if !path.contains('/') {
+        vec![(path.to_string(), payload.data)]
+    } else {
+        // A field inside an existing reading changed; the reading itself was already delivered
+        Vec::new()
+    };
+    
+    Ok(readings_from(children))
+}
+
+/// Children that are sensor readings, in key order; removed children and other records are skipped
+fn readings_from(children: Vec<(String, Value)>) -> Vec<(String, ESP32SensorData)> {
+    let mut readings: Vec<(String, ESP32SensorData)> = children
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .filter_map(|(key, value)| match serde_json::from_value(value) {
+            Ok(reading) => Some((key, reading)),
+            Err(e) => {
+                log::debug!("Skipping Firebase child {} that is not a sensor reading: {}", key, e);
+                None
+            }
+        })
+        .collect();
+    readings.sort_by(|a, b| a.0.cmp(&b.0));
+    readings
+}
+
+/// Query selecting children from `last_key` on, or the newest few when nothing was synced yet.
+/// `startAt` is inclusive, so callers skip `last_key` itself.
+fn key_window(last_key: Option<&str>) -> Result<[(&'static str, String); 2]> {
+    let window = match last_key {
+        Some(key) => ("startAt", serde_json::to_string(key)?),
+        None => ("limitToLast", INITIAL_READINGS.to_string()),
+    };
+    Ok([("orderBy", "\"$key\"".to_string()), window])
+}
+
+/// Incremental parser for a `text/event-stream` body
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    event: String,
+    data: String,
+}
+
+impl EventParser {
+    /// Add received bytes, returning every `(event, data)` pair they complete
+    fn feed(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            
+            // A blank line ends the event
+            if line.is_empty() {
+                if !self.event.is_empty() || !self.data.is_empty() {
+                    events.push((std::mem::take(&mut self.event), std::mem::take(&mut self.data)));
+                }
+                continue;
+            }
+            
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event = value.to_string(),
+                "data" => {
+                    if !self.data.is_empty() {
+                        self.data.push('\n');
+                    }
+                    self.data.push_str(value);
+                }
+                // Comments, ids and retry hints are not used by Firebase
+                _ => {}
+            }
+        }
+        
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    
+    /// Records what it is given; `last_key` is the position stored by an earlier run
+    /// and storing `fail_once` fails the first time
+    struct RecordingSink {
+        last_key: Option<String>,
+        fail_once: Mutex<Option<String>>,
+        stored: Arc<Mutex<Vec<(String, String, i64)>>>,
+    }
+    
+    impl FirebaseSink for RecordingSink {
+        fn last_key(&self, _source: &str) -> Result<Option<String>> {
+            Ok(self.last_key.clone())
+        }
+        
+        fn store(&self, source: &str, key: &str, data: ESP32SensorData) -> Result<()> {
+            let mut fail_once = self.fail_once.lock().unwrap();
+            if fail_once.as_deref() == Some(key) {
+                *fail_once = None;
+                return Err(anyhow!("database is locked"));
+            }
+            self.stored.lock().unwrap().push((source.to_string(), key.to_string(), data.timestamp));
+            Ok(())
+        }
+    }
+    
+    fn reading(timestamp: i64) -> Value {
+        json!({
+            "device_id": "firebase-test",
+            "temperature": 25.0,
+            "humidity": 60.0,
+            "water_level": 50.0,
+            "ph": 7.0,
+            "salinity": 10.0,
+            "rain": false,
+            "soil_moisture": 40.0,
+            "timestamp": timestamp,
+        })
+    }
+    
+    /// Read one request head and answer it with the headers of an event stream; returns the request line
+    async fn accept_stream(listener: &TcpListener) -> (TcpStream, String) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut buffer = [0; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = socket.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "connection closed before the request was sent");
+            head.extend_from_slice(&buffer[..read]);
+        }
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        
+        let head = String::from_utf8_lossy(&head);
+        (socket, head.lines().next().unwrap_or_default().to_string())
+    }
+    
+    async fn send_event(socket: &mut TcpStream, event: &str, data: Value) {
+        let frame = format!("event: {}\ndata: {}\n\n", event, data);
+        socket.write_all(frame.as_bytes()).await.unwrap();
+    }
+    
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+    
+    fn test_config(listener: &TcpListener) -> FirebaseConfig {
+        FirebaseConfig {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            path: "/readings/".to_string(),
+            auth: FirebaseAuth::None,
+        }
+    }
+    
+    async fn wait_for_stored(stored: &Mutex<Vec<(String, String, i64)>>, count: usize) {
+        let waited = time::timeout(Duration::from_secs(10), async {
+            while stored.lock().unwrap().len() < count {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        waited.await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn streams_readings_and_resumes_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config(&listener);
+        let source = config.source();
+        
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let sink = RecordingSink {
+            last_key: Some("k001".to_string()),
+            fail_once: Mutex::new(None),
+            stored: Arc::clone(&stored),
+        };
+        let policy = fast_policy();
+        let (shutdown_sender, shutdown) = watch::channel(false);
+        let listener_task = tokio::spawn(listen(move || Some(config.clone()), policy, shutdown, sink));
+        
+        let server = async {
+            // Resumes from the stored key; startAt is inclusive, so k001 comes back and is skipped
+            let (mut socket, first_request) = accept_stream(&listener).await;
+            send_event(&mut socket, "put", json!({ "path": "/", "data": {
+                "k001": reading(1000),
+                "k002": reading(2000),
+                "k002a": { "note": "not a reading" },
+            }})).await;
+            send_event(&mut socket, "keep-alive", Value::Null).await;
+            send_event(&mut socket, "put", json!({ "path": "/k003", "data": reading(3000) })).await;
+            send_event(&mut socket, "patch", json!({ "path": "/k003", "data": { "temperature": 30.0 } })).await;
+            send_event(&mut socket, "put", json!({ "path": "/k003/humidity", "data": 65.0 })).await;
+            drop(socket);
+            
+            // The reconnect picks up after the last key delivered
+            let (mut socket, second_request) = accept_stream(&listener).await;
+            send_event(&mut socket, "put", json!({ "path": "/", "data": {
+                "k003": reading(3000),
+                "k004": reading(4000),
+            }})).await;
+            (socket, first_request, second_request)
+        };
+        let (_socket, first_request, second_request) = time::timeout(Duration::from_secs(10), server).await.unwrap();
+        
+        wait_for_stored(&stored, 3).await;
+        
+        shutdown_sender.send_replace(true);
+        time::timeout(Duration::from_secs(10), listener_task).await.unwrap().unwrap();
+        
+        assert!(first_request.starts_with("GET /readings.json?"), "{}", first_request);
+        assert!(first_request.contains("startAt=%22k001%22"), "{}", first_request);
+        assert!(second_request.contains("startAt=%22k003%22"), "{}", second_request);
+        
+        let stored = stored.lock().unwrap().clone();
+        let expected: Vec<(String, String, i64)> = [("k002", 2000), ("k003", 3000), ("k004", 4000)]
+            .into_iter()
+            .map(|(key, timestamp)| (source.clone(), key.to_string(), timestamp))
+            .collect();
+        assert_eq!(stored, expected);
+    }
+    
+    #[tokio::test]
+    async fn failed_store_is_retried_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config(&listener);
+        
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let sink = RecordingSink {
+            last_key: Some("k000".to_string()),
+            fail_once: Mutex::new(Some("k002".to_string())),
+            stored: Arc::clone(&stored),
+        };
+        let (shutdown_sender, shutdown) = watch::channel(false);
+        let listener_task = tokio::spawn(listen(move || Some(config.clone()), fast_policy(), shutdown, sink));
+        
+        let readings = json!({ "path": "/", "data": {
+            "k001": reading(1000),
+            "k002": reading(2000),
+            "k003": reading(3000),
+        }});
+        let server = async {
+            // Storing k002 fails, so k003 must not be taken from this connection either
+            let (mut socket, first_request) = accept_stream(&listener).await;
+            send_event(&mut socket, "put", readings.clone()).await;
+            
+            let (mut socket, second_request) = accept_stream(&listener).await;
+            send_event(&mut socket, "put", readings.clone()).await;
+            (socket, first_request, second_request)
+        };
+        let (_socket, first_request, second_request) = time::timeout(Duration::from_secs(10), server).await.unwrap();
+        
+        wait_for_stored(&stored, 3).await;
+        
+        shutdown_sender.send_replace(true);
+        time::timeout(Duration::from_secs(10), listener_task).await.unwrap().unwrap();
+        
+        assert!(first_request.contains("startAt=%22k000%22"), "{}", first_request);
+        assert!(second_request.contains("startAt=%22k001%22"), "{}", second_request);
+        
+        let keys: Vec<String> = stored.lock().unwrap().iter().map(|(_, key, _)| key.clone()).collect();
+        assert_eq!(keys, ["k001", "k002", "k003"]);
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use std::future::Future;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::runtime::{Handle, Runtime};
+use crate::util::preferences;
+
+/// Process-wide runtime used by the blocking API entry points
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("sensor-api")
+        .enable_all()
+        .build()
+        .expect("Failed to create API runtime")
+});
+
+/// Pooled HTTP client shared by all API calls, rebuilt when the timeouts change
+static CLIENT: Lazy<RwLock<Client>> = Lazy::new(|| {
+    let connect_timeout = preferences::load_http_connect_timeout_secs().unwrap_or(5);
+    let request_timeout = preferences::load_http_request_timeout_secs().unwrap_or(10);
+    let client = build_client(connect_timeout, request_timeout).unwrap_or_else(|e| {
+        log::error!("Failed to configure HTTP client, using defaults: {}", e);
+        Client::new()
+    });
+    RwLock::new(client)
+});
+
+fn build_client(connect_timeout_secs: u64, request_timeout_secs: u64) -> Result<Client> {
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(connect_timeout_secs))
+        .timeout(Duration::from_secs(request_timeout_secs))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .build()?;
+    Ok(client)
+}
+
+/// Client for long-lived streaming responses; only connecting is timed out since the body never ends
+pub fn streaming_client() -> Result<Client> {
+    let connect_timeout = preferences::load_http_connect_timeout_secs().unwrap_or(5);
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(connect_timeout))
+        .build()?;
+    Ok(client)
+}
+
+/// Shared HTTP client; cloning is cheap and reuses the connection pool
+pub fn client() -> Client {
+    match CLIENT.read() {
+        Ok(client) => client.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Rebuild the shared client with new timeouts
+pub fn configure_timeouts(connect_timeout_secs: u64, request_timeout_secs: u64) -> Result<()> {
+    let client = build_client(connect_timeout_secs, request_timeout_secs)?;
+    let mut shared = CLIENT.write().map_err(|_| anyhow!("Failed to lock HTTP client"))?;
+    *shared = client;
+    Ok(())
+}
+
+/// Run a future to completion on the shared runtime.
+/// Safe to call from inside another Tokio runtime: the future is then driven from a scoped thread.
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    match Handle::try_current() {
+        Ok(_) => std::thread::scope(|scope| {
+            scope
+                .spawn(|| RUNTIME.block_on(future))
+                .join()
+                .expect("API call panicked")
+        }),
+        Err(_) => RUNTIME.block_on(future),
+    }
+}
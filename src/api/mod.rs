@@ -1,2 +1,5 @@
 pub mod esp32_api;
-pub mod firebase_api; 
\ No newline at end of file
+pub mod firebase_api;
+pub mod firebase_auth;
+pub mod http_client;
+pub mod retry; 
\ No newline at end of file
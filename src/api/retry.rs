@@ -0,0 +1,164 @@
+use anyhow::{Result, anyhow};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use crate::util::preferences;
+
+/// Exponential backoff settings for retried API calls
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_millis(30000),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Load the policy from preferences, falling back to defaults for missing values
+    pub fn from_preferences() -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: preferences::load_retry_max_attempts().unwrap_or(default.max_attempts),
+            base_delay: preferences::load_retry_base_delay_ms()
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+            max_delay: preferences::load_retry_max_delay_ms()
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_delay),
+        }
+    }
+    
+    /// Delay before retrying after `attempt` failures: half of the capped exponential step plus a random half
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let step = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+        let half_ms = (step.as_millis() / 2) as u64;
+        let jitter_ms = if half_ms > 0 { rand::thread_rng().gen_range(0..=half_ms) } else { 0 };
+        Duration::from_millis(half_ms + jitter_ms)
+    }
+}
+
+/// Run `operation` until it succeeds or the policy runs out of attempts, returning the last error.
+/// Gives up as soon as `shutdown` is signalled, whether a request or a backoff sleep is in flight.
+pub async fn retry_async<T, F, Fut>(policy: &RetryPolicy, mut shutdown: watch::Receiver<bool>, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 1;
+    
+    loop {
+        if *shutdown.borrow() {
+            return Err(anyhow!("Shutting down"));
+        }
+        
+        let result = tokio::select! {
+            result = operation() => result,
+            _ = shutdown.changed() => return Err(anyhow!("Shutting down")),
+        };
+        
+        match result {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= max_attempts => return Err(e),
+            Err(e) => {
+                let delay = policy.delay_for_attempt(attempt);
+                log::warn!("Attempt {}/{} failed: {}. Retrying in {:?}", attempt, max_attempts, e, delay);
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => return Err(anyhow!("Shutting down")),
+                }
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    
+    fn slow_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(60),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+    
+    #[tokio::test]
+    async fn shutdown_interrupts_backoff_sleep() {
+        let (tx, rx) = watch::channel(false);
+        let started = Instant::now();
+        
+        let signal = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let _ = tx.send(true);
+            tx
+        });
+        
+        let mut calls = 0;
+        let result: Result<()> = retry_async(&slow_policy(), rx, || {
+            calls += 1;
+            async { Err(anyhow!("unreachable")) }
+        }).await;
+        
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(signal.await);
+    }
+    
+    #[tokio::test]
+    async fn shutdown_interrupts_pending_request() {
+        let (tx, rx) = watch::channel(false);
+        let started = Instant::now();
+        
+        let signal = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let _ = tx.send(true);
+            tx
+        });
+        
+        let result: Result<()> = retry_async(&slow_policy(), rx, || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }).await;
+        
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(signal.await);
+    }
+    
+    #[tokio::test]
+    async fn returns_first_success() {
+        let (_tx, rx) = watch::channel(false);
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        
+        let mut calls = 0;
+        let result = retry_async(&policy, rx, || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                if attempt < 3 { Err(anyhow!("not yet")) } else { Ok(attempt) }
+            }
+        }).await;
+        
+        assert_eq!(result.unwrap(), 3);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Options that never take a value
+const FLAGS: [&str; 5] = ["--json", "--raw", "--alerts", "--parquet", "--help"];
+
+/// A command line that was not understood; reported with exit code 2
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Shorthand for returning a `UsageError` from a function returning `anyhow::Result`
+pub fn usage<T>(message: impl Into<String>) -> anyhow::Result<T> {
+    Err(UsageError(message.into()).into())
+}
+
+/// Command-line arguments split into positionals, `--option value` pairs and flags
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    /// Parse arguments; options accept both `--name value` and `--name=value`
+    pub fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut parsed = Args::default();
+        let mut iter = args.iter();
+        
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with("--") || arg == "--" {
+                parsed.positional.push(arg.clone());
+                continue;
+            }
+            
+            if let Some((name, value)) = arg.split_once('=') {
+                parsed.options.insert(name.to_string(), value.to_string());
+            } else if FLAGS.contains(&arg.as_str()) {
+                parsed.flags.insert(arg.clone());
+            } else {
+                match iter.next() {
+                    Some(value) => {
+                        parsed.options.insert(arg.clone(), value.clone());
+                    }
+                    None => return usage(format!("{} needs a value", arg)),
+                }
+            }
+        }
+        
+        Ok(parsed)
+    }
+    
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(String::as_str)
+    }
+    
+    /// Positional argument that must be present
+    pub fn required(&self, index: usize, name: &str) -> anyhow::Result<&str> {
+        match self.positional(index) {
+            Some(value) => Ok(value),
+            None => usage(format!("Missing <{}>", name)),
+        }
+    }
+    
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+    
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+    
+    /// Option parsed with `FromStr`, reporting a usage error when it does not parse
+    pub fn parsed_option<T: std::str::FromStr>(&self, name: &str) -> anyhow::Result<Option<T>> {
+        match self.option(name) {
+            Some(value) => match value.parse() {
+                Ok(parsed) => Ok(Some(parsed)),
+                Err(_) => usage(format!("Invalid value for {}: '{}'", name, value)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn parse(args: &[&str]) -> anyhow::Result<Args> {
+        Args::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+    
+    #[test]
+    fn splits_positionals_options_and_flags() {
+        let args = parse(&["history", "temperature", "--device", "esp32-2", "--from=2024-01-01", "--raw", "--json"]).unwrap();
+        
+        assert_eq!(args.positional(0), Some("history"));
+        assert_eq!(args.positional(1), Some("temperature"));
+        assert_eq!(args.positional(2), None);
+        assert_eq!(args.option("--device"), Some("esp32-2"));
+        assert_eq!(args.option("--from"), Some("2024-01-01"));
+        assert!(args.flag("--raw") && args.flag("--json"));
+        assert!(!args.flag("--alerts"));
+        // A flag never swallows the next argument
+        assert_eq!(parse(&["--json", "latest"]).unwrap().positional(0), Some("latest"));
+    }
+    
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        assert!(parse(&["latest", "--device"]).unwrap_err().is::<UsageError>());
+        
+        let args = parse(&["thresholds", "set", "--min", "low"]).unwrap();
+        assert!(args.parsed_option::<f32>("--min").unwrap_err().is::<UsageError>());
+        assert_eq!(args.parsed_option::<f32>("--max").unwrap(), None);
+        assert!(args.required(2, "sensor").unwrap_err().is::<UsageError>());
+    }
+}
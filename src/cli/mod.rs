@@ -0,0 +1,524 @@
+pub mod args;
+
+use anyhow::{Result, anyhow};
+use args::{usage, Args, UsageError};
+use crate::daemon::config::DaemonConfig;
+use crate::data::{self, migration};
+use crate::data::dao::{retention_dao, sensor_reading_dao};
+use crate::model::device::{Device, DEFAULT_DEVICE_ID};
+use crate::model::export::ReadingFilter;
+use crate::model::import::ImportOptions;
+use crate::model::sensor_data::SensorThreshold;
+use crate::model::sensor_types;
+use crate::repository::{archive_repository, export_repository, import_repository, sensor_repository};
+use crate::util::date_converter;
+use serde::Serialize;
+use serde_json::json;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// `println!` that returns an error instead of panicking when stdout is closed,
+/// e.g. when the output is piped into `head`
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        writeln!(std::io::stdout(), $($arg)*)?
+    };
+}
+
+/// First arguments that run a CLI command instead of the GUI
+const COMMANDS: [&str; 8] = ["latest", "history", "thresholds", "export", "import", "devices", "db", "help"];
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+const USAGE: &str = "Usage: sensor_monitor <command> [options]
+
+Commands:
+  latest [--device <id>]                        Newest reading of every sensor
+  history <sensor> [--device <id>] [--from <time>] [--to <time>] [--raw]
+                                                Bucketed history (last 24 h by default), or raw readings
+  thresholds get [<sensor>]                     Show alert thresholds
+  thresholds set <sensor> [--min <v>] [--max <v>] [--hysteresis <v>]
+                          [--min-readings <n>] [--min-duration <secs>]
+  thresholds reset <sensor>                     Restore the default threshold
+  export <path> [--device <id>] [--sensor <type>] [--from <time>] [--to <time>]
+                [--alerts | --parquet]          CSV of readings, CSV of alerts, or a Parquet archive folder
+  import <file> [--device <id>] [--map <a=b,...>]
+                                                Import readings from CSV or JSON lines
+  devices list                                  Registered devices and their status
+  devices add <id> [--name <name>] [--url <url>] [--location <text>]
+  db vacuum                                     Reclaim space in the database file
+  db migrate                                    Bring the database schema up to date
+
+Global options:
+  --json               Print JSON instead of tables
+  --database <path>    Use this SQLite file
+  --config <path>      Use a daemon config file for preferences and the database path
+
+Times are epoch milliseconds, RFC 3339, 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD' (local time).
+Exit codes: 0 success, 1 the command failed, 2 invalid command line.";
+
+/// Whether `arg` names a CLI command
+pub fn is_command(arg: &str) -> bool {
+    COMMANDS.contains(&arg)
+}
+
+/// Run a CLI command and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    // Logs go to stderr so they never mix with the output on stdout
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).try_init();
+    
+    match Args::parse(args).and_then(|args| dispatch(&args)) {
+        Ok(()) => 0,
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == ErrorKind::BrokenPipe) => 0,
+        Err(e) if e.is::<UsageError>() => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            2
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn dispatch(args: &Args) -> Result<()> {
+    let command = args.positional(0).unwrap_or("help");
+    if command == "help" || args.flag("--help") {
+        outln!("{}", USAGE);
+        return Ok(());
+    }
+    if !is_command(command) {
+        return usage(format!("Unknown command '{}'", command));
+    }
+    
+    let database_path = configure(args)?;
+    
+    // Migrations run on every open, so `db migrate` opens the file itself to report them
+    if command == "db" && args.positional(1) == Some("migrate") {
+        return db_migrate(args, &database_path);
+    }
+    data::initialize_database_at(&database_path)?;
+    
+    match command {
+        "latest" => latest(args),
+        "history" => history(args),
+        "thresholds" => thresholds(args),
+        "export" => export(args),
+        "import" => import(args),
+        "devices" => devices(args),
+        _ => db(args, &database_path),
+    }
+}
+
+/// Apply `--config` and `--database`, returning the database file to open
+fn configure(args: &Args) -> Result<PathBuf> {
+    let config = match args.option("--config") {
+        Some(path) => Some(DaemonConfig::load(Path::new(path))?),
+        None => None,
+    };
+    
+    let database_path = args.option("--database")
+        .map(PathBuf::from)
+        .or_else(|| config.as_ref().and_then(|config| config.database_path.clone()))
+        .unwrap_or_else(data::get_database_path);
+    
+    if let Some(config) = &config {
+        config.apply_preferences(&database_path)?;
+    }
+    
+    Ok(database_path)
+}
+
+fn latest(args: &Args) -> Result<()> {
+    let readings = match args.option("--device") {
+        Some(device_id) => sensor_repository::get_latest_readings(device_id)?,
+        None => sensor_repository::get_all_latest_readings()?,
+    };
+    
+    if args.flag("--json") {
+        return print_json(&readings);
+    }
+    if readings.is_empty() {
+        outln!("No readings stored");
+        return Ok(());
+    }
+    
+    let rows = readings.iter()
+        .map(|reading| vec![
+            reading.device_id.clone(),
+            sensor_types::get_display_name(&reading.sensor_type).to_string(),
+            format_value(&reading.sensor_type, reading.value),
+            date_converter::format_timestamp(reading.timestamp),
+            if reading.is_alert { "ALERT".to_string() } else { String::new() },
+        ])
+        .collect::<Vec<_>>();
+    print_table(&["DEVICE", "SENSOR", "VALUE", "TIME", ""], &rows)
+}
+
+fn history(args: &Args) -> Result<()> {
+    let sensor_type = sensor_argument(args.required(1, "sensor")?)?;
+    let device_id = args.option("--device").unwrap_or(DEFAULT_DEVICE_ID);
+    let to = time_option(args, "--to")?.unwrap_or_else(date_converter::current_timestamp);
+    let from = time_option(args, "--from")?.unwrap_or(to - DAY_MS);
+    if from >= to {
+        return usage("--from must be before --to");
+    }
+    
+    if args.flag("--raw") {
+        let readings = sensor_reading_dao::get_readings_in_range(device_id, sensor_type, from, to)?;
+        if args.flag("--json") {
+            return print_json(&readings);
+        }
+        
+        let rows = readings.iter()
+            .map(|reading| vec![
+                date_converter::format_timestamp(reading.timestamp),
+                format_value(sensor_type, reading.value),
+                if reading.is_alert { "ALERT".to_string() } else { String::new() },
+            ])
+            .collect::<Vec<_>>();
+        return print_table(&["TIME", "VALUE", ""], &rows);
+    }
+    
+    let history = sensor_repository::get_aggregated_history(device_id, sensor_type, from, to)?;
+    if args.flag("--json") {
+        return print_json(&history);
+    }
+    
+    let rows = history.buckets.iter()
+        .map(|bucket| vec![
+            date_converter::format_timestamp(bucket.bucket_start),
+            format_value(sensor_type, bucket.min_value),
+            format_value(sensor_type, bucket.avg_value),
+            format_value(sensor_type, bucket.max_value),
+            bucket.count.to_string(),
+            bucket.alert_count.to_string(),
+        ])
+        .collect::<Vec<_>>();
+    outln!(
+        "{} on {}, {} buckets of {} min",
+        sensor_types::get_display_name(sensor_type),
+        device_id,
+        rows.len(),
+        history.bucket_ms / 60_000
+    );
+    print_table(&["BUCKET START", "MIN", "AVG", "MAX", "READINGS", "ALERTS"], &rows)
+}
+
+fn thresholds(args: &Args) -> Result<()> {
+    let thresholds = match args.positional(1).unwrap_or("get") {
+        "get" => match args.positional(2) {
+            Some(sensor_type) => vec![sensor_repository::get_threshold(sensor_argument(sensor_type)?)?],
+            None => sensor_repository::get_all_thresholds()?,
+        },
+        "set" => {
+            let sensor_type = sensor_argument(args.required(2, "sensor")?)?;
+            let mut threshold = sensor_repository::get_threshold(sensor_type)?;
+            let before = threshold.clone();
+            
+            if let Some(value) = args.parsed_option("--min")? {
+                threshold.min_value = value;
+            }
+            if let Some(value) = args.parsed_option("--max")? {
+                threshold.max_value = value;
+            }
+            if let Some(value) = args.parsed_option("--hysteresis")? {
+                threshold.hysteresis = value;
+            }
+            if let Some(value) = args.parsed_option("--min-readings")? {
+                threshold.min_alert_readings = value;
+            }
+            if let Some(value) = args.parsed_option("--min-duration")? {
+                threshold.min_alert_duration_secs = value;
+            }
+            if threshold == before {
+                return usage("Nothing to set; pass --min, --max, --hysteresis, --min-readings or --min-duration");
+            }
+            
+            sensor_repository::set_threshold(&threshold)?;
+            vec![threshold]
+        }
+        "reset" => {
+            let sensor_type = sensor_argument(args.required(2, "sensor")?)?;
+            vec![sensor_repository::reset_threshold(sensor_type)?]
+        }
+        other => return usage(format!("Unknown thresholds command '{}'", other)),
+    };
+    
+    if args.flag("--json") {
+        return print_json(&thresholds);
+    }
+    
+    let rows = thresholds.iter().map(threshold_row).collect::<Vec<_>>();
+    print_table(&["SENSOR", "MIN", "MAX", "HYSTERESIS", "MIN READINGS", "MIN DURATION"], &rows)
+}
+
+fn threshold_row(threshold: &SensorThreshold) -> Vec<String> {
+    vec![
+        threshold.sensor_type.clone(),
+        format_value(&threshold.sensor_type, threshold.min_value),
+        format_value(&threshold.sensor_type, threshold.max_value),
+        threshold.hysteresis.to_string(),
+        threshold.min_alert_readings.to_string(),
+        format!("{} s", threshold.min_alert_duration_secs),
+    ]
+}
+
+fn export(args: &Args) -> Result<()> {
+    let path = PathBuf::from(args.required(1, "path")?);
+    let filter = ReadingFilter {
+        device_id: args.option("--device").map(str::to_string),
+        sensor_type: args.option("--sensor").map(sensor_argument).transpose()?.map(str::to_string),
+        from: time_option(args, "--from")?,
+        to: time_option(args, "--to")?,
+    };
+    
+    let summary = if args.flag("--parquet") {
+        archive_repository::export_archive(&path, &filter)?
+    } else if args.flag("--alerts") {
+        export_repository::export_alerts_csv(&path, &filter)?
+    } else {
+        export_repository::export_readings_csv(&path, &filter)?
+    };
+    
+    if args.flag("--json") {
+        return print_json(&summary);
+    }
+    outln!("Exported {} rows to {}", summary.rows, summary.path);
+    Ok(())
+}
+
+fn import(args: &Args) -> Result<()> {
+    let path = PathBuf::from(args.required(1, "file")?);
+    let column_map = match ImportOptions::parse_column_map(args.option("--map").unwrap_or("")) {
+        Ok(column_map) => column_map,
+        Err(e) => return usage(e),
+    };
+    let options = ImportOptions {
+        default_device_id: args.option("--device").unwrap_or(DEFAULT_DEVICE_ID).to_string(),
+        column_map,
+    };
+    
+    let summary = import_repository::import_readings(&path, &options)?;
+    
+    if args.flag("--json") {
+        print_json(&summary)?;
+    } else {
+        outln!(
+            "{} rows read: {} readings imported, {} duplicates skipped, {} rows with errors",
+            summary.rows, summary.imported, summary.duplicates, summary.error_count
+        );
+        for error in &summary.errors {
+            outln!("  line {}: {}", error.line, error.message);
+        }
+    }
+    
+    if summary.error_count > 0 {
+        return Err(anyhow!("{} rows could not be imported", summary.error_count));
+    }
+    Ok(())
+}
+
+fn devices(args: &Args) -> Result<()> {
+    match args.positional(1).unwrap_or("list") {
+        "list" => {
+            let devices = sensor_repository::get_devices()?;
+            let mut statuses = Vec::new();
+            for device in &devices {
+                statuses.push(sensor_repository::get_device_status(&device.id)?);
+            }
+            
+            if args.flag("--json") {
+                let devices = devices.iter()
+                    .zip(&statuses)
+                    .map(|(device, status)| json!({ "device": device, "status": status }))
+                    .collect::<Vec<_>>();
+                return print_json(&devices);
+            }
+            
+            let rows = devices.iter()
+                .zip(&statuses)
+                .map(|(device, status)| vec![
+                    device.id.clone(),
+                    device.name.clone(),
+                    device.url.clone(),
+                    device.location.clone(),
+                    status.as_ref().map_or("unknown".to_string(), |event| {
+                        format!("{} since {}", event.status, date_converter::format_timestamp(event.timestamp))
+                    }),
+                ])
+                .collect::<Vec<_>>();
+            print_table(&["ID", "NAME", "URL", "LOCATION", "STATUS"], &rows)
+        }
+        "add" => {
+            let id = args.required(2, "id")?;
+            let device = Device::new(
+                id,
+                args.option("--name").unwrap_or(id),
+                args.option("--url").unwrap_or(""),
+                args.option("--location").unwrap_or(""),
+            );
+            sensor_repository::save_device(&device)?;
+            
+            if args.flag("--json") {
+                return print_json(&device);
+            }
+            outln!("Saved device {}", device.id);
+            Ok(())
+        }
+        other => usage(format!("Unknown devices command '{}'", other)),
+    }
+}
+
+fn db(args: &Args, database_path: &Path) -> Result<()> {
+    match args.positional(1) {
+        Some("vacuum") => {
+            let size_before = std::fs::metadata(database_path)?.len();
+            retention_dao::vacuum()?;
+            let size_after = std::fs::metadata(database_path)?.len();
+            
+            if args.flag("--json") {
+                return print_json(&json!({
+                    "path": database_path.display().to_string(),
+                    "size_before": size_before,
+                    "size_after": size_after,
+                }));
+            }
+            outln!("Vacuumed {}: {} -> {} bytes", database_path.display(), size_before, size_after);
+            Ok(())
+        }
+        Some(other) => usage(format!("Unknown db command '{}'", other)),
+        None => usage("Missing db command: vacuum or migrate"),
+    }
+}
+
+fn db_migrate(args: &Args, database_path: &Path) -> Result<()> {
+    if let Some(parent) = database_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut conn = rusqlite::Connection::open(database_path)?;
+    
+    let version_before = migration::current_version(&conn)?;
+    let backup_dir = database_path.parent().map(|parent| parent.join("backups"));
+    let applied = migration::run_migrations(&mut conn, backup_dir.as_deref())?;
+    let version = migration::current_version(&conn)?;
+    
+    if args.flag("--json") {
+        return print_json(&json!({
+            "path": database_path.display().to_string(),
+            "version_before": version_before,
+            "version": version,
+            "applied": applied,
+        }));
+    }
+    if applied == 0 {
+        outln!("{} is up to date at version {}", database_path.display(), version);
+    } else {
+        outln!("Migrated {} from version {} to {}", database_path.display(), version_before, version);
+    }
+    Ok(())
+}
+
+fn sensor_argument(sensor_type: &str) -> Result<&str> {
+    match sensor_types::ALL.iter().find(|known| **known == sensor_type) {
+        Some(known) => Ok(known),
+        None => usage(format!("Unknown sensor '{}'; expected one of {}", sensor_type, sensor_types::ALL.join(", "))),
+    }
+}
+
+fn time_option(args: &Args, name: &str) -> Result<Option<i64>> {
+    match args.option(name) {
+        Some(text) => match date_converter::parse_datetime(text) {
+            Some(timestamp) => Ok(Some(timestamp)),
+            None => usage(format!("Invalid time for {}: '{}'", name, text)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn format_value(sensor_type: &str, value: f32) -> String {
+    match sensor_types::get_unit(sensor_type) {
+        "" => format!("{:.1}", value),
+        unit => format!("{:.1} {}", value, unit),
+    }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    outln!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print rows under a header with every column padded to its widest cell
+fn print_table(headers: &[&str], rows: &[Vec<String>]) -> Result<()> {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    
+    let line = |cells: Vec<&str>| {
+        let padded = cells.iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect::<Vec<_>>();
+        padded.join("  ").trim_end().to_string()
+    };
+    
+    outln!("{}", line(headers.to_vec()));
+    for row in rows {
+        outln!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Run a command against the shared test database. `--database` keeps the CLI from opening
+    /// the user's database; the connection opened first stays the one in use.
+    fn run_with(args: &[&str]) -> i32 {
+        data::init_test_database();
+        let database = std::env::temp_dir().join(format!("sensor_monitor_cli_test_{}.db", std::process::id()));
+        
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.extend(["--database".to_string(), database.display().to_string()]);
+        run(&args)
+    }
+    
+    #[test]
+    fn successful_commands_exit_with_0() {
+        assert_eq!(run(&["help".to_string()]), 0);
+        assert_eq!(run_with(&["latest", "--device", "cli-test", "--json"]), 0);
+        assert_eq!(run_with(&["thresholds", "get", "temperature"]), 0);
+        assert_eq!(run_with(&["devices", "add", "cli-test", "--name", "CLI test"]), 0);
+        
+        let devices = sensor_repository::get_devices().unwrap();
+        assert!(devices.iter().any(|device| device.id == "cli-test" && device.name == "CLI test"));
+    }
+    
+    #[test]
+    fn failed_commands_exit_with_1() {
+        let missing = std::env::temp_dir().join("sensor_monitor_cli_test_missing.csv");
+        assert_eq!(run_with(&["import", &missing.display().to_string()]), 1);
+        
+        // Rows that cannot be imported fail the command
+        let bad = std::env::temp_dir().join(format!("sensor_monitor_cli_test_{}.csv", std::process::id()));
+        std::fs::write(&bad, "timestamp_ms,device_id,temperature\nyesterday,cli-import-test,20\n").unwrap();
+        assert_eq!(run_with(&["import", &bad.display().to_string()]), 1);
+        let _ = std::fs::remove_file(&bad);
+    }
+    
+    #[test]
+    fn invalid_command_lines_exit_with_2() {
+        assert_eq!(run(&["frobnicate".to_string()]), 2);
+        assert_eq!(run(&["latest".to_string(), "--device".to_string()]), 2);
+        assert_eq!(run_with(&["history", "wind"]), 2);
+        assert_eq!(run_with(&["history", "temperature", "--from", "2024-01-02", "--to", "2024-01-01"]), 2);
+        assert_eq!(run_with(&["history", "temperature", "--from", "someday"]), 2);
+        assert_eq!(run_with(&["thresholds", "set", "temperature"]), 2);
+        assert_eq!(run_with(&["thresholds", "set", "temperature", "--min", "cold"]), 2);
+        assert_eq!(run_with(&["db"]), 2);
+    }
+}
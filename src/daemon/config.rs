@@ -0,0 +1,89 @@
+use anyhow::{Result, anyhow};
+use crate::util::preferences;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "daemon.json";
+
+/// Settings only the headless daemon reads.
+///
+/// Any other key (`esp32_url`, `poll_interval_secs`, retention, notification settings, ...)
+/// is a preference. The config file is only ever read; preferences are copied into a
+/// preferences file next to the database, which is where changes at runtime are saved.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// SQLite file to use instead of the one in the user data dir
+    pub database_path: Option<PathBuf>,
+    /// File log lines are appended to in addition to stdout
+    pub log_file: Option<PathBuf>,
+    /// Log filter such as `info` or `sensor_monitor=debug`; `RUST_LOG` takes precedence
+    pub log_level: Option<String>,
+    /// Address the REST API listens on, e.g. `0.0.0.0:8080`; the API is off when unset
+    pub api_listen: Option<String>,
+    /// Every remaining key, applied as a preference on startup
+    #[serde(flatten)]
+    pub preferences: serde_json::Map<String, serde_json::Value>,
+}
+
+impl DaemonConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+    }
+    
+    /// Use the preferences file next to `database_path` and copy this config's preferences into it,
+    /// so values in the config win over ones saved by an earlier run
+    pub fn apply_preferences(&self, database_path: &Path) -> Result<()> {
+        let path = database_path.with_file_name(preferences::PREFERENCES_FILE);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        preferences::use_preferences_file(path);
+        preferences::merge_preferences(&self.preferences)
+    }
+}
+
+/// Config file used when `--config` is not given
+pub fn default_config_path() -> PathBuf {
+    dirs_next::config_dir()
+        .map(|dir| dir.join("sensor_monitor").join(CONFIG_FILE))
+        .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn preferences_are_copied_next_to_database_and_config_is_never_written() {
+        let dir = std::env::temp_dir().join(format!("sensor_monitor_daemon_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        let config_path = dir.join(CONFIG_FILE);
+        let contents = r#"{ "database_path": "data/sensor_monitor.db", "api_listen": "127.0.0.1:0", "esp32_url": "192.168.1.50", "poll_interval_secs": 30 }"#;
+        std::fs::write(&config_path, contents).unwrap();
+        
+        let config = DaemonConfig::load(&config_path).unwrap();
+        assert_eq!(config.api_listen.as_deref(), Some("127.0.0.1:0"));
+        assert_eq!(config.preferences.len(), 2);
+        assert!(!config.preferences.contains_key("database_path"));
+        
+        config.apply_preferences(&dir.join("data").join("sensor_monitor.db")).unwrap();
+        assert_eq!(preferences::load_esp32_url().unwrap(), "192.168.1.50");
+        assert_eq!(preferences::load_poll_interval_secs().unwrap(), 30);
+        
+        preferences::save_poll_interval_secs(90).unwrap();
+        assert_eq!(preferences::load_poll_interval_secs().unwrap(), 90);
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), contents);
+        
+        let saved = std::fs::read_to_string(dir.join("data").join(preferences::PREFERENCES_FILE)).unwrap();
+        assert!(saved.contains("\"poll_interval_secs\": 90"));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,125 @@
+pub mod config;
+
+use anyhow::{Result, anyhow};
+use crate::data;
+use crate::server;
+use crate::worker::sensor_data_worker;
+use config::DaemonConfig;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::sync::oneshot;
+
+/// Run the collector without a GUI until SIGTERM or Ctrl+C.
+///
+/// Polling, storage and alerting are the same as in the app; alerts are written to the log
+/// and the in-app notification centre only, since there is no desktop to show them on.
+pub fn run(config_path: &Path) -> Result<()> {
+    let config = DaemonConfig::load(config_path)?;
+    init_logging(&config)?;
+    log::info!("Starting Sensor Monitor in headless mode with config {}", config_path.display());
+    
+    let database_path = config.database_path.clone().unwrap_or_else(data::get_database_path);
+    config.apply_preferences(&database_path)?;
+    data::initialize_database_at(&database_path)?;
+    
+    sensor_data_worker::start_worker()?;
+    
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(serve_until_shutdown(&config))?;
+    
+    // Let the current poll finish so no batch is half written
+    log::info!("Shutting down, waiting for the worker to finish");
+    sensor_data_worker::stop_worker()?;
+    log::info!("Sensor Monitor stopped");
+    
+    Ok(())
+}
+
+/// Serve the REST API, if configured, until a shutdown signal arrives
+async fn serve_until_shutdown(config: &DaemonConfig) -> Result<()> {
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    
+    let server = match &config.api_listen {
+        Some(listen) => {
+            let addr: SocketAddr = listen
+                .parse()
+                .map_err(|_| anyhow!("Invalid api_listen address '{}'", listen))?;
+            Some(server::start(addr, async {
+                let _ = stop_rx.await;
+            })?)
+        }
+        None => None,
+    };
+    
+    wait_for_shutdown().await?;
+    
+    let _ = stop_tx.send(());
+    if let Some(server) = server {
+        server.await??;
+    }
+    Ok(())
+}
+
+fn init_logging(config: &DaemonConfig) -> Result<()> {
+    let level = config.log_level.as_deref().unwrap_or("info");
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level));
+    
+    if let Some(path) = &config.log_file {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("Failed to open log file {}: {}", path.display(), e))?;
+        builder.target(env_logger::Target::Pipe(Box::new(TeeWriter { file })));
+    } else {
+        builder.target(env_logger::Target::Stdout);
+    }
+    
+    builder.try_init()?;
+    Ok(())
+}
+
+/// Log output that goes to stdout and a file
+struct TeeWriter {
+    file: File,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::stdout().write_all(buf)?;
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()?;
+        self.file.flush()
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = terminate.recv() => log::info!("Received SIGTERM"),
+        result = tokio::signal::ctrl_c() => {
+            result?;
+            log::info!("Received Ctrl+C");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    log::info!("Received Ctrl+C");
+    Ok(())
+}
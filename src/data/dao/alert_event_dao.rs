@@ -0,0 +1,232 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Error as SqlError, Row};
+use crate::data::get_database;
+use crate::model::alert::AlertEvent;
+
+const ALERT_COLUMNS: &str = "id, device_id, sensor_type, start_time, end_time, peak_value, threshold_breached, threshold_value, acknowledged_by, acknowledged_at, rule_id";
+
+/// Events exported per database lock; the lock is released while a page is written out
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+fn from_row(row: &Row) -> rusqlite::Result<AlertEvent> {
+    Ok(AlertEvent {
+        id: Some(row.get(0)?),
+        device_id: row.get(1)?,
+        sensor_type: row.get(2)?,
+        start_time: row.get(3)?,
+        end_time: row.get(4)?,
+        peak_value: row.get(5)?,
+        threshold_breached: row.get(6)?,
+        threshold_value: row.get(7)?,
+        acknowledged_by: row.get(8)?,
+        acknowledged_at: row.get(9)?,
+        rule_id: row.get(10)?,
+    })
+}
+
+pub fn insert(event: &AlertEvent) -> Result<i64> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "INSERT INTO alert_events (device_id, sensor_type, start_time, end_time, peak_value, threshold_breached, threshold_value, acknowledged_by, acknowledged_at, rule_id) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            event.device_id,
+            event.sensor_type,
+            event.start_time,
+            event.end_time,
+            event.peak_value,
+            event.threshold_breached,
+            event.threshold_value,
+            event.acknowledged_by,
+            event.acknowledged_at,
+            event.rule_id
+        ],
+    )?;
+    
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_peak(id: i64, peak_value: f32) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "UPDATE alert_events SET peak_value = ? WHERE id = ?",
+        params![peak_value, id],
+    )?;
+    
+    Ok(())
+}
+
+pub fn close(id: i64, end_time: i64) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "UPDATE alert_events SET end_time = ? WHERE id = ? AND end_time IS NULL",
+        params![end_time, id],
+    )?;
+    
+    Ok(())
+}
+
+/// Mark an event as seen; returns false if it was already acknowledged or does not exist
+pub fn acknowledge(id: i64, acknowledged_by: &str, acknowledged_at: i64) -> Result<bool> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let updated = conn.execute(
+        "UPDATE alert_events SET acknowledged_by = ?, acknowledged_at = ? WHERE id = ? AND acknowledged_at IS NULL",
+        params![acknowledged_by, acknowledged_at, id],
+    )?;
+    
+    Ok(updated > 0)
+}
+
+pub fn get_open_event(device_id: &str, sensor_type: &str) -> Result<Option<AlertEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} FROM alert_events 
+             WHERE device_id = ? AND sensor_type = ? AND rule_id IS NULL AND end_time IS NULL 
+             ORDER BY start_time DESC 
+             LIMIT 1",
+            ALERT_COLUMNS
+        ),
+        params![device_id, sensor_type],
+        from_row,
+    );
+    
+    match result {
+        Ok(event) => Ok(Some(event)),
+        Err(SqlError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
+
+pub fn get_open_rule_event(device_id: &str, rule_id: i64) -> Result<Option<AlertEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} FROM alert_events 
+             WHERE device_id = ? AND rule_id = ? AND end_time IS NULL 
+             ORDER BY start_time DESC 
+             LIMIT 1",
+            ALERT_COLUMNS
+        ),
+        params![device_id, rule_id],
+        from_row,
+    );
+    
+    match result {
+        Ok(event) => Ok(Some(event)),
+        Err(SqlError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
+
+/// Close every open event raised by a rule, e.g. when the rule is deleted
+pub fn close_rule_events(rule_id: i64, end_time: i64) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "UPDATE alert_events SET end_time = ? WHERE rule_id = ? AND end_time IS NULL",
+        params![end_time, rule_id],
+    )?;
+    
+    Ok(())
+}
+
+pub fn get_open_events() -> Result<Vec<AlertEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM alert_events WHERE end_time IS NULL ORDER BY start_time DESC",
+        ALERT_COLUMNS
+    ))?;
+    
+    let rows = stmt.query_map([], from_row)?;
+    
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row?);
+    }
+    
+    Ok(events)
+}
+
+pub fn get_closed_events(limit: i64) -> Result<Vec<AlertEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM alert_events WHERE end_time IS NOT NULL ORDER BY start_time DESC LIMIT ?",
+        ALERT_COLUMNS
+    ))?;
+    
+    let rows = stmt.query_map(params![limit], from_row)?;
+    
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row?);
+    }
+    
+    Ok(events)
+}
+
+/// Call `f` for every event starting in `[from, to)` (when given) for a device (when given),
+/// oldest first, without loading them all into memory.
+/// Events are read a page at a time and the database is unlocked while `f` handles a page.
+pub fn for_each_event<F>(device_id: Option<&str>, from: Option<i64>, to: Option<i64>, mut f: F) -> Result<()>
+where
+    F: FnMut(AlertEvent) -> Result<()>,
+{
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let sql = format!(
+        "SELECT {} FROM alert_events 
+         WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR start_time >= ?2) AND (?3 IS NULL OR start_time < ?3) 
+           AND (start_time, id) > (?4, ?5) 
+         ORDER BY start_time ASC, id ASC 
+         LIMIT ?6",
+        ALERT_COLUMNS
+    );
+    let mut after = (i64::MIN, i64::MIN);
+    
+    loop {
+        let page = {
+            let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+            let mut stmt = conn.prepare_cached(&sql)?;
+            
+            let rows = stmt.query_map(params![device_id, from, to, after.0, after.1, EXPORT_PAGE_SIZE], from_row)?;
+            
+            let mut events = Vec::new();
+            for row in rows {
+                events.push(row?);
+            }
+            events
+        };
+        
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = (last.start_time, last.id.unwrap_or_default());
+        let is_last_page = (page.len() as i64) < EXPORT_PAGE_SIZE;
+        
+        for event in page {
+            f(event)?;
+        }
+        if is_last_page {
+            break;
+        }
+    }
+    
+    Ok(())
+}
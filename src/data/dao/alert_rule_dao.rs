@@ -0,0 +1,88 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Row};
+use crate::data::get_database;
+use crate::model::alert_rule::AlertRule;
+
+fn from_row(row: &Row) -> rusqlite::Result<AlertRule> {
+    let conditions: String = row.get(3)?;
+    
+    Ok(AlertRule {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        device_id: row.get(2)?,
+        conditions: serde_json::from_str(&conditions).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        enabled: row.get::<_, i32>(4)? != 0,
+    })
+}
+
+pub fn get_all_rules() -> Result<Vec<AlertRule>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, device_id, conditions, enabled FROM alert_rules ORDER BY name, id"
+    )?;
+    
+    let rows = stmt.query_map([], from_row)?;
+    
+    let mut rules = Vec::new();
+    for row in rows {
+        rules.push(row?);
+    }
+    
+    Ok(rules)
+}
+
+pub fn get_enabled_rules() -> Result<Vec<AlertRule>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, device_id, conditions, enabled FROM alert_rules WHERE enabled = 1"
+    )?;
+    
+    let rows = stmt.query_map([], from_row)?;
+    
+    let mut rules = Vec::new();
+    for row in rows {
+        rules.push(row?);
+    }
+    
+    Ok(rules)
+}
+
+/// Insert a new rule or update an existing one, returning its id
+pub fn save_rule(rule: &AlertRule) -> Result<i64> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let conditions = serde_json::to_string(&rule.conditions)?;
+    
+    match rule.id {
+        Some(id) => {
+            conn.execute(
+                "UPDATE alert_rules SET name = ?, device_id = ?, conditions = ?, enabled = ? WHERE id = ?",
+                params![rule.name, rule.device_id, conditions, rule.enabled as i32, id],
+            )?;
+            Ok(id)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO alert_rules (name, device_id, conditions, enabled) VALUES (?, ?, ?, ?)",
+                params![rule.name, rule.device_id, conditions, rule.enabled as i32],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+pub fn delete_rule(id: i64) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute("DELETE FROM alert_rules WHERE id = ?", params![id])?;
+    
+    Ok(())
+}
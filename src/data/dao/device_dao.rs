@@ -0,0 +1,148 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Error as SqlError};
+use crate::data::get_database;
+use crate::model::device::{Device, DeviceStatusEvent};
+
+pub fn get_all_devices() -> Result<Vec<Device>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT id, name, url, location FROM devices ORDER BY name, id"
+    )?;
+    
+    let rows = stmt.query_map([], |row| {
+        Ok(Device {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            url: row.get(2)?,
+            location: row.get(3)?,
+        })
+    })?;
+    
+    let mut devices = Vec::new();
+    for row in rows {
+        devices.push(row?);
+    }
+    
+    Ok(devices)
+}
+
+pub fn save_device(device: &Device) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "INSERT OR REPLACE INTO devices (id, name, url, location) VALUES (?, ?, ?, ?)",
+        params![
+            device.id,
+            device.name,
+            device.url,
+            device.location
+        ],
+    )?;
+    
+    Ok(())
+}
+
+/// Register a device by id if it is not known yet, keeping existing details
+pub fn ensure_device(device_id: &str) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "INSERT OR IGNORE INTO devices (id, name, url, location) VALUES (?, ?, '', '')",
+        params![device_id, device_id],
+    )?;
+    
+    Ok(())
+}
+
+pub fn delete_device(device_id: &str) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute("DELETE FROM devices WHERE id = ?", params![device_id])?;
+    
+    Ok(())
+}
+
+pub fn insert_status_event(event: &DeviceStatusEvent) -> Result<i64> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "INSERT INTO device_status_events (device_id, status, timestamp, reason) VALUES (?, ?, ?, ?)",
+        params![
+            event.device_id,
+            event.status,
+            event.timestamp,
+            event.reason
+        ],
+    )?;
+    
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn get_latest_status(device_id: &str) -> Result<Option<DeviceStatusEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let result = conn.query_row(
+        "SELECT id, device_id, status, timestamp, reason 
+         FROM device_status_events 
+         WHERE device_id = ? 
+         ORDER BY timestamp DESC, id DESC 
+         LIMIT 1",
+        params![device_id],
+        |row| {
+            Ok(DeviceStatusEvent {
+                id: Some(row.get(0)?),
+                device_id: row.get(1)?,
+                status: row.get(2)?,
+                timestamp: row.get(3)?,
+                reason: row.get(4)?,
+            })
+        },
+    );
+    
+    match result {
+        Ok(event) => Ok(Some(event)),
+        Err(SqlError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
+
+/// Most recent status event of every device that has one
+pub fn get_latest_statuses() -> Result<Vec<DeviceStatusEvent>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.device_id, e.status, e.timestamp, e.reason
+         FROM device_status_events e
+         WHERE e.id = (
+            SELECT id FROM device_status_events
+            WHERE device_id = e.device_id
+            ORDER BY timestamp DESC, id DESC
+            LIMIT 1
+         )"
+    )?;
+    
+    let rows = stmt.query_map([], |row| {
+        Ok(DeviceStatusEvent {
+            id: Some(row.get(0)?),
+            device_id: row.get(1)?,
+            status: row.get(2)?,
+            timestamp: row.get(3)?,
+            reason: row.get(4)?,
+        })
+    })?;
+    
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row?);
+    }
+    
+    Ok(events)
+}
@@ -0,0 +1,35 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Error as SqlError};
+use crate::data::get_database;
+use crate::util::date_converter;
+
+/// Key of the last Firebase child stored from `source`
+pub fn get_last_key(source: &str) -> Result<Option<String>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let result = conn.query_row(
+        "SELECT last_key FROM firebase_sync WHERE source = ?",
+        params![source],
+        |row| row.get(0),
+    );
+    
+    match result {
+        Ok(key) => Ok(Some(key)),
+        Err(SqlError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
+
+/// Remember that the child `key` with reading time `timestamp` has been stored from `source`
+pub fn save_position(source: &str, key: &str, timestamp: i64) -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute(
+        "INSERT OR REPLACE INTO firebase_sync (source, last_key, last_timestamp, updated_at) VALUES (?, ?, ?, ?)",
+        params![source, key, timestamp, date_converter::current_timestamp()],
+    )?;
+    
+    Ok(())
+}
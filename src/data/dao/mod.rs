@@ -1,2 +1,7 @@
+pub mod alert_event_dao;
+pub mod alert_rule_dao;
+pub mod device_dao;
+pub mod firebase_sync_dao;
+pub mod retention_dao;
 pub mod sensor_reading_dao;
 pub mod sensor_threshold_dao; 
\ No newline at end of file
@@ -0,0 +1,341 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, Transaction};
+use crate::data::get_database;
+use crate::model::export::ReadingFilter;
+use crate::model::sensor_data::{RollupReading, SensorReading};
+
+/// Raw readings are averaged into five-minute buckets
+pub const RAW_ROLLUP_TABLE: &str = "sensor_readings_5m";
+pub const RAW_ROLLUP_BUCKET_MS: i64 = 5 * 60 * 1000;
+
+/// Five-minute buckets are averaged into hourly buckets that are kept forever
+pub const HOURLY_ROLLUP_TABLE: &str = "sensor_readings_1h";
+pub const HOURLY_ROLLUP_BUCKET_MS: i64 = 60 * 60 * 1000;
+
+/// Buckets exported per database lock; the lock is released while a page is written out
+const EXPORT_PAGE_SIZE: i64 = 5000;
+
+/// Rows removed from each tier by one compaction run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactionCounts {
+    pub raw_rows: usize,
+    pub rollup_rows: usize,
+}
+
+/// Fold raw readings older than `raw_cutoff` into five-minute buckets and five-minute
+/// buckets older than `rollup_cutoff` into hourly ones, deleting what was folded.
+/// Both cutoffs are aligned down to their bucket size so buckets are never split.
+pub fn compact(raw_cutoff: i64, rollup_cutoff: i64) -> Result<CompactionCounts> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let mut conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let raw_cutoff = raw_cutoff - raw_cutoff.rem_euclid(RAW_ROLLUP_BUCKET_MS);
+    let rollup_cutoff = rollup_cutoff - rollup_cutoff.rem_euclid(HOURLY_ROLLUP_BUCKET_MS);
+    
+    let tx = conn.transaction()?;
+    
+    tx.execute(
+        &format!(
+            "INSERT INTO {table} (device_id, sensor_type, bucket_start, avg_value, min_value, max_value, sample_count)
+             SELECT device_id, sensor_type, timestamp - timestamp % ?1, AVG(value), MIN(value), MAX(value), COUNT(*)
+             FROM sensor_readings
+             WHERE timestamp < ?2
+             GROUP BY device_id, sensor_type, timestamp - timestamp % ?1
+             {merge}",
+            table = RAW_ROLLUP_TABLE,
+            merge = MERGE_BUCKET,
+        ),
+        params![RAW_ROLLUP_BUCKET_MS, raw_cutoff],
+    )?;
+    let raw_rows = tx.execute("DELETE FROM sensor_readings WHERE timestamp < ?", params![raw_cutoff])?;
+    
+    tx.execute(
+        &format!(
+            "INSERT INTO {target} (device_id, sensor_type, bucket_start, avg_value, min_value, max_value, sample_count)
+             SELECT device_id, sensor_type, bucket_start - bucket_start % ?1,
+                    SUM(avg_value * sample_count) / SUM(sample_count), MIN(min_value), MAX(max_value), SUM(sample_count)
+             FROM {source}
+             WHERE bucket_start < ?2
+             GROUP BY device_id, sensor_type, bucket_start - bucket_start % ?1
+             {merge}",
+            target = HOURLY_ROLLUP_TABLE,
+            source = RAW_ROLLUP_TABLE,
+            merge = MERGE_BUCKET,
+        ),
+        params![HOURLY_ROLLUP_BUCKET_MS, rollup_cutoff],
+    )?;
+    let rollup_rows = tx.execute(
+        &format!("DELETE FROM {} WHERE bucket_start < ?", RAW_ROLLUP_TABLE),
+        params![rollup_cutoff],
+    )?;
+    
+    tx.commit()?;
+    
+    Ok(CompactionCounts { raw_rows, rollup_rows })
+}
+
+/// Fold readings straight into a rollup table whose buckets are `bucket_ms` long, merging
+/// them into buckets that already exist. Returns the number of readings folded.
+pub fn fold_readings(table: &str, bucket_ms: i64, readings: &[SensorReading]) -> Result<usize> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let mut conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(&format!(
+            "INSERT INTO {table} (device_id, sensor_type, bucket_start, avg_value, min_value, max_value, sample_count)
+             VALUES (?1, ?2, ?3, ?4, ?4, ?4, 1)
+             {merge}",
+            table = table,
+            merge = MERGE_BUCKET,
+        ))?;
+        for reading in readings {
+            let bucket_start = reading.timestamp - reading.timestamp.rem_euclid(bucket_ms);
+            stmt.execute(params![reading.device_id, reading.sensor_type, bucket_start, reading.value])?;
+        }
+    }
+    tx.commit()?;
+    
+    Ok(readings.len())
+}
+
+/// Late readings can land in a bucket that already exists; merge them weighted by sample count
+const MERGE_BUCKET: &str = "ON CONFLICT (device_id, sensor_type, bucket_start) DO UPDATE SET
+        avg_value = (avg_value * sample_count + excluded.avg_value * excluded.sample_count)
+                    / (sample_count + excluded.sample_count),
+        min_value = MIN(min_value, excluded.min_value),
+        max_value = MAX(max_value, excluded.max_value),
+        sample_count = sample_count + excluded.sample_count";
+
+/// Remove every reading, rollup and alert event; devices, thresholds and rules are kept.
+/// Returns the number of raw readings removed.
+pub fn delete_all_data() -> Result<usize> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let mut conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM sensor_readings", [])?;
+    tx.execute(&format!("DELETE FROM {}", RAW_ROLLUP_TABLE), [])?;
+    tx.execute(&format!("DELETE FROM {}", HOURLY_ROLLUP_TABLE), [])?;
+    tx.execute("DELETE FROM alert_events", [])?;
+    tx.commit()?;
+    
+    Ok(deleted)
+}
+
+/// Remove readings and rollups with `from <= timestamp < to`, for one device or all of them.
+/// Returns the number of rows removed across all tiers.
+pub fn delete_range(device_id: Option<&str>, from: i64, to: i64) -> Result<usize> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let mut conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let tx = conn.transaction()?;
+    let mut deleted = delete_range_in(&tx, "sensor_readings", "timestamp", device_id, from, to)?;
+    deleted += delete_range_in(&tx, RAW_ROLLUP_TABLE, "bucket_start", device_id, from, to)?;
+    deleted += delete_range_in(&tx, HOURLY_ROLLUP_TABLE, "bucket_start", device_id, from, to)?;
+    tx.commit()?;
+    
+    Ok(deleted)
+}
+
+fn delete_range_in(tx: &Transaction, table: &str, column: &str, device_id: Option<&str>, from: i64, to: i64) -> Result<usize> {
+    let sql = format!(
+        "DELETE FROM {table} WHERE {column} >= ?1 AND {column} < ?2 AND (?3 IS NULL OR device_id = ?3)",
+        table = table,
+        column = column,
+    );
+    Ok(tx.execute(&sql, params![from, to, device_id])?)
+}
+
+/// Rebuild the database file to give back the space freed by deletes and compaction
+pub fn vacuum() -> Result<()> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    conn.execute_batch("VACUUM")?;
+    Ok(())
+}
+
+/// Oldest and newest `bucket_start` in a rollup table among buckets matching `filter`
+pub fn get_rollup_time_bounds(table: &str, filter: &ReadingFilter) -> Result<Option<(i64, i64)>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let bounds: (Option<i64>, Option<i64>) = conn.query_row(
+        &format!(
+            "SELECT MIN(bucket_start), MAX(bucket_start) 
+             FROM {} 
+             WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR sensor_type = ?2) 
+               AND (?3 IS NULL OR bucket_start >= ?3) AND (?4 IS NULL OR bucket_start < ?4)",
+            table
+        ),
+        params![filter.device_id, filter.sensor_type, filter.from, filter.to],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    Ok(bounds.0.zip(bounds.1))
+}
+
+/// Call `f` for every bucket of a rollup table matching `filter`, ordered by device, sensor and time.
+/// Buckets are read a page at a time and the database is unlocked while `f` handles a page.
+pub fn for_each_rollup<F>(table: &str, filter: &ReadingFilter, mut f: F) -> Result<()>
+where
+    F: FnMut(RollupReading) -> Result<()>,
+{
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let sql = format!(
+        "SELECT device_id, sensor_type, bucket_start, avg_value, min_value, max_value, sample_count 
+         FROM {} 
+         WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR sensor_type = ?2) 
+           AND (?3 IS NULL OR bucket_start >= ?3) AND (?4 IS NULL OR bucket_start < ?4) 
+           AND (device_id, sensor_type, bucket_start) > (?5, ?6, ?7) 
+         ORDER BY device_id, sensor_type, bucket_start 
+         LIMIT ?8",
+        table
+    );
+    let mut after = (String::new(), String::new(), i64::MIN);
+    
+    loop {
+        let page = {
+            let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+            let mut stmt = conn.prepare_cached(&sql)?;
+            
+            let rows = stmt.query_map(
+                params![filter.device_id, filter.sensor_type, filter.from, filter.to, after.0, after.1, after.2, EXPORT_PAGE_SIZE],
+                |row| {
+                    Ok(RollupReading {
+                        device_id: row.get(0)?,
+                        sensor_type: row.get(1)?,
+                        bucket_start: row.get(2)?,
+                        avg_value: row.get(3)?,
+                        min_value: row.get(4)?,
+                        max_value: row.get(5)?,
+                        sample_count: row.get(6)?,
+                    })
+                },
+            )?;
+            
+            let mut rollups = Vec::new();
+            for row in rows {
+                rollups.push(row?);
+            }
+            rollups
+        };
+        
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = (last.device_id.clone(), last.sensor_type.clone(), last.bucket_start);
+        let is_last_page = (page.len() as i64) < EXPORT_PAGE_SIZE;
+        
+        for rollup in page {
+            f(rollup)?;
+        }
+        if is_last_page {
+            break;
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{self, dao::sensor_reading_dao};
+    use crate::model::sensor_types;
+    
+    /// Hour-aligned and older than anything other tests store, so global compaction
+    /// and deletes below it only touch this module's rows
+    const T0: i64 = 900_000_000_000;
+    const MINUTE: i64 = 60_000;
+    const HOUR: i64 = 60 * MINUTE;
+    
+    fn reading(device_id: &str, timestamp: i64, value: f32) -> SensorReading {
+        SensorReading::new(device_id, sensor_types::TEMPERATURE, value, timestamp, false)
+    }
+    
+    /// `(bucket_start, avg, min, max, samples)` of a device's buckets in a rollup table
+    fn buckets(table: &str, device_id: &str) -> Vec<(i64, f32, f32, f32, i64)> {
+        let filter = ReadingFilter { device_id: Some(device_id.to_string()), ..Default::default() };
+        let mut buckets = Vec::new();
+        for_each_rollup(table, &filter, |r| {
+            buckets.push((r.bucket_start, r.avg_value, r.min_value, r.max_value, r.sample_count));
+            Ok(())
+        }).unwrap();
+        buckets
+    }
+    
+    fn raw_timestamps(device_id: &str) -> Vec<i64> {
+        let filter = ReadingFilter { device_id: Some(device_id.to_string()), ..Default::default() };
+        let mut timestamps = Vec::new();
+        sensor_reading_dao::for_each_reading(&filter, |r| {
+            timestamps.push(r.timestamp);
+            Ok(())
+        }).unwrap();
+        timestamps
+    }
+    
+    #[test]
+    fn compaction_merges_into_existing_buckets() {
+        data::init_test_database();
+        let device_id = "compact-test";
+        
+        // Buckets that already hold a sample, e.g. from an earlier run or an import
+        fold_readings(RAW_ROLLUP_TABLE, RAW_ROLLUP_BUCKET_MS, &[reading(device_id, T0, 10.0)]).unwrap();
+        fold_readings(HOURLY_ROLLUP_TABLE, HOURLY_ROLLUP_BUCKET_MS, &[reading(device_id, T0 + 30 * MINUTE, 7.5)]).unwrap();
+        sensor_reading_dao::insert_batch(&[
+            reading(device_id, T0 + MINUTE, 20.0),
+            reading(device_id, T0 + 2 * MINUTE, 30.0),
+            reading(device_id, T0 + 10 * MINUTE, 50.0),
+            reading(device_id, T0 + 2 * HOUR, 99.0),
+        ]).unwrap();
+        
+        // The raw cutoff is aligned down to T0 + 1 h, so the reading at 2 h stays raw
+        let counts = compact(T0 + HOUR + 123, T0 - HOUR).unwrap();
+        
+        assert_eq!((counts.raw_rows, counts.rollup_rows), (3, 0));
+        assert_eq!(raw_timestamps(device_id), vec![T0 + 2 * HOUR]);
+        assert_eq!(buckets(RAW_ROLLUP_TABLE, device_id), vec![
+            (T0, 20.0, 10.0, 30.0, 3),
+            (T0 + 10 * MINUTE, 50.0, 50.0, 50.0, 1),
+        ]);
+        
+        let counts = compact(T0 + HOUR, T0 + HOUR).unwrap();
+        
+        assert_eq!((counts.raw_rows, counts.rollup_rows), (0, 2));
+        assert!(buckets(RAW_ROLLUP_TABLE, device_id).is_empty());
+        // Averages are weighted by sample count: (20 * 3 + 50 + 7.5) / 5
+        assert_eq!(buckets(HOURLY_ROLLUP_TABLE, device_id), vec![(T0, 23.5, 7.5, 50.0, 5)]);
+    }
+    
+    #[test]
+    fn delete_range_removes_every_tier() {
+        data::init_test_database();
+        let start = T0 + 24 * HOUR;
+        let seed = |device_id: &str| {
+            for timestamp in [start - HOUR, start, start + HOUR] {
+                sensor_reading_dao::insert_batch(&[reading(device_id, timestamp, 1.0)]).unwrap();
+                fold_readings(RAW_ROLLUP_TABLE, RAW_ROLLUP_BUCKET_MS, &[reading(device_id, timestamp, 1.0)]).unwrap();
+                fold_readings(HOURLY_ROLLUP_TABLE, HOURLY_ROLLUP_BUCKET_MS, &[reading(device_id, timestamp, 1.0)]).unwrap();
+            }
+        };
+        seed("delete-range-a");
+        seed("delete-range-b");
+        
+        assert_eq!(delete_range(Some("delete-range-a"), start, start + HOUR).unwrap(), 3);
+        
+        assert_eq!(raw_timestamps("delete-range-a"), vec![start - HOUR, start + HOUR]);
+        for table in [RAW_ROLLUP_TABLE, HOURLY_ROLLUP_TABLE] {
+            let starts: Vec<_> = buckets(table, "delete-range-a").iter().map(|b| b.0).collect();
+            assert_eq!(starts, vec![start - HOUR, start + HOUR]);
+            assert_eq!(buckets(table, "delete-range-b").len(), 3);
+        }
+        assert_eq!(raw_timestamps("delete-range-b").len(), 3);
+        
+        // Without a device the range is cleared for all of them
+        assert_eq!(delete_range(None, start - HOUR, start + 2 * HOUR).unwrap(), 6 + 9);
+        assert!(raw_timestamps("delete-range-a").is_empty());
+        assert!(buckets(HOURLY_ROLLUP_TABLE, "delete-range-b").is_empty());
+    }
+}
@@ -1,15 +1,99 @@
 use anyhow::{Result, anyhow};
 use rusqlite::{Connection, params};
-use crate::model::sensor_data::SensorReading;
+use crate::model::export::ReadingFilter;
+use crate::model::sensor_data::{AggregatedReading, SensorReading};
 use crate::data::get_database;
 
+/// Readings exported per database lock; the lock is released while a page is written out
+const EXPORT_PAGE_SIZE: i64 = 5000;
+
+/// Aggregates of one sensor's readings over a time range; the values are `None` when there are none
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RangeSummary {
+    pub count: i64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub mean_of_squares: Option<f64>,
+}
+
+/// One page of readings matching an export filter, after a (timestamp, id) position
+pub(crate) const EXPORT_PAGE_SQL: &str =
+    "SELECT id, device_id, sensor_type, value, timestamp, is_alert 
+     FROM sensor_readings 
+     WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR sensor_type = ?2) 
+       AND (?3 IS NULL OR timestamp >= ?3) AND (?4 IS NULL OR timestamp < ?4) 
+       AND (timestamp, id) > (?5, ?6) 
+     ORDER BY timestamp, id 
+     LIMIT ?7";
+
+/// Newest reading of every sensor of a device.
+/// The sensor types are walked with a recursive skip-scan over the
+/// (device_id, sensor_type, timestamp) index, so the cost grows with the
+/// number of sensors rather than the number of rows.
+pub(crate) const LATEST_READINGS_SQL: &str =
+    "WITH RECURSIVE types(sensor_type) AS (
+        SELECT MIN(sensor_type) FROM sensor_readings WHERE device_id = ?1
+        UNION ALL
+        SELECT (SELECT MIN(sensor_type) FROM sensor_readings
+                WHERE device_id = ?1 AND sensor_type > types.sensor_type)
+        FROM types WHERE types.sensor_type IS NOT NULL
+     )
+     SELECT sr.id, sr.device_id, sr.sensor_type, sr.value, sr.timestamp, sr.is_alert
+     FROM types
+     INNER JOIN sensor_readings sr ON sr.id = (
+        SELECT id FROM sensor_readings
+        WHERE device_id = ?1 AND sensor_type = types.sensor_type
+        ORDER BY timestamp DESC
+        LIMIT 1
+     )";
+
+/// Readings of one sensor older than a timestamp, newest first
+pub(crate) const HISTORY_BEFORE_SQL: &str =
+    "SELECT id, device_id, sensor_type, value, timestamp, is_alert 
+     FROM sensor_readings 
+     WHERE device_id = ?1 AND sensor_type = ?2 AND timestamp < ?3 
+     ORDER BY timestamp DESC 
+     LIMIT ?4";
+
+/// Readings of one sensor in a half-open time range, oldest first
+pub(crate) const READINGS_IN_RANGE_SQL: &str =
+    "SELECT id, device_id, sensor_type, value, timestamp, is_alert 
+     FROM sensor_readings 
+     WHERE device_id = ?1 AND sensor_type = ?2 AND timestamp >= ?3 AND timestamp < ?4 
+     ORDER BY timestamp ASC";
+
+/// Per-bucket min/max/avg/count of one sensor over a time range.
+/// Raw readings are combined with the five-minute and hourly rollups so ranges
+/// reaching past the raw retention period still have data; averages are weighted
+/// by sample count.
+pub(crate) const AGGREGATED_IN_RANGE_SQL: &str =
+    "SELECT bucket, MIN(min_value), MAX(max_value), SUM(avg_value * samples) / SUM(samples), SUM(samples), SUM(alerts)
+     FROM (
+        SELECT timestamp - timestamp % ?5 AS bucket, value AS min_value, value AS max_value,
+               value AS avg_value, 1 AS samples, is_alert AS alerts
+        FROM sensor_readings
+        WHERE device_id = ?1 AND sensor_type = ?2 AND timestamp >= ?3 AND timestamp < ?4
+        UNION ALL
+        SELECT bucket_start - bucket_start % ?5, min_value, max_value, avg_value, sample_count, 0
+        FROM sensor_readings_5m
+        WHERE device_id = ?1 AND sensor_type = ?2 AND bucket_start >= ?3 AND bucket_start < ?4
+        UNION ALL
+        SELECT bucket_start - bucket_start % ?5, min_value, max_value, avg_value, sample_count, 0
+        FROM sensor_readings_1h
+        WHERE device_id = ?1 AND sensor_type = ?2 AND bucket_start >= ?3 AND bucket_start < ?4
+     )
+     GROUP BY bucket
+     ORDER BY bucket ASC";
+
 pub fn insert(reading: &SensorReading) -> Result<i64> {
     let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
     conn.execute(
-        "INSERT INTO sensor_readings (sensor_type, value, timestamp, is_alert) VALUES (?, ?, ?, ?)",
+        "INSERT INTO sensor_readings (device_id, sensor_type, value, timestamp, is_alert) VALUES (?, ?, ?, ?, ?)",
         params![
+            reading.device_id,
             reading.sensor_type,
             reading.value,
             reading.timestamp,
@@ -20,76 +104,78 @@ pub fn insert(reading: &SensorReading) -> Result<i64> {
     Ok(conn.last_insert_rowid())
 }
 
-pub fn insert_batch(readings: &[SensorReading]) -> Result<()> {
+/// Store readings in one transaction, skipping any already stored for the same device, sensor
+/// and timestamp. Returns the readings that were new, with their ids.
+pub fn insert_batch(readings: &[SensorReading]) -> Result<Vec<SensorReading>> {
     let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
-    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    let mut conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
     let tx = conn.transaction()?;
+    let mut inserted = Vec::new();
     
     for reading in readings {
-        tx.execute(
-            "INSERT INTO sensor_readings (sensor_type, value, timestamp, is_alert) VALUES (?, ?, ?, ?)",
+        let changed = tx.execute(
+            "INSERT OR IGNORE INTO sensor_readings (device_id, sensor_type, value, timestamp, is_alert) VALUES (?, ?, ?, ?, ?)",
             params![
+                reading.device_id,
                 reading.sensor_type,
                 reading.value,
                 reading.timestamp,
                 reading.is_alert as i32
             ],
         )?;
+        if changed > 0 {
+            inserted.push(SensorReading {
+                id: Some(tx.last_insert_rowid()),
+                ..reading.clone()
+            });
+        }
     }
     
     tx.commit()?;
-    Ok(())
+    Ok(inserted)
 }
 
-pub fn get_latest_by_type(sensor_type: &str) -> Result<Option<SensorReading>> {
+pub fn get_latest_readings(device_id: &str) -> Result<Vec<SensorReading>> {
     let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
-    let mut stmt = conn.prepare(
-        "SELECT id, sensor_type, value, timestamp, is_alert 
-         FROM sensor_readings 
-         WHERE sensor_type = ? 
-         ORDER BY timestamp DESC 
-         LIMIT 1"
-    )?;
-    
-    let mut rows = stmt.query(params![sensor_type])?;
+    let mut stmt = conn.prepare(LATEST_READINGS_SQL)?;
     
-    if let Some(row) = rows.next()? {
-        Ok(Some(SensorReading {
+    let rows = stmt.query_map(params![device_id], |row| {
+        Ok(SensorReading {
             id: Some(row.get(0)?),
-            sensor_type: row.get(1)?,
-            value: row.get(2)?,
-            timestamp: row.get(3)?,
-            is_alert: row.get::<_, i32>(4)? != 0,
-        }))
-    } else {
-        Ok(None)
+            device_id: row.get(1)?,
+            sensor_type: row.get(2)?,
+            value: row.get(3)?,
+            timestamp: row.get(4)?,
+            is_alert: row.get::<_, i32>(5)? != 0,
+        })
+    })?;
+    
+    let mut readings = Vec::new();
+    for row in rows {
+        readings.push(row?);
     }
+    
+    Ok(readings)
 }
 
-pub fn get_latest_readings() -> Result<Vec<SensorReading>> {
+/// Readings of a sensor strictly older than `before_timestamp`, newest first
+pub fn get_history_before(device_id: &str, sensor_type: &str, before_timestamp: i64, limit: i64) -> Result<Vec<SensorReading>> {
     let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
-    let mut stmt = conn.prepare(
-        "SELECT sr.id, sr.sensor_type, sr.value, sr.timestamp, sr.is_alert
-         FROM sensor_readings sr
-         INNER JOIN (
-            SELECT sensor_type, MAX(timestamp) as max_timestamp
-            FROM sensor_readings
-            GROUP BY sensor_type
-         ) latest ON sr.sensor_type = latest.sensor_type AND sr.timestamp = latest.max_timestamp"
-    )?;
+    let mut stmt = conn.prepare(HISTORY_BEFORE_SQL)?;
     
-    let rows = stmt.query_map([], |row| {
+    let rows = stmt.query_map(params![device_id, sensor_type, before_timestamp, limit], |row| {
         Ok(SensorReading {
             id: Some(row.get(0)?),
-            sensor_type: row.get(1)?,
-            value: row.get(2)?,
-            timestamp: row.get(3)?,
-            is_alert: row.get::<_, i32>(4)? != 0,
+            device_id: row.get(1)?,
+            sensor_type: row.get(2)?,
+            value: row.get(3)?,
+            timestamp: row.get(4)?,
+            is_alert: row.get::<_, i32>(5)? != 0,
         })
     })?;
     
@@ -101,25 +187,21 @@ pub fn get_latest_readings() -> Result<Vec<SensorReading>> {
     Ok(readings)
 }
 
-pub fn get_history_by_type(sensor_type: &str, limit: i64) -> Result<Vec<SensorReading>> {
+/// Readings of a sensor with `from <= timestamp < to`, oldest first
+pub fn get_readings_in_range(device_id: &str, sensor_type: &str, from: i64, to: i64) -> Result<Vec<SensorReading>> {
     let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
-    let mut stmt = conn.prepare(
-        "SELECT id, sensor_type, value, timestamp, is_alert 
-         FROM sensor_readings 
-         WHERE sensor_type = ? 
-         ORDER BY timestamp DESC 
-         LIMIT ?"
-    )?;
+    let mut stmt = conn.prepare(READINGS_IN_RANGE_SQL)?;
     
-    let rows = stmt.query_map(params![sensor_type, limit], |row| {
+    let rows = stmt.query_map(params![device_id, sensor_type, from, to], |row| {
         Ok(SensorReading {
             id: Some(row.get(0)?),
-            sensor_type: row.get(1)?,
-            value: row.get(2)?,
-            timestamp: row.get(3)?,
-            is_alert: row.get::<_, i32>(4)? != 0,
+            device_id: row.get(1)?,
+            sensor_type: row.get(2)?,
+            value: row.get(3)?,
+            timestamp: row.get(4)?,
+            is_alert: row.get::<_, i32>(5)? != 0,
         })
     })?;
     
@@ -129,4 +211,173 @@ pub fn get_history_by_type(sensor_type: &str, limit: i64) -> Result<Vec<SensorRe
     }
     
     Ok(readings)
-} 
\ No newline at end of file
+}
+
+/// Readings of a sensor with `from <= timestamp < to` aggregated into buckets of `bucket_ms`, oldest first
+pub fn get_aggregated_in_range(device_id: &str, sensor_type: &str, from: i64, to: i64, bucket_ms: i64) -> Result<Vec<AggregatedReading>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(AGGREGATED_IN_RANGE_SQL)?;
+    
+    let rows = stmt.query_map(params![device_id, sensor_type, from, to, bucket_ms], |row| {
+        Ok(AggregatedReading {
+            bucket_start: row.get(0)?,
+            min_value: row.get(1)?,
+            max_value: row.get(2)?,
+            avg_value: row.get::<_, f64>(3)? as f32,
+            count: row.get(4)?,
+            alert_count: row.get(5)?,
+        })
+    })?;
+    
+    let mut buckets = Vec::new();
+    for row in rows {
+        buckets.push(row?);
+    }
+    
+    Ok(buckets)
+}
+
+/// Summary of a sensor's readings with `from <= timestamp < to`
+pub fn get_range_summary(device_id: &str, sensor_type: &str, from: i64, to: i64) -> Result<RangeSummary> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let summary = conn.query_row(
+        "SELECT COUNT(*), MIN(value), MAX(value), AVG(value), AVG(value * value) 
+         FROM sensor_readings 
+         WHERE device_id = ? AND sensor_type = ? AND timestamp >= ? AND timestamp < ?",
+        params![device_id, sensor_type, from, to],
+        |row| {
+            Ok(RangeSummary {
+                count: row.get(0)?,
+                min: row.get(1)?,
+                max: row.get(2)?,
+                mean: row.get(3)?,
+                mean_of_squares: row.get(4)?,
+            })
+        },
+    )?;
+    
+    Ok(summary)
+}
+
+/// The value at position `rank` (0-based) when a sensor's readings in `[from, to)` are sorted ascending
+pub fn get_value_at_rank(device_id: &str, sensor_type: &str, from: i64, to: i64, rank: i64) -> Result<Option<f32>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT value 
+         FROM sensor_readings 
+         WHERE device_id = ? AND sensor_type = ? AND timestamp >= ? AND timestamp < ? 
+         ORDER BY value 
+         LIMIT 1 OFFSET ?"
+    )?;
+    
+    let mut rows = stmt.query(params![device_id, sensor_type, from, to, rank])?;
+    
+    if let Some(row) = rows.next()? {
+        Ok(Some(row.get(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Time (ms) a sensor spent above `max_value`, below `min_value`, flagged as alert, and in total
+/// within `[from, to)`. Each reading lasts until the next one, at most `max_gap_ms`.
+pub fn get_state_durations(device_id: &str, sensor_type: &str, from: i64, to: i64, min_value: f32, max_value: f32, max_gap_ms: i64) -> Result<(i64, i64, i64, i64)> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let durations = conn.query_row(
+        "SELECT 
+            COALESCE(SUM(CASE WHEN value > ?5 THEN duration ELSE 0 END), 0),
+            COALESCE(SUM(CASE WHEN value < ?6 THEN duration ELSE 0 END), 0),
+            COALESCE(SUM(CASE WHEN is_alert != 0 THEN duration ELSE 0 END), 0),
+            COALESCE(SUM(duration), 0)
+         FROM (
+            SELECT value, is_alert,
+                   MIN(COALESCE(LEAD(timestamp) OVER (ORDER BY timestamp), ?4) - timestamp, ?7) AS duration
+            FROM sensor_readings 
+            WHERE device_id = ?1 AND sensor_type = ?2 AND timestamp >= ?3 AND timestamp < ?4
+         )",
+        params![device_id, sensor_type, from, to, max_value, min_value, max_gap_ms],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    
+    Ok(durations)
+}
+
+/// Timestamps of the oldest and newest readings matching `filter`, if there are any
+pub fn get_time_bounds(filter: &ReadingFilter) -> Result<Option<(i64, i64)>> {
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+    
+    let bounds: (Option<i64>, Option<i64>) = conn.query_row(
+        "SELECT MIN(timestamp), MAX(timestamp) 
+         FROM sensor_readings 
+         WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR sensor_type = ?2) 
+           AND (?3 IS NULL OR timestamp >= ?3) AND (?4 IS NULL OR timestamp < ?4)",
+        params![filter.device_id, filter.sensor_type, filter.from, filter.to],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    Ok(bounds.0.zip(bounds.1))
+}
+
+/// Call `f` for every reading matching `filter`, ordered by timestamp then id,
+/// without loading them all into memory.
+///
+/// Readings are read a page at a time and the database is unlocked while `f` handles a page,
+/// so a long export does not hold up ingestion.
+pub fn for_each_reading<F>(filter: &ReadingFilter, mut f: F) -> Result<()>
+where
+    F: FnMut(SensorReading) -> Result<()>,
+{
+    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+    let mut after = (i64::MIN, i64::MIN);
+    
+    loop {
+        let page = {
+            let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+            let mut stmt = conn.prepare_cached(EXPORT_PAGE_SQL)?;
+            
+            let rows = stmt.query_map(
+                params![filter.device_id, filter.sensor_type, filter.from, filter.to, after.0, after.1, EXPORT_PAGE_SIZE],
+                |row| {
+                    Ok(SensorReading {
+                        id: Some(row.get(0)?),
+                        device_id: row.get(1)?,
+                        sensor_type: row.get(2)?,
+                        value: row.get(3)?,
+                        timestamp: row.get(4)?,
+                        is_alert: row.get::<_, i32>(5)? != 0,
+                    })
+                },
+            )?;
+            
+            let mut readings = Vec::new();
+            for row in rows {
+                readings.push(row?);
+            }
+            readings
+        };
+        
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = (last.timestamp, last.id.unwrap_or_default());
+        let is_last_page = (page.len() as i64) < EXPORT_PAGE_SIZE;
+        
+        for reading in page {
+            f(reading)?;
+        }
+        if is_last_page {
+            break;
+        }
+    }
+    
+    Ok(())
+}
@@ -2,39 +2,37 @@ use anyhow::{Result, anyhow};
 use rusqlite::{Connection, params, Error as SqlError};
 use crate::data::get_database;
 use crate::model::sensor_data::SensorThreshold;
-use crate::model::sensor_types;
 
 pub fn get_threshold(sensor_type: &str) -> Result<SensorThreshold> {
-    let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
-    let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT sensor_type, min_value, max_value 
-         FROM sensor_thresholds 
-         WHERE sensor_type = ?"
-    )?;
-    
-    let result = stmt.query_row(params![sensor_type], |row| {
-        Ok(SensorThreshold {
-            sensor_type: row.get(0)?,
-            min_value: row.get(1)?,
-            max_value: row.get(2)?,
+    let result = {
+        let db = get_database().ok_or_else(|| anyhow!("Database not initialized"))?;
+        let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT sensor_type, min_value, max_value, hysteresis, min_alert_readings, min_alert_duration_secs 
+             FROM sensor_thresholds 
+             WHERE sensor_type = ?"
+        )?;
+        
+        stmt.query_row(params![sensor_type], |row| {
+            Ok(SensorThreshold {
+                sensor_type: row.get(0)?,
+                min_value: row.get(1)?,
+                max_value: row.get(2)?,
+                hysteresis: row.get(3)?,
+                min_alert_readings: row.get(4)?,
+                min_alert_duration_secs: row.get(5)?,
+            })
         })
-    });
+    };
     
     match result {
         Ok(threshold) => Ok(threshold),
         Err(SqlError::QueryReturnedNoRows) => {
             // Use default thresholds if not set
-            let (min_value, max_value) = sensor_types::get_default_threshold(sensor_type);
-            
-            let threshold = SensorThreshold {
-                sensor_type: sensor_type.to_string(),
-                min_value,
-                max_value,
-            };
+            let threshold = SensorThreshold::default_for(sensor_type);
             
-            // Save default threshold
+            // Save default threshold (the lock above has been released by now)
             set_threshold(&threshold)?;
             
             Ok(threshold)
@@ -48,11 +46,15 @@ pub fn set_threshold(threshold: &SensorThreshold) -> Result<()> {
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
     conn.execute(
-        "INSERT OR REPLACE INTO sensor_thresholds (sensor_type, min_value, max_value) VALUES (?, ?, ?)",
+        "INSERT OR REPLACE INTO sensor_thresholds (sensor_type, min_value, max_value, hysteresis, min_alert_readings, min_alert_duration_secs) 
+         VALUES (?, ?, ?, ?, ?, ?)",
         params![
             threshold.sensor_type,
             threshold.min_value,
-            threshold.max_value
+            threshold.max_value,
+            threshold.hysteresis,
+            threshold.min_alert_readings,
+            threshold.min_alert_duration_secs
         ],
     )?;
     
@@ -64,7 +66,8 @@ pub fn get_all_thresholds() -> Result<Vec<SensorThreshold>> {
     let conn = db.lock().map_err(|_| anyhow!("Failed to lock database"))?;
     
     let mut stmt = conn.prepare(
-        "SELECT sensor_type, min_value, max_value FROM sensor_thresholds"
+        "SELECT sensor_type, min_value, max_value, hysteresis, min_alert_readings, min_alert_duration_secs 
+         FROM sensor_thresholds"
     )?;
     
     let rows = stmt.query_map([], |row| {
@@ -72,6 +75,9 @@ pub fn get_all_thresholds() -> Result<Vec<SensorThreshold>> {
             sensor_type: row.get(0)?,
             min_value: row.get(1)?,
             max_value: row.get(2)?,
+            hysteresis: row.get(3)?,
+            min_alert_readings: row.get(4)?,
+            min_alert_duration_secs: row.get(5)?,
         })
     })?;
     
@@ -0,0 +1,417 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+/// One forward schema change; `version` is the `user_version` the database has after it ran
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<()>,
+}
+
+/// All migrations in the order they are applied. Never edit or reorder a released entry,
+/// only append new ones.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "initial schema", up: initial_schema },
+    Migration { version: 2, description: "device registry", up: device_registry },
+    Migration { version: 3, description: "alert events", up: alert_events },
+    Migration { version: 4, description: "threshold hysteresis and persistence", up: threshold_persistence },
+    Migration { version: 5, description: "alert rules", up: alert_rules },
+    Migration { version: 6, description: "time-series indexes", up: time_series_indexes },
+    Migration { version: 7, description: "downsampled rollup tables", up: rollup_tables },
+    Migration { version: 8, description: "unique readings and Firebase sync position", up: unique_readings },
+    Migration { version: 9, description: "reading time index", up: reading_time_index },
+];
+
+/// Schema version this build expects
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Bring the database up to `latest_version`, one transaction per migration.
+///
+/// When `backup_dir` is given and there is something to migrate in a database that
+/// already holds data, a copy is written there first.
+/// Returns the number of migrations applied.
+pub fn run_migrations(conn: &mut Connection, backup_dir: Option<&Path>) -> Result<usize> {
+    let version = current_version(conn)?;
+    let latest = latest_version();
+    
+    if version > latest {
+        return Err(anyhow!(
+            "Database schema version {} is newer than this app supports ({})",
+            version, latest
+        ));
+    }
+    if version == latest {
+        return Ok(0);
+    }
+    
+    if let Some(dir) = backup_dir {
+        if has_tables(conn)? {
+            let path = backup_database(conn, dir, version)?;
+            log::info!("Backed up database to {} before migrating", path.display());
+        }
+    }
+    
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        log::info!("Migrating database to version {}: {}", migration.version, migration.description);
+        
+        let tx = conn.transaction()?;
+        (migration.up)(&tx).map_err(|e| {
+            anyhow!("Migration {} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        
+        applied += 1;
+    }
+    
+    Ok(applied)
+}
+
+/// Write a consistent copy of the database into `dir`, named after its current schema version
+pub fn backup_database(conn: &Connection, dir: &Path, version: i64) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    
+    let stamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    let path = dir.join(format!("sensor_monitor-v{}-{}.db", version, stamp));
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    Ok(path)
+}
+
+fn has_tables(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Databases created before migrations existed may already have some columns
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: i64 = tx.query_row(
+        &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?1", table),
+        [column],
+        |row| row.get(0),
+    )?;
+    
+    if exists == 0 {
+        tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+fn initial_schema(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS sensor_readings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sensor_type TEXT NOT NULL,
+            value REAL NOT NULL,
+            timestamp INTEGER NOT NULL,
+            is_alert INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS sensor_thresholds (
+            sensor_type TEXT PRIMARY KEY,
+            min_value REAL NOT NULL,
+            max_value REAL NOT NULL
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+fn device_registry(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "sensor_readings", "device_id", "TEXT NOT NULL DEFAULT 'default'")?;
+    
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS devices (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            url TEXT NOT NULL,
+            location TEXT NOT NULL
+        )",
+        [],
+    )?;
+    
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS device_status_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            status TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            reason TEXT NOT NULL
+        )",
+        [],
+    )?;
+    
+    // Register the default device so readings without a device id have an owner
+    tx.execute(
+        "INSERT OR IGNORE INTO devices (id, name, url, location) VALUES ('default', 'ESP32', '', '')",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+fn alert_events(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS alert_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            sensor_type TEXT NOT NULL,
+            start_time INTEGER NOT NULL,
+            end_time INTEGER,
+            peak_value REAL NOT NULL,
+            threshold_breached TEXT NOT NULL,
+            threshold_value REAL NOT NULL,
+            acknowledged_by TEXT,
+            acknowledged_at INTEGER
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+fn threshold_persistence(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "sensor_thresholds", "hysteresis", "REAL NOT NULL DEFAULT 0")?;
+    add_column_if_missing(tx, "sensor_thresholds", "min_alert_readings", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(tx, "sensor_thresholds", "min_alert_duration_secs", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+fn alert_rules(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "alert_events", "rule_id", "INTEGER")?;
+    
+    // Conditions are stored as JSON
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS alert_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            device_id TEXT,
+            conditions TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+fn time_series_indexes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_sensor_readings_device_type_time
+            ON sensor_readings (device_id, sensor_type, timestamp);
+         CREATE INDEX IF NOT EXISTS idx_alert_events_open
+            ON alert_events (device_id, sensor_type, end_time);
+         CREATE INDEX IF NOT EXISTS idx_alert_events_start
+            ON alert_events (start_time);
+         CREATE INDEX IF NOT EXISTS idx_device_status_events_device_time
+            ON device_status_events (device_id, timestamp);
+         ANALYZE;",
+    )?;
+    Ok(())
+}
+
+fn rollup_tables(tx: &Transaction) -> Result<()> {
+    for table in ["sensor_readings_5m", "sensor_readings_1h"] {
+        tx.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    device_id TEXT NOT NULL,
+                    sensor_type TEXT NOT NULL,
+                    bucket_start INTEGER NOT NULL,
+                    avg_value REAL NOT NULL,
+                    min_value REAL NOT NULL,
+                    max_value REAL NOT NULL,
+                    sample_count INTEGER NOT NULL,
+                    PRIMARY KEY (device_id, sensor_type, bucket_start)
+                ) WITHOUT ROWID",
+                table
+            ),
+            [],
+        )?;
+    }
+    
+    Ok(())
+}
+
+fn unique_readings(tx: &Transaction) -> Result<()> {
+    // Keep the first copy of readings that were stored more than once
+    let removed = tx.execute(
+        "DELETE FROM sensor_readings WHERE id NOT IN (
+            SELECT MIN(id) FROM sensor_readings GROUP BY device_id, sensor_type, timestamp
+        )",
+        [],
+    )?;
+    if removed > 0 {
+        log::info!("Removed {} duplicate readings", removed);
+    }
+    
+    // Same columns as the existing lookup index, now also enforcing uniqueness
+    tx.execute_batch(
+        "DROP INDEX IF EXISTS idx_sensor_readings_device_type_time;
+         CREATE UNIQUE INDEX IF NOT EXISTS idx_sensor_readings_device_type_time
+            ON sensor_readings (device_id, sensor_type, timestamp);",
+    )?;
+    
+    // Last Firebase child stored per database path, so syncing resumes after it
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS firebase_sync (
+            source TEXT PRIMARY KEY,
+            last_key TEXT NOT NULL,
+            last_timestamp INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    Ok(())
+}
+
+fn reading_time_index(tx: &Transaction) -> Result<()> {
+    // Exports page through readings by (timestamp, id); the rowid makes this index cover both
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_sensor_readings_time ON sensor_readings (timestamp)",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Empty directory under the system temp dir, unique to this test run
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sensor_monitor_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    /// Database as the first release created it, with a reading stored twice
+    fn v1_database(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE sensor_readings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sensor_type TEXT NOT NULL,
+                value REAL NOT NULL,
+                timestamp INTEGER NOT NULL,
+                is_alert INTEGER NOT NULL
+            );
+            CREATE TABLE sensor_thresholds (
+                sensor_type TEXT PRIMARY KEY,
+                min_value REAL NOT NULL,
+                max_value REAL NOT NULL
+            );
+            INSERT INTO sensor_readings (sensor_type, value, timestamp, is_alert) VALUES
+                ('temperature', 21.5, 1000, 0),
+                ('temperature', 21.5, 1000, 0),
+                ('temperature', 22.0, 2000, 0),
+                ('humidity', 55.0, 1000, 0);
+            INSERT INTO sensor_thresholds (sensor_type, min_value, max_value) VALUES ('temperature', 10.0, 35.0);
+            PRAGMA user_version = 1;",
+        )
+        .unwrap();
+        conn
+    }
+    
+    fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?1", table),
+            [column],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+    
+    fn has_table(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+    
+    #[test]
+    fn upgrades_v1_database_to_latest() {
+        let dir = temp_dir("migration_upgrade");
+        let backups = dir.join("backups");
+        let mut conn = v1_database(&dir.join("sensor_monitor.db"));
+        
+        let applied = run_migrations(&mut conn, Some(&backups)).unwrap();
+        
+        assert_eq!(applied, MIGRATIONS.len() - 1);
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        
+        assert!(has_column(&conn, "sensor_readings", "device_id"));
+        assert!(has_column(&conn, "sensor_thresholds", "hysteresis"));
+        assert!(has_column(&conn, "alert_events", "rule_id"));
+        for table in ["devices", "alert_rules", "firebase_sync", "sensor_readings_5m", "sensor_readings_1h"] {
+            assert!(has_table(&conn, table), "missing table {}", table);
+        }
+        
+        // The duplicate reading is gone and the rest kept their data
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM sensor_readings", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 3);
+        let device: String = conn
+            .query_row("SELECT device_id FROM sensor_readings WHERE timestamp = 2000", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(device, "default");
+        let max: f64 = conn
+            .query_row("SELECT max_value FROM sensor_thresholds WHERE sensor_type = 'temperature'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(max, 35.0);
+        
+        let duplicate = conn.execute(
+            "INSERT INTO sensor_readings (device_id, sensor_type, value, timestamp, is_alert)
+             VALUES ('default', 'temperature', 23.0, 2000, 0)",
+            [],
+        );
+        assert!(duplicate.is_err(), "unique index accepted a duplicate reading");
+        
+        let backup_files: Vec<_> = std::fs::read_dir(&backups).unwrap().collect();
+        assert_eq!(backup_files.len(), 1);
+        let backup_name = backup_files[0].as_ref().unwrap().file_name();
+        assert!(backup_name.to_string_lossy().starts_with("sensor_monitor-v1-"));
+        
+        assert_eq!(run_migrations(&mut conn, Some(&backups)).unwrap(), 0);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn rejects_newer_schema() {
+        let dir = temp_dir("migration_newer");
+        let mut conn = Connection::open(dir.join("sensor_monitor.db")).unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+        
+        let error = run_migrations(&mut conn, None).unwrap_err();
+        
+        assert!(error.to_string().contains("newer than this app supports"));
+        assert_eq!(current_version(&conn).unwrap(), latest_version() + 1);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
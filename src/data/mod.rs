@@ -1,19 +1,25 @@
 pub mod dao;
 pub mod entity;
 pub mod database;
+pub mod migration;
+#[cfg(test)]
+mod query_plan;
 
 use anyhow::Result;
 use once_cell::sync::OnceCell;
 use rusqlite::Connection;
 use std::sync::{Arc, Mutex};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 static DATABASE: OnceCell<Arc<Mutex<Connection>>> = OnceCell::new();
 
 /// Initialize the database connection
 pub fn initialize_database() -> Result<()> {
-    let db_path = get_database_path();
-    
+    initialize_database_at(&get_database_path())
+}
+
+/// Initialize the database connection using a file other than the default one
+pub fn initialize_database_at(db_path: &Path) -> Result<()> {
     // Create parent directory if it doesn't exist
     if let Some(parent) = db_path.parent() {
         if !parent.exists() {
@@ -22,10 +28,14 @@ pub fn initialize_database() -> Result<()> {
     }
     
     // Open or create database
-    let conn = Connection::open(&db_path)?;
+    let mut conn = Connection::open(db_path)?;
     
-    // Create tables
-    create_tables(&conn)?;
+    // Bring the schema up to date, keeping a backup next to the database
+    let backup_dir = db_path.parent().map(|parent| parent.join("backups"));
+    let applied = migration::run_migrations(&mut conn, backup_dir.as_deref())?;
+    if applied > 0 {
+        log::info!("Applied {} database migrations", applied);
+    }
     
     // Store connection in global static
     DATABASE.get_or_init(|| Arc::new(Mutex::new(conn)));
@@ -34,7 +44,7 @@ pub fn initialize_database() -> Result<()> {
 }
 
 /// Get the database path
-fn get_database_path() -> PathBuf {
+pub fn get_database_path() -> PathBuf {
     match std::env::var("ANDROID_DATA") {
         Ok(data_dir) => PathBuf::from(format!("{}/data/com.example.sensormonitor/databases/sensor_monitor.db", data_dir)),
         Err(_) => {
@@ -52,29 +62,14 @@ pub fn get_database() -> Option<Arc<Mutex<Connection>>> {
     DATABASE.get().cloned()
 }
 
-/// Create database tables
-fn create_tables(conn: &Connection) -> Result<()> {
-    // Create sensor readings table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sensor_readings (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            sensor_type TEXT NOT NULL,
-            value REAL NOT NULL,
-            timestamp INTEGER NOT NULL,
-            is_alert INTEGER NOT NULL
-        )",
-        [],
-    )?;
-    
-    // Create sensor thresholds table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sensor_thresholds (
-            sensor_type TEXT PRIMARY KEY,
-            min_value REAL NOT NULL,
-            max_value REAL NOT NULL
-        )",
-        [],
-    )?;
-    
-    Ok(())
-} 
\ No newline at end of file
+/// Point the global connection at a fresh database in the temp dir, once per test run.
+/// Tests share it, so each uses its own device ids.
+#[cfg(test)]
+pub fn init_test_database() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        let path = std::env::temp_dir().join(format!("sensor_monitor_test_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        initialize_database_at(&path).expect("Failed to create test database");
+    });
+}
@@ -0,0 +1,175 @@
+//! `EXPLAIN QUERY PLAN` regression tests for the time-series queries, and a benchmark that
+//! loads synthetic readings and times each DAO function. The benchmark is ignored by default:
+//!
+//! cargo test --release query_benchmark -- --ignored --nocapture
+//!
+//! `SENSOR_MONITOR_BENCHMARK_ROWS` sets the number of readings loaded (default one million).
+
+use anyhow::Result;
+use rusqlite::{params_from_iter, types::Null, Connection};
+use crate::data::{self, dao::{device_dao, sensor_reading_dao}, get_database, migration};
+use crate::model::sensor_data::SensorReading;
+use crate::model::sensor_types;
+use crate::util::date_converter;
+use std::time::Instant;
+
+/// Devices the synthetic readings are spread over
+const BENCHMARK_DEVICES: [&str; 2] = ["benchmark-1", "benchmark-2"];
+
+/// Readings inserted per `insert_batch` call while loading
+const LOAD_BATCH_SIZE: usize = 5000;
+
+/// Times each query is repeated when measuring
+const QUERY_ITERATIONS: u32 = 200;
+
+/// Queries on time-series tables that must be served from an index
+fn indexed_queries() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("get_latest_readings", sensor_reading_dao::LATEST_READINGS_SQL),
+        ("get_history_before", sensor_reading_dao::HISTORY_BEFORE_SQL),
+        ("get_readings_in_range", sensor_reading_dao::READINGS_IN_RANGE_SQL),
+        ("for_each_reading", sensor_reading_dao::EXPORT_PAGE_SQL),
+    ]
+}
+
+/// `EXPLAIN QUERY PLAN` detail lines for a statement, with every parameter bound to NULL
+fn query_plan(conn: &Connection, sql: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+    let nulls = vec![Null; stmt.parameter_count()];
+    
+    let rows = stmt.query_map(params_from_iter(nulls), |row| row.get::<_, String>(3))?;
+    
+    let mut plan = Vec::new();
+    for row in rows {
+        plan.push(row?);
+    }
+    
+    Ok(plan)
+}
+
+/// Plan steps that read `sensor_readings` without an index or sort in a temporary table
+fn plan_problems(plan: &[String]) -> Vec<String> {
+    plan.iter()
+        .filter(|step| {
+            let full_scan = step.starts_with("SCAN")
+                && (step.contains("sensor_readings") || step.starts_with("SCAN sr"))
+                && !step.contains("INDEX");
+            full_scan || step.contains("TEMP B-TREE")
+        })
+        .cloned()
+        .collect()
+}
+
+/// Assert that every time-series query uses an index, printing the plans when `verbose`
+fn assert_indexed(conn: &Connection, verbose: bool) {
+    for (name, sql) in indexed_queries() {
+        let plan = query_plan(conn, sql).unwrap();
+        if verbose {
+            println!("{}:", name);
+            for step in &plan {
+                println!("    {}", step);
+            }
+        }
+        assert!(plan_problems(&plan).is_empty(), "{} does not use an index: {:?}", name, plan);
+    }
+}
+
+#[test]
+fn time_series_queries_use_indexes() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migration::run_migrations(&mut conn, None).unwrap();
+    assert_indexed(&conn, false);
+}
+
+#[test]
+#[ignore]
+fn query_benchmark() {
+    let rows = std::env::var("SENSOR_MONITOR_BENCHMARK_ROWS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1_000_000);
+    
+    data::init_test_database();
+    for device_id in BENCHMARK_DEVICES {
+        device_dao::ensure_device(device_id).unwrap();
+    }
+    
+    println!("Loading {} readings", rows);
+    let started = Instant::now();
+    load_readings(rows).unwrap();
+    println!("{:<24} {:>10.2} s total", "insert_batch", started.elapsed().as_secs_f64());
+    
+    {
+        let db = get_database().unwrap();
+        let conn = db.lock().unwrap();
+        conn.execute_batch("ANALYZE").unwrap();
+        
+        println!();
+        assert_indexed(&conn, true);
+    }
+    
+    let device_id = BENCHMARK_DEVICES[0];
+    let sensor_type = sensor_types::TEMPERATURE;
+    let now = start_timestamp(rows) + minutes_per_sensor(rows) as i64 * 60_000;
+    
+    println!();
+    report("get_latest_readings", || sensor_reading_dao::get_latest_readings(device_id).map(|_| ()));
+    report("get_history_before", || sensor_reading_dao::get_history_before(device_id, sensor_type, now, 100).map(|_| ()));
+    report("get_readings_in_range", || {
+        sensor_reading_dao::get_readings_in_range(device_id, sensor_type, now - 3_600_000, now).map(|_| ())
+    });
+}
+
+/// Readings per sensor so that all devices and sensors together make up `rows`
+fn minutes_per_sensor(rows: usize) -> usize {
+    rows.div_ceil(BENCHMARK_DEVICES.len() * sensor_types::ALL.len())
+}
+
+fn start_timestamp(rows: usize) -> i64 {
+    date_converter::current_timestamp() - minutes_per_sensor(rows) as i64 * 60_000
+}
+
+/// Load `rows` synthetic one-minute readings ending now
+fn load_readings(rows: usize) -> Result<()> {
+    let start = start_timestamp(rows);
+    let mut batch = Vec::with_capacity(LOAD_BATCH_SIZE);
+    let mut loaded = 0;
+    
+    'outer: for minute in 0..minutes_per_sensor(rows) {
+        let timestamp = start + minute as i64 * 60_000;
+        for device_id in BENCHMARK_DEVICES {
+            for (index, sensor_type) in sensor_types::ALL.iter().enumerate() {
+                if loaded == rows {
+                    break 'outer;
+                }
+                
+                // A slow daily wave per sensor keeps values realistic
+                let phase = (minute as f32 / 1440.0 + index as f32 / 7.0) * std::f32::consts::TAU;
+                let value = 50.0 + 20.0 * phase.sin();
+                batch.push(SensorReading::new(device_id, sensor_type, value, timestamp, false));
+                loaded += 1;
+                
+                if batch.len() == LOAD_BATCH_SIZE {
+                    sensor_reading_dao::insert_batch(&batch)?;
+                    batch.clear();
+                }
+            }
+        }
+    }
+    
+    if !batch.is_empty() {
+        sensor_reading_dao::insert_batch(&batch)?;
+    }
+    
+    Ok(())
+}
+
+fn report<F: Fn() -> Result<()>>(name: &str, f: F) {
+    let started = Instant::now();
+    for _ in 0..QUERY_ITERATIONS {
+        f().unwrap();
+    }
+    let average = started.elapsed() / QUERY_ITERATIONS;
+    
+    println!("{:<24} {:>10.3} ms avg over {} runs", name, average.as_secs_f64() * 1000.0, QUERY_ITERATIONS);
+}
@@ -1,14 +1,24 @@
 use eframe::{egui, NativeOptions};
-use egui_plot::{Line, Plot, PlotPoints};
-use std::time::Instant;
+use egui_plot::{Line, LineStyle, Plot, PlotPoints};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use worker::repository_worker::{Command, RepositoryWorker, RequestId, Response};
 
 mod model;
 mod data;
 mod api;
+mod notification;
 mod repository;
 mod util;
 mod worker;
 
+#[cfg(not(target_os = "android"))]
+mod cli;
+#[cfg(not(target_os = "android"))]
+mod daemon;
+#[cfg(not(target_os = "android"))]
+mod server;
+
 #[cfg(target_os = "android")]
 use ndk_glue::{android_main, native_activity::NativeActivity};
 
@@ -30,6 +40,12 @@ fn android_run(app: NativeActivity) {
         log::error!("Failed to initialize database: {}", e);
     }
 
+    // Khởi tạo các kênh thông báo
+    notification::init_backends();
+
+    // Khởi động tiến trình thu thập dữ liệu nền
+    start_background_worker();
+
     // Chạy ứng dụng
     let options = NativeOptions::default();
     eframe::run_native(
@@ -42,6 +58,27 @@ fn android_run(app: NativeActivity) {
 
 #[cfg(not(target_os = "android"))]
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // Giao diện dòng lệnh: sensor_monitor <lệnh> [tùy chọn]
+    if args.get(1).is_some_and(|arg| cli::is_command(arg)) {
+        std::process::exit(cli::run(&args[1..]));
+    }
+
+    // Chế độ chạy nền không giao diện: --headless [--config <tệp>]
+    if args.iter().any(|arg| arg == "--headless") {
+        let config_path = args.iter()
+            .position(|arg| arg == "--config")
+            .and_then(|index| args.get(index + 1))
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(daemon::config::default_config_path);
+        if let Err(e) = daemon::run(&config_path) {
+            eprintln!("Sensor Monitor daemon failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Khởi tạo logging
     env_logger::init();
     log::info!("Starting Sensor Monitor app");
@@ -51,6 +88,12 @@ fn main() {
         log::error!("Failed to initialize database: {}", e);
     }
 
+    // Khởi tạo các kênh thông báo
+    notification::init_backends();
+
+    // Khởi động tiến trình thu thập dữ liệu nền
+    start_background_worker();
+
     // Chạy ứng dụng
     let options = NativeOptions::default();
     eframe::run_native(
@@ -61,34 +104,289 @@ fn main() {
     .expect("Failed to start app");
 }
 
+// Chỉ giữ ký tự an toàn cho tên tệp
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn start_background_worker() {
+    if util::preferences::load_worker_enabled().unwrap_or(true) {
+        if let Err(e) = worker::sensor_data_worker::start_worker() {
+            log::error!("Failed to start background worker: {}", e);
+        }
+    }
+}
+
 struct SensorMonitorApp {
     selected_tab: Tab,
     esp32_url: String,
     last_update: Instant,
-    sensor_data: std::collections::HashMap<String, model::sensor_data::SensorReading>,
-    sensor_history: Vec<model::sensor_data::SensorReading>,
+    sensor_data: HashMap<String, model::sensor_data::SensorReading>,
+    sensor_history: model::sensor_data::AggregatedHistory,
+    sensor_statistics: Option<model::statistics::SensorStatistics>,
+    history_range: HistoryRange,
+    history_custom_from: String,
+    history_custom_to: String,
+    export_all_sensors: bool,
+    export_status: Option<String>,
+    export_request: Option<RequestId>,
+    import_path: String,
+    import_column_map: String,
+    import_request: Option<RequestId>,
+    import_summary: Option<model::import::ImportSummary>,
     selected_sensor: String,
+    devices: Vec<model::device::Device>,
+    selected_device: String,
+    device_status: Option<model::device::DeviceStatusEvent>,
+    new_device: model::device::Device,
+    worker_enabled: bool,
+    poll_interval_secs: u64,
+    poll_jitter_secs: u64,
+    retry_max_attempts: u32,
+    retry_base_delay_ms: u64,
+    retry_max_delay_ms: u64,
+    offline_after_failures: u32,
+    offline_retry_secs: u64,
+    http_connect_timeout_secs: u64,
+    http_request_timeout_secs: u64,
+    firebase_url: String,
+    firebase_path: String,
+    firebase_auth_mode: String,
+    firebase_secret: String,
+    firebase_service_account_file: String,
+    thresholds: HashMap<String, model::sensor_data::SensorThreshold>,
+    threshold_drafts: HashMap<String, model::sensor_data::SensorThreshold>,
+    connection_status: Option<String>,
+    alerts: model::alert::AlertOverview,
+    operator_name: String,
+    rules: Vec<model::alert_rule::AlertRule>,
+    rule_draft: model::alert_rule::AlertRule,
+    condition_draft: ConditionDraft,
+    show_notifications: bool,
+    desktop_notifications: bool,
+    quiet_hours_enabled: bool,
+    quiet_hours_start: u32,
+    quiet_hours_end: u32,
+    muted_sensors: Vec<String>,
+    raw_retention_days: u64,
+    rollup_retention_months: u64,
+    delete_range_from: String,
+    delete_range_to: String,
+    delete_range_all_devices: bool,
+    pending_delete: Option<DeleteRequest>,
+    data_status: Option<String>,
     error_message: Option<String>,
-    is_loading: bool,
+    repository_worker: Option<RepositoryWorker>,
+    // Yêu cầu đang chờ của từng tab, dùng để bỏ qua kết quả đã cũ
+    latest_request: Option<RequestId>,
+    history_request: Option<RequestId>,
+    statistics_request: Option<RequestId>,
+    alerts_request: Option<RequestId>,
+    connection_test_request: Option<RequestId>,
+    devices_request: Option<RequestId>,
+    device_status_request: Option<RequestId>,
+    thresholds_request: Option<RequestId>,
+    // Lưu ngưỡng đang chờ theo loại cảm biến, vì có thể lưu nhiều cảm biến cùng lúc
+    threshold_save_requests: HashMap<String, RequestId>,
+    rules_request: Option<RequestId>,
+    compact_request: Option<RequestId>,
+    delete_request: Option<RequestId>,
+}
+
+// Khoảng thời gian hiển thị trong tab lịch sử
+#[derive(Clone, Copy, PartialEq)]
+enum HistoryRange {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Custom,
+}
+
+impl HistoryRange {
+    const ALL: [HistoryRange; 5] = [
+        HistoryRange::Hour,
+        HistoryRange::Day,
+        HistoryRange::Week,
+        HistoryRange::Month,
+        HistoryRange::Custom,
+    ];
+    
+    fn label(self) -> &'static str {
+        match self {
+            HistoryRange::Hour => "1 h",
+            HistoryRange::Day => "24 h",
+            HistoryRange::Week => "7 d",
+            HistoryRange::Month => "30 d",
+            HistoryRange::Custom => "Custom",
+        }
+    }
+    
+    // Độ dài khoảng thời gian (ms), không áp dụng cho khoảng tùy chọn
+    fn duration_ms(self) -> Option<i64> {
+        const HOUR_MS: i64 = 60 * 60 * 1000;
+        match self {
+            HistoryRange::Hour => Some(HOUR_MS),
+            HistoryRange::Day => Some(24 * HOUR_MS),
+            HistoryRange::Week => Some(7 * 24 * HOUR_MS),
+            HistoryRange::Month => Some(30 * 24 * HOUR_MS),
+            HistoryRange::Custom => None,
+        }
+    }
+}
+
+// Thao tác xóa dữ liệu đang chờ người dùng xác nhận
+#[derive(Clone)]
+enum DeleteRequest {
+    All,
+    Range { device_id: Option<String>, from: i64, to: i64 },
 }
 
 enum Tab {
     Dashboard,
     History,
+    Alerts,
     Settings,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum ConditionKind {
+    Above,
+    Below,
+    RisesBy,
+    FallsBy,
+    StaysBelow,
+}
+
+impl ConditionKind {
+    const ALL: [ConditionKind; 5] = [
+        ConditionKind::Above,
+        ConditionKind::Below,
+        ConditionKind::RisesBy,
+        ConditionKind::FallsBy,
+        ConditionKind::StaysBelow,
+    ];
+    
+    fn label(self) -> &'static str {
+        match self {
+            ConditionKind::Above => "is above",
+            ConditionKind::Below => "is below",
+            ConditionKind::RisesBy => "rises by",
+            ConditionKind::FallsBy => "falls by",
+            ConditionKind::StaysBelow => "stays below",
+        }
+    }
+    
+    fn has_window(self) -> bool {
+        !matches!(self, ConditionKind::Above | ConditionKind::Below)
+    }
+}
+
+// Trạng thái biểu mẫu thêm điều kiện cho quy tắc cảnh báo
+struct ConditionDraft {
+    kind: ConditionKind,
+    sensor_type: String,
+    value: f32,
+    window_mins: i64,
+}
+
+impl ConditionDraft {
+    fn build(&self) -> model::alert_rule::RuleCondition {
+        use model::alert_rule::RuleCondition;
+        
+        let sensor_type = self.sensor_type.clone();
+        let window_secs = self.window_mins * 60;
+        match self.kind {
+            ConditionKind::Above => RuleCondition::Above { sensor_type, value: self.value },
+            ConditionKind::Below => RuleCondition::Below { sensor_type, value: self.value },
+            ConditionKind::RisesBy => RuleCondition::RisesBy { sensor_type, delta: self.value, window_secs },
+            ConditionKind::FallsBy => RuleCondition::FallsBy { sensor_type, delta: self.value, window_secs },
+            ConditionKind::StaysBelow => RuleCondition::StaysBelow { sensor_type, value: self.value, window_secs },
+        }
+    }
+}
+
 impl Default for SensorMonitorApp {
     fn default() -> Self {
         Self {
             selected_tab: Tab::Dashboard,
             esp32_url: String::from("http://192.168.1.100"),
             last_update: Instant::now(),
-            sensor_data: std::collections::HashMap::new(),
-            sensor_history: Vec::new(),
+            sensor_data: HashMap::new(),
+            sensor_history: model::sensor_data::AggregatedHistory::default(),
+            sensor_statistics: None,
+            history_range: HistoryRange::Day,
+            history_custom_from: String::new(),
+            history_custom_to: String::new(),
+            export_all_sensors: false,
+            export_status: None,
+            export_request: None,
+            import_path: String::new(),
+            import_column_map: String::new(),
+            import_request: None,
+            import_summary: None,
             selected_sensor: String::from(model::sensor_types::TEMPERATURE),
+            devices: Vec::new(),
+            selected_device: String::from(model::device::DEFAULT_DEVICE_ID),
+            device_status: None,
+            new_device: model::device::Device::new("", "", "", ""),
+            worker_enabled: true,
+            poll_interval_secs: 60,
+            poll_jitter_secs: 5,
+            retry_max_attempts: 3,
+            retry_base_delay_ms: 1000,
+            retry_max_delay_ms: 30000,
+            offline_after_failures: 5,
+            offline_retry_secs: 300,
+            http_connect_timeout_secs: 5,
+            http_request_timeout_secs: 10,
+            firebase_url: String::new(),
+            firebase_path: String::from("sensor_readings"),
+            firebase_auth_mode: String::from(api::firebase_auth::AUTH_NONE),
+            firebase_secret: String::new(),
+            firebase_service_account_file: String::new(),
+            thresholds: HashMap::new(),
+            threshold_drafts: HashMap::new(),
+            connection_status: None,
+            alerts: model::alert::AlertOverview::default(),
+            operator_name: String::new(),
+            rules: Vec::new(),
+            rule_draft: model::alert_rule::AlertRule::new(""),
+            condition_draft: ConditionDraft {
+                kind: ConditionKind::RisesBy,
+                sensor_type: String::from(model::sensor_types::TEMPERATURE),
+                value: 5.0,
+                window_mins: 10,
+            },
+            show_notifications: false,
+            desktop_notifications: true,
+            quiet_hours_enabled: false,
+            quiet_hours_start: 22,
+            quiet_hours_end: 7,
+            muted_sensors: Vec::new(),
+            raw_retention_days: 30,
+            rollup_retention_months: 12,
+            delete_range_from: String::new(),
+            delete_range_to: String::new(),
+            delete_range_all_devices: false,
+            pending_delete: None,
+            data_status: None,
             error_message: None,
-            is_loading: false,
+            repository_worker: None,
+            latest_request: None,
+            history_request: None,
+            statistics_request: None,
+            alerts_request: None,
+            connection_test_request: None,
+            devices_request: None,
+            device_status_request: None,
+            thresholds_request: None,
+            threshold_save_requests: HashMap::new(),
+            rules_request: None,
+            compact_request: None,
+            delete_request: None,
         }
     }
 }
@@ -109,84 +407,405 @@ impl SensorMonitorApp {
         // Khởi tạo trạng thái mặc định
         let mut app = Self::default();
         
+        // Khởi động luồng xử lý truy vấn, giao diện được vẽ lại mỗi khi có kết quả
+        let ctx = cc.egui_ctx.clone();
+        match RepositoryWorker::start(move || ctx.request_repaint()) {
+            Ok(repository_worker) => app.repository_worker = Some(repository_worker),
+            Err(e) => app.error_message = Some(format!("Failed to start repository worker: {}", e)),
+        }
+        
         // Tải URL ESP32 từ cài đặt
         if let Ok(url) = util::preferences::load_esp32_url() {
             app.esp32_url = url;
         }
         
+        // Tải cài đặt tiến trình nền
+        if let Ok(enabled) = util::preferences::load_worker_enabled() {
+            app.worker_enabled = enabled;
+        }
+        if let Ok(secs) = util::preferences::load_poll_interval_secs() {
+            app.poll_interval_secs = secs;
+        }
+        if let Ok(secs) = util::preferences::load_poll_jitter_secs() {
+            app.poll_jitter_secs = secs;
+        }
+        
+        // Tải cài đặt thử lại và ngưỡng ngoại tuyến
+        if let Ok(attempts) = util::preferences::load_retry_max_attempts() {
+            app.retry_max_attempts = attempts;
+        }
+        if let Ok(ms) = util::preferences::load_retry_base_delay_ms() {
+            app.retry_base_delay_ms = ms;
+        }
+        if let Ok(ms) = util::preferences::load_retry_max_delay_ms() {
+            app.retry_max_delay_ms = ms;
+        }
+        if let Ok(failures) = util::preferences::load_offline_after_failures() {
+            app.offline_after_failures = failures;
+        }
+        if let Ok(secs) = util::preferences::load_offline_retry_secs() {
+            app.offline_retry_secs = secs;
+        }
+        
+        // Tải tên người vận hành để xác nhận cảnh báo
+        if let Ok(name) = util::preferences::load_operator_name() {
+            app.operator_name = name;
+        }
+        
+        // Tải cài đặt thông báo
+        if let Ok(enabled) = util::preferences::load_desktop_notifications() {
+            app.desktop_notifications = enabled;
+        }
+        if let Ok(enabled) = util::preferences::load_quiet_hours_enabled() {
+            app.quiet_hours_enabled = enabled;
+        }
+        if let Ok(hour) = util::preferences::load_quiet_hours_start() {
+            app.quiet_hours_start = hour;
+        }
+        if let Ok(hour) = util::preferences::load_quiet_hours_end() {
+            app.quiet_hours_end = hour;
+        }
+        if let Ok(sensors) = util::preferences::load_muted_sensors() {
+            app.muted_sensors = sensors;
+        }
+        
+        // Tải chính sách lưu trữ dữ liệu
+        if let Ok(days) = util::preferences::load_raw_retention_days() {
+            app.raw_retention_days = days;
+        }
+        if let Ok(months) = util::preferences::load_rollup_retention_months() {
+            app.rollup_retention_months = months;
+        }
+        
+        // Tải thời gian chờ kết nối HTTP
+        if let Ok(secs) = util::preferences::load_http_connect_timeout_secs() {
+            app.http_connect_timeout_secs = secs;
+        }
+        if let Ok(secs) = util::preferences::load_http_request_timeout_secs() {
+            app.http_request_timeout_secs = secs;
+        }
+        
+        // Tải cài đặt Firebase
+        if let Ok(url) = util::preferences::load_firebase_url() {
+            app.firebase_url = url;
+        }
+        if let Ok(path) = util::preferences::load_firebase_path() {
+            app.firebase_path = path;
+        }
+        if let Ok(mode) = util::preferences::load_firebase_auth_mode() {
+            app.firebase_auth_mode = mode;
+        }
+        if let Ok(secret) = util::preferences::load_firebase_secret() {
+            app.firebase_secret = secret;
+        }
+        if let Ok(file) = util::preferences::load_firebase_service_account_file() {
+            app.firebase_service_account_file = file;
+        }
+        
+        // Tải danh sách thiết bị và ngưỡng cảm biến
+        app.load_devices();
+        app.thresholds_request = app.send(Command::LoadThresholds);
+        app.rules_request = app.send(Command::LoadRules);
+        
         // Kích hoạt cập nhật dữ liệu ban đầu
         app.refresh_data();
         
         app
     }
     
-    fn refresh_data(&mut self) {
-        self.is_loading = true;
-        self.error_message = None;
-        
-        // Sử dụng reqwest blocking để thực hiện yêu cầu HTTP
-        let esp32_url = self.esp32_url.clone();
-        
-        // Tải dữ liệu sử dụng repository
-        match repository::sensor_repository::fetch_latest_readings() {
-            Ok(json) => {
-                match serde_json::from_str(&json) {
-                    Ok(data) => {
-                        self.sensor_data = data;
-                        self.last_update = Instant::now();
-                    },
-                    Err(e) => {
-                        self.error_message = Some(format!("Failed to parse data: {}", e));
-                    }
-                }
-            },
-            Err(e) => {
-                self.error_message = Some(format!("Failed to fetch data: {}", e));
+    fn send(&mut self, command: Command) -> Option<RequestId> {
+        match self.repository_worker.as_mut() {
+            Some(repository_worker) => Some(repository_worker.send(command)),
+            None => {
+                self.error_message = Some("Repository worker is not running".to_string());
+                None
             }
         }
-        
-        self.is_loading = false;
+    }
+    
+    fn refresh_data(&mut self) {
+        // Dữ liệu mới được tiến trình nền thu thập, ở đây chỉ đọc từ cơ sở dữ liệu
+        self.latest_request = self.send(Command::LoadLatest {
+            device_id: self.selected_device.clone(),
+        });
+        self.device_status_request = self.send(Command::LoadDeviceStatus {
+            device_id: self.selected_device.clone(),
+        });
+        self.load_alerts();
+        self.last_update = Instant::now();
     }
     
     fn load_history(&mut self) {
-        self.is_loading = true;
-        self.error_message = None;
-        
-        // Tải lịch sử cho cảm biến đã chọn
-        match repository::sensor_repository::fetch_reading_history(&self.selected_sensor, 100) {
-            Ok(json) => {
-                match serde_json::from_str(&json) {
-                    Ok(data) => {
-                        self.sensor_history = data;
-                    },
-                    Err(e) => {
-                        self.error_message = Some(format!("Failed to parse history data: {}", e));
-                    }
-                }
+        let range = match self.history_range.duration_ms() {
+            Some(duration) => {
+                let now = util::date_converter::current_timestamp();
+                Some((now - duration, now))
             },
-            Err(e) => {
-                self.error_message = Some(format!("Failed to fetch history: {}", e));
+            // Ngày kết thúc của khoảng tùy chọn được tính trọn ngày
+            None => util::date_converter::parse_date(&self.history_custom_from)
+                .zip(util::date_converter::parse_date(&self.history_custom_to))
+                .map(|(from, to)| (from, to + 24 * 60 * 60 * 1000)),
+        };
+        
+        let Some((from, to)) = range else {
+            self.error_message = Some("Please enter dates as YYYY-MM-DD".to_string());
+            return;
+        };
+        
+        // Tải lịch sử cho cảm biến đã chọn, yêu cầu trước đó (nếu có) sẽ bị hủy
+        self.history_request = self.send(Command::LoadHistory {
+            device_id: self.selected_device.clone(),
+            sensor_type: self.selected_sensor.clone(),
+            from,
+            to,
+        });
+        
+        // Thống kê cho cùng khoảng thời gian
+        self.sensor_statistics = None;
+        self.statistics_request = self.send(Command::LoadStatistics {
+            device_id: self.selected_device.clone(),
+            sensor_type: self.selected_sensor.clone(),
+            from,
+            to,
+        });
+    }
+    
+    fn load_alerts(&mut self) {
+        self.alerts_request = self.send(Command::LoadAlerts);
+    }
+    
+    fn load_devices(&mut self) {
+        self.devices_request = self.send(Command::LoadDevices);
+    }
+    
+    fn is_dashboard_loading(&self) -> bool {
+        self.latest_request.is_some()
+    }
+    
+    fn is_history_loading(&self) -> bool {
+        self.history_request.is_some()
+    }
+    
+    // Nhận kết quả từ luồng xử lý mà không chặn giao diện
+    fn process_responses(&mut self) {
+        let mut responses = Vec::new();
+        if let Some(repository_worker) = &self.repository_worker {
+            while let Some(response) = repository_worker.try_recv() {
+                responses.push(response);
             }
         }
         
-        self.is_loading = false;
+        for response in responses {
+            match response {
+                Response::Latest(request_id, result) => {
+                    if self.latest_request != Some(request_id) {
+                        continue;
+                    }
+                    self.latest_request = None;
+                    match result {
+                        Ok(data) => self.sensor_data = data,
+                        Err(e) => self.error_message = Some(format!("Failed to fetch data: {}", e)),
+                    }
+                },
+                Response::History(request_id, result) => {
+                    if self.history_request != Some(request_id) {
+                        continue;
+                    }
+                    self.history_request = None;
+                    match result {
+                        Ok(data) => self.sensor_history = data,
+                        Err(e) => self.error_message = Some(format!("Failed to fetch history: {}", e)),
+                    }
+                },
+                Response::Statistics(request_id, result) => {
+                    if self.statistics_request != Some(request_id) {
+                        continue;
+                    }
+                    self.statistics_request = None;
+                    match result {
+                        Ok(stats) => self.sensor_statistics = Some(stats),
+                        Err(e) => log::warn!("Failed to compute statistics: {}", e),
+                    }
+                },
+                Response::Devices(request_id, result) => {
+                    if self.devices_request != Some(request_id) {
+                        continue;
+                    }
+                    self.devices_request = None;
+                    match result {
+                        Ok(devices) => {
+                            // Giữ lựa chọn hiện tại nếu thiết bị vẫn còn tồn tại
+                            if !devices.is_empty() && !devices.iter().any(|d| d.id == self.selected_device) {
+                                self.selected_device = devices[0].id.clone();
+                                self.refresh_data();
+                            }
+                            self.devices = devices;
+                        },
+                        Err(e) => self.error_message = Some(format!("Failed to load devices: {}", e)),
+                    }
+                },
+                Response::DeviceStatus(request_id, result) => {
+                    if self.device_status_request != Some(request_id) {
+                        continue;
+                    }
+                    self.device_status_request = None;
+                    match result {
+                        // Bỏ qua trạng thái của thiết bị không còn được chọn
+                        Ok(status) => {
+                            self.device_status = status.filter(|event| event.device_id == self.selected_device);
+                        },
+                        Err(e) => log::warn!("Failed to load device status: {}", e),
+                    }
+                },
+                Response::Thresholds(request_id, result) => {
+                    if self.thresholds_request != Some(request_id) {
+                        continue;
+                    }
+                    self.thresholds_request = None;
+                    match result {
+                        Ok(thresholds) => {
+                            for threshold in thresholds {
+                                self.threshold_drafts.insert(threshold.sensor_type.clone(), threshold.clone());
+                                self.thresholds.insert(threshold.sensor_type.clone(), threshold);
+                            }
+                        },
+                        Err(e) => self.error_message = Some(format!("Failed to load thresholds: {}", e)),
+                    }
+                },
+                Response::ThresholdSaved(request_id, result) => {
+                    let before = self.threshold_save_requests.len();
+                    self.threshold_save_requests.retain(|_, pending| *pending != request_id);
+                    if self.threshold_save_requests.len() == before {
+                        continue;
+                    }
+                    match result {
+                        Ok(threshold) => {
+                            self.threshold_drafts.insert(threshold.sensor_type.clone(), threshold.clone());
+                            self.thresholds.insert(threshold.sensor_type.clone(), threshold);
+                        },
+                        Err(e) => self.error_message = Some(format!("Failed to save threshold: {}", e)),
+                    }
+                },
+                Response::ConnectionTested(request_id, result) => {
+                    if self.connection_test_request != Some(request_id) {
+                        continue;
+                    }
+                    self.connection_test_request = None;
+                    match result {
+                        Ok(()) => self.connection_status = Some("Connection successful".to_string()),
+                        Err(e) => {
+                            self.connection_status = None;
+                            self.error_message = Some(format!("Connection failed: {}", e));
+                        }
+                    }
+                },
+                Response::Alerts(request_id, result) => {
+                    if self.alerts_request == Some(request_id) {
+                        self.alerts_request = None;
+                    }
+                    match result {
+                        Ok(alerts) => self.alerts = alerts,
+                        Err(e) => self.error_message = Some(format!("Failed to update alerts: {}", e)),
+                    }
+                },
+                Response::Rules(request_id, result) => {
+                    if self.rules_request != Some(request_id) {
+                        continue;
+                    }
+                    self.rules_request = None;
+                    match result {
+                        Ok(rules) => self.rules = rules,
+                        Err(e) => self.error_message = Some(format!("Failed to update rules: {}", e)),
+                    }
+                },
+                Response::Compacted(request_id, result) => {
+                    if self.compact_request != Some(request_id) {
+                        continue;
+                    }
+                    self.compact_request = None;
+                    match result {
+                        Ok(counts) => {
+                            self.data_status = Some(format!(
+                                "Compacted {} readings and {} five-minute averages",
+                                counts.raw_rows, counts.rollup_rows
+                            ));
+                        },
+                        Err(e) => self.error_message = Some(format!("Failed to compact data: {}", e)),
+                    }
+                },
+                Response::DataDeleted(request_id, result) => {
+                    if self.delete_request != Some(request_id) {
+                        continue;
+                    }
+                    self.delete_request = None;
+                    match result {
+                        Ok(count) => {
+                            self.data_status = Some(format!("Deleted {} rows", count));
+                            self.sensor_history.buckets.clear();
+                            self.refresh_data();
+                        },
+                        Err(e) => self.error_message = Some(format!("Failed to delete data: {}", e)),
+                    }
+                },
+                Response::Exported(request_id, result) => {
+                    if self.export_request == Some(request_id) {
+                        self.export_request = None;
+                    }
+                    match result {
+                        Ok(summary) => {
+                            self.export_status = Some(format!("Exported {} rows to {}", summary.rows, summary.path));
+                        },
+                        Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+                    }
+                },
+                Response::Imported(request_id, result) => {
+                    if self.import_request == Some(request_id) {
+                        self.import_request = None;
+                    }
+                    match result {
+                        Ok(summary) => {
+                            self.import_summary = Some(summary);
+                            self.refresh_data();
+                        },
+                        Err(e) => self.error_message = Some(format!("Import failed: {}", e)),
+                    }
+                },
+                Response::Cancelled(request_id) => {
+                    log::debug!("Request {} was superseded", request_id);
+                },
+            }
+        }
     }
 }
 
 impl eframe::App for SensorMonitorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.process_responses();
+        
         // Tự động làm mới dữ liệu mỗi 60 giây
-        if self.last_update.elapsed().as_secs() > 60 && !self.is_loading {
+        if self.last_update.elapsed().as_secs() > 60 && !self.is_dashboard_loading() {
             self.refresh_data();
         }
+        ctx.request_repaint_after(Duration::from_secs(1));
         
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.heading("Sensor Monitor");
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui.button("⟳").clicked() {
+                        worker::sensor_data_worker::request_poll();
                         self.refresh_data();
                     }
+                    
+                    let unread = notification::centre::unread_count();
+                    let bell_label = if unread > 0 {
+                        format!("🔔 {}", unread)
+                    } else {
+                        "🔔".to_string()
+                    };
+                    if ui.selectable_label(self.show_notifications, bell_label).clicked() {
+                        self.show_notifications = !self.show_notifications;
+                    }
                 });
             });
             
@@ -198,13 +817,22 @@ impl eframe::App for SensorMonitorApp {
                     self.selected_tab = Tab::History;
                     self.load_history();
                 }
+                let alerts_label = if self.alerts.open.is_empty() {
+                    "Alerts".to_string()
+                } else {
+                    format!("Alerts ({})", self.alerts.open.len())
+                };
+                if ui.selectable_label(matches!(self.selected_tab, Tab::Alerts), alerts_label).clicked() {
+                    self.selected_tab = Tab::Alerts;
+                    self.load_alerts();
+                }
                 if ui.selectable_label(matches!(self.selected_tab, Tab::Settings), "Settings").clicked() {
                     self.selected_tab = Tab::Settings;
                 }
             });
         });
         
-        if let Some(error) = &self.error_message {
+        if let Some(error) = self.error_message.clone() {
             egui::Window::new("Error")
                 .collapsible(false)
                 .resizable(false)
@@ -216,10 +844,19 @@ impl eframe::App for SensorMonitorApp {
                 });
         }
         
+        if self.show_notifications {
+            self.render_notification_centre(ctx);
+        }
+        
+        if self.pending_delete.is_some() {
+            self.render_delete_confirmation(ctx);
+        }
+        
         egui::CentralPanel::default().show(ctx, |ui| {
             match self.selected_tab {
                 Tab::Dashboard => self.render_dashboard(ui),
                 Tab::History => self.render_history(ui),
+                Tab::Alerts => self.render_alerts(ui),
                 Tab::Settings => self.render_settings(ui),
             }
         });
@@ -227,10 +864,97 @@ impl eframe::App for SensorMonitorApp {
 }
 
 impl SensorMonitorApp {
+    fn render_notification_centre(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        
+        egui::Window::new("Notifications")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Mark all read").clicked() {
+                        notification::centre::mark_all_read();
+                    }
+                    if ui.button("Clear").clicked() {
+                        notification::centre::clear();
+                    }
+                });
+                ui.separator();
+                
+                let notifications = notification::centre::notifications();
+                if notifications.is_empty() {
+                    ui.label("No notifications.");
+                    return;
+                }
+                
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for item in &notifications {
+                        let mut title = egui::RichText::new(&item.title);
+                        if !item.read {
+                            title = title.strong();
+                        }
+                        if item.urgent {
+                            title = title.color(egui::Color32::from_rgb(255, 100, 100));
+                        }
+                        ui.label(title);
+                        ui.label(&item.body);
+                        ui.small(util::date_converter::format_timestamp(item.timestamp));
+                        ui.separator();
+                    }
+                });
+            });
+        
+        // Đóng cửa sổ thì coi như đã đọc hết
+        if !open {
+            self.show_notifications = false;
+            notification::centre::mark_all_read();
+        }
+    }
+    
+    fn render_device_selector(&mut self, ui: &mut egui::Ui, id_source: &str) -> bool {
+        let mut changed = false;
+        
+        let selected_text = self.devices.iter()
+            .find(|d| d.id == self.selected_device)
+            .map_or(self.selected_device.clone(), |d| d.display_name().to_string());
+        
+        ui.horizontal(|ui| {
+            ui.label("Select device:");
+            egui::ComboBox::from_id_source(id_source)
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for device in &self.devices {
+                        if ui.selectable_label(self.selected_device == device.id, device.display_name()).clicked() {
+                            self.selected_device = device.id.clone();
+                            changed = true;
+                        }
+                    }
+                });
+        });
+        
+        changed
+    }
+    
     fn render_dashboard(&mut self, ui: &mut egui::Ui) {
         ui.heading("Sensor Readings");
         
-        if self.is_loading {
+        if self.render_device_selector(ui, "dashboard_device_selector") {
+            self.device_status = None;
+            self.refresh_data();
+        }
+        
+        // Hiển thị trạng thái kết nối của thiết bị
+        if let Some(status) = &self.device_status {
+            let since = util::date_converter::format_timestamp(status.timestamp);
+            if status.is_offline() {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), format!("● Offline since {}", since))
+                    .on_hover_text(&status.reason);
+            } else {
+                ui.colored_label(egui::Color32::from_rgb(100, 255, 100), format!("● Online since {}", since));
+            }
+        }
+        
+        if self.is_dashboard_loading() && self.sensor_data.is_empty() {
             ui.spinner();
             ui.label("Loading data...");
             return;
@@ -303,6 +1027,10 @@ impl SensorMonitorApp {
     fn render_history(&mut self, ui: &mut egui::Ui) {
         ui.heading("Sensor History");
         
+        if self.render_device_selector(ui, "history_device_selector") {
+            self.load_history();
+        }
+        
         ui.horizontal(|ui| {
             ui.label("Select sensor:");
             egui::ComboBox::from_id_source("sensor_selector")
@@ -326,80 +1054,147 @@ impl SensorMonitorApp {
                 });
         });
         
-        if self.is_loading {
+        ui.horizontal(|ui| {
+            ui.label("Range:");
+            for range in HistoryRange::ALL {
+                if ui.selectable_label(self.history_range == range, range.label()).clicked() {
+                    self.history_range = range;
+                    if range != HistoryRange::Custom {
+                        self.load_history();
+                    }
+                }
+            }
+        });
+        
+        if self.history_range == HistoryRange::Custom {
+            ui.horizontal(|ui| {
+                ui.label("From (YYYY-MM-DD):");
+                ui.add(egui::TextEdit::singleline(&mut self.history_custom_from).desired_width(110.0));
+                ui.label("To:");
+                ui.add(egui::TextEdit::singleline(&mut self.history_custom_to).desired_width(110.0));
+                if ui.button("Apply").clicked() {
+                    self.load_history();
+                }
+            });
+        }
+        
+        self.render_export_controls(ui);
+        
+        if self.is_history_loading() {
             ui.spinner();
             ui.label("Loading data...");
             return;
         }
         
-        if self.sensor_history.is_empty() {
+        if self.sensor_history.buckets.is_empty() {
             ui.label("No history data available for the selected sensor.");
             return;
         }
         
         let unit = model::sensor_types::get_unit(&self.selected_sensor);
         let display_name = model::sensor_types::get_display_name(&self.selected_sensor);
+        let is_rain = self.selected_sensor == model::sensor_types::RAIN;
+        let format_value = |value: f32| {
+            if is_rain {
+                // Trung bình của cảm biến mưa là tỉ lệ thời gian có mưa
+                format!("{:.0}% rain", value * 100.0)
+            } else {
+                format!("{:.1} {}", value, unit)
+            }
+        };
+        
+        ui.label(format!(
+            "{} buckets of {} min",
+            self.sensor_history.buckets.len(),
+            self.sensor_history.bucket_ms / 60_000
+        ));
+        
+        if let Some(stats) = &self.sensor_statistics {
+            Self::render_statistics_card(ui, stats, &format_value);
+            ui.add_space(10.0);
+        }
         
-        // Chuẩn bị dữ liệu cho biểu đồ
-        let points: PlotPoints = self.sensor_history.iter()
-            .map(|reading| [reading.timestamp as f64, reading.value as f64])
-            .collect();
+        // Chuẩn bị dữ liệu cho biểu đồ: đường trung bình và đường min/max của mỗi khoảng
+        let buckets = &self.sensor_history.buckets;
+        let series = |value: fn(&model::sensor_data::AggregatedReading) -> f32| -> PlotPoints {
+            buckets.iter()
+                .map(|bucket| [bucket.bucket_start as f64, value(bucket) as f64])
+                .collect()
+        };
         
-        let line = Line::new(points)
-            .name(display_name)
+        let avg_line = Line::new(series(|b| b.avg_value))
+            .name(format!("{} (avg)", display_name))
             .width(2.0);
+        let min_line = Line::new(series(|b| b.min_value))
+            .name("min")
+            .style(LineStyle::dashed_dense())
+            .width(1.0);
+        let max_line = Line::new(series(|b| b.max_value))
+            .name("max")
+            .style(LineStyle::dashed_dense())
+            .width(1.0);
+        
+        let span_ms = self.sensor_history.to - self.sensor_history.from;
         
         Plot::new("history_plot")
             .height(300.0)
-            .show_x_axis(true)
-            .show_y_axis(true)
+            .show_axes([true, true])
             .allow_zoom(true)
             .allow_drag(true)
             .y_axis_label(format!("{} ({})", display_name, unit))
             .x_axis_label("Time")
+            .x_axis_formatter(move |x, _, _| {
+                if span_ms <= 24 * 60 * 60 * 1000 {
+                    util::date_converter::format_time(x as i64)
+                } else {
+                    util::date_converter::format_date(x as i64)
+                }
+            })
+            .label_formatter(|name, point| {
+                let time = util::date_converter::format_timestamp(point.x as i64);
+                if name.is_empty() {
+                    time
+                } else {
+                    format!("{}\n{}: {:.2}", time, name, point.y)
+                }
+            })
             .show(ui, |plot_ui| {
-                plot_ui.line(line);
+                plot_ui.line(max_line);
+                plot_ui.line(min_line);
+                plot_ui.line(avg_line);
             });
         
         ui.add_space(20.0);
         
-        // Hiển thị dữ liệu trong bảng
+        // Hiển thị dữ liệu trong bảng, mới nhất trước
         egui::ScrollArea::vertical().show(ui, |ui| {
             egui::Grid::new("history_grid")
                 .striped(true)
                 .spacing([40.0, 8.0])
                 .show(ui, |ui| {
                     ui.label("Time");
-                    ui.label("Value");
+                    ui.label("Average");
+                    ui.label("Min");
+                    ui.label("Max");
+                    ui.label("Readings");
                     ui.label("Status");
                     ui.end_row();
                     
-                    for reading in &self.sensor_history {
-                        let time_str = util::date_converter::format_timestamp(reading.timestamp);
-                        
-                        ui.label(time_str);
-                        
-                        let value_text = if self.selected_sensor == model::sensor_types::RAIN {
-                            if reading.value > 0.5 { "Yes" } else { "No" }.to_string()
-                        } else {
-                            format!("{:.1} {}", reading.value, unit)
-                        };
-                        
-                        ui.label(value_text);
-                        
-                        let status_text = if reading.is_alert {
-                            "⚠ Alert"
-                        } else {
-                            "✓ Normal"
-                        };
+                    for bucket in self.sensor_history.buckets.iter().rev() {
+                        ui.label(util::date_converter::format_timestamp(bucket.bucket_start));
+                        ui.label(format_value(bucket.avg_value));
+                        ui.label(format_value(bucket.min_value));
+                        ui.label(format_value(bucket.max_value));
+                        ui.label(bucket.count.to_string());
                         
-                        let status_color = if reading.is_alert {
-                            egui::Color32::from_rgb(255, 100, 100)
+                        if bucket.alert_count > 0 {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(255, 100, 100),
+                                format!("⚠ {} alerts", bucket.alert_count),
+                            );
                         } else {
-                            egui::Color32::from_rgb(100, 255, 100)
-                        };
-                        
-                        ui.colored_label(status_color, status_text);
+                            ui.colored_label(egui::Color32::from_rgb(100, 255, 100), "✓ Normal");
+                        }
                         
                         ui.end_row();
                     }
@@ -407,11 +1202,252 @@ impl SensorMonitorApp {
         });
     }
     
-    fn render_settings(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Settings");
-        
-        ui.add_space(20.0);
-        ui.label("ESP32 Connection Settings");
+    fn render_export_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let exporting = self.export_request.is_some();
+            let has_range = self.sensor_history.from < self.sensor_history.to;
+            
+            if ui.add_enabled(!exporting && has_range, egui::Button::new("Export CSV")).clicked() {
+                // Xuất đúng thiết bị và khoảng thời gian đang xem
+                let sensor_type = if self.export_all_sensors {
+                    None
+                } else {
+                    Some(self.selected_sensor.clone())
+                };
+                let file_name = format!(
+                    "readings-{}-{}-{}.csv",
+                    sanitize_file_name(&self.selected_device),
+                    sensor_type.as_deref().unwrap_or("all"),
+                    chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                );
+                
+                self.export_status = None;
+                self.export_request = self.send(Command::ExportReadings {
+                    path: repository::export_repository::default_export_dir().join(file_name),
+                    filter: model::export::ReadingFilter {
+                        device_id: Some(self.selected_device.clone()),
+                        sensor_type,
+                        from: Some(self.sensor_history.from),
+                        to: Some(self.sensor_history.to),
+                    },
+                });
+            }
+            ui.checkbox(&mut self.export_all_sensors, "All sensors");
+            
+            if exporting {
+                ui.spinner();
+            } else if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+    }
+    
+    fn render_statistics_card(ui: &mut egui::Ui, stats: &model::statistics::SensorStatistics, format_value: &dyn Fn(f32) -> String) {
+        let value_text = |value: Option<f32>| value.map_or("-".to_string(), format_value);
+        let duration_text = |duration_ms: i64| {
+            format!(
+                "{:.1} h ({:.0}%)",
+                duration_ms as f64 / 3_600_000.0,
+                stats.percent_of_covered(duration_ms)
+            )
+        };
+        
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.label(egui::RichText::new("Summary").strong());
+            egui::Grid::new("statistics_grid")
+                .spacing([30.0, 4.0])
+                .show(ui, |ui| {
+                    ui.label("Readings");
+                    ui.label("Min");
+                    ui.label("Max");
+                    ui.label("Mean");
+                    ui.label("Median");
+                    ui.label("Std dev");
+                    ui.label("P5");
+                    ui.label("P95");
+                    ui.end_row();
+                    
+                    ui.label(stats.count.to_string());
+                    ui.label(value_text(stats.min));
+                    ui.label(value_text(stats.max));
+                    ui.label(value_text(stats.mean));
+                    ui.label(value_text(stats.median));
+                    ui.label(stats.std_dev.map_or("-".to_string(), |v| format!("{:.2}", v)));
+                    ui.label(value_text(stats.p5));
+                    ui.label(value_text(stats.p95));
+                    ui.end_row();
+                });
+            
+            ui.horizontal(|ui| {
+                ui.label(format!("Above max: {}", duration_text(stats.time_above_threshold_ms)));
+                ui.label(format!("Below min: {}", duration_text(stats.time_below_threshold_ms)));
+                ui.label(format!("In alert: {}", duration_text(stats.time_in_alert_ms)));
+            });
+        });
+    }
+    
+    // Tên hiển thị của cảnh báo: tên cảm biến hoặc tên quy tắc
+    fn alert_title(&self, event: &model::alert::AlertEvent) -> String {
+        match event.rule_id {
+            Some(rule_id) => self.rules.iter()
+                .find(|rule| rule.id == Some(rule_id))
+                .map_or(format!("Rule #{}", rule_id), |rule| rule.name.clone()),
+            None => model::sensor_types::get_display_name(&event.sensor_type).to_string(),
+        }
+    }
+    
+    fn render_alerts(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Alerts");
+        
+        ui.horizontal(|ui| {
+            let exporting = self.export_request.is_some();
+            if ui.add_enabled(!exporting, egui::Button::new("Export CSV")).clicked() {
+                let file_name = format!("alerts-{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+                self.export_status = None;
+                self.export_request = self.send(Command::ExportAlerts {
+                    path: repository::export_repository::default_export_dir().join(file_name),
+                    filter: model::export::ReadingFilter::default(),
+                });
+            }
+            if exporting {
+                ui.spinner();
+            } else if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Acknowledge as:");
+            if ui.text_edit_singleline(&mut self.operator_name).changed() {
+                if let Err(e) = util::preferences::save_operator_name(&self.operator_name) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+        });
+        
+        if self.alerts_request.is_some() && self.alerts.open.is_empty() && self.alerts.history.is_empty() {
+            ui.spinner();
+            ui.label("Loading alerts...");
+            return;
+        }
+        
+        let mut acknowledge_id = None;
+        
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Open Alerts").strong());
+            
+            if self.alerts.open.is_empty() {
+                ui.label("No open alerts.");
+            } else {
+                egui::Grid::new("open_alerts_grid")
+                    .striped(true)
+                    .spacing([30.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.label("Sensor");
+                        ui.label("Device");
+                        ui.label("Since");
+                        ui.label("Peak");
+                        ui.label("Threshold");
+                        ui.label("");
+                        ui.end_row();
+                        
+                        for event in &self.alerts.open {
+                            let unit = model::sensor_types::get_unit(&event.sensor_type);
+                            ui.colored_label(
+                                egui::Color32::from_rgb(255, 100, 100),
+                                format!("⚠ {}", self.alert_title(event)),
+                            );
+                            ui.label(&event.device_id);
+                            ui.label(util::date_converter::format_timestamp(event.start_time));
+                            ui.label(format!("{:.1} {}", event.peak_value, unit));
+                            if event.rule_id.is_some() {
+                                ui.label("rule");
+                            } else {
+                                ui.label(format!("{} {:.1} {}", event.threshold_breached, event.threshold_value, unit));
+                            }
+                            
+                            match (&event.acknowledged_by, event.id) {
+                                (Some(by), _) => {
+                                    ui.label(format!("✓ {}", by));
+                                },
+                                (None, Some(id)) => {
+                                    if ui.button("Acknowledge").clicked() {
+                                        acknowledge_id = Some(id);
+                                    }
+                                },
+                                (None, None) => {
+                                    ui.label("");
+                                },
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+            
+            ui.add_space(20.0);
+            ui.label(egui::RichText::new("Alert History").strong());
+            
+            if self.alerts.history.is_empty() {
+                ui.label("No past alerts.");
+            } else {
+                egui::Grid::new("alert_history_grid")
+                    .striped(true)
+                    .spacing([30.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.label("Sensor");
+                        ui.label("Device");
+                        ui.label("Start");
+                        ui.label("End");
+                        ui.label("Peak");
+                        ui.label("Acknowledged");
+                        ui.end_row();
+                        
+                        for event in &self.alerts.history {
+                            let unit = model::sensor_types::get_unit(&event.sensor_type);
+                            ui.label(self.alert_title(event));
+                            ui.label(&event.device_id);
+                            ui.label(util::date_converter::format_timestamp(event.start_time));
+                            ui.label(event.end_time.map_or(String::new(), util::date_converter::format_timestamp));
+                            ui.label(format!("{:.1} {}", event.peak_value, unit));
+                            
+                            match (&event.acknowledged_by, event.id) {
+                                (Some(by), _) => {
+                                    ui.label(format!(
+                                        "{} ({})",
+                                        by,
+                                        event.acknowledged_at.map_or(String::new(), util::date_converter::format_timestamp)
+                                    ));
+                                },
+                                (None, Some(id)) => {
+                                    if ui.button("Acknowledge").clicked() {
+                                        acknowledge_id = Some(id);
+                                    }
+                                },
+                                (None, None) => {
+                                    ui.label("");
+                                },
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+        });
+        
+        if let Some(id) = acknowledge_id {
+            // Xác nhận cảnh báo với tên người vận hành hiện tại
+            self.alerts_request = self.send(Command::AcknowledgeAlert {
+                id,
+                acknowledged_by: self.operator_name.clone(),
+            });
+        }
+    }
+    
+    fn render_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Settings");
+        
+        ui.add_space(20.0);
+        ui.label("ESP32 Connection Settings");
         
         ui.horizontal(|ui| {
             ui.label("ESP32 URL:");
@@ -425,19 +1461,51 @@ impl SensorMonitorApp {
             }
         });
         
-        ui.add_space(10.0);
-        if ui.button("Test Connection").clicked() {
-            // Kiểm tra kết nối đến ESP32
-            match api::esp32_api::fetch_data_from_esp32(&self.esp32_url) {
-                Ok(_) => {
-                    ui.label("Connection successful");
-                },
-                Err(e) => {
-                    self.error_message = Some(format!("Connection failed: {}", e));
-                }
+        let mut timeouts_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Connect timeout (s):");
+            timeouts_changed |= ui.add(egui::DragValue::new(&mut self.http_connect_timeout_secs).clamp_range(1..=120)).changed();
+            ui.label("Request timeout (s):");
+            timeouts_changed |= ui.add(egui::DragValue::new(&mut self.http_request_timeout_secs).clamp_range(1..=300)).changed();
+        });
+        
+        if timeouts_changed {
+            // Lưu thời gian chờ và áp dụng ngay cho HTTP client dùng chung
+            let result = util::preferences::save_http_connect_timeout_secs(self.http_connect_timeout_secs)
+                .and_then(|_| util::preferences::save_http_request_timeout_secs(self.http_request_timeout_secs))
+                .and_then(|_| api::http_client::configure_timeouts(self.http_connect_timeout_secs, self.http_request_timeout_secs));
+            if let Err(e) = result {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
             }
         }
         
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            let testing = self.connection_test_request.is_some();
+            if ui.add_enabled(!testing, egui::Button::new("Test Connection")).clicked() {
+                // Kiểm tra kết nối đến ESP32
+                self.connection_status = None;
+                self.connection_test_request = self.send(Command::TestConnection {
+                    url: self.esp32_url.clone(),
+                });
+            }
+            
+            if testing {
+                ui.spinner();
+            } else if let Some(status) = &self.connection_status {
+                ui.label(status);
+            }
+        });
+        
+        ui.add_space(20.0);
+        self.render_firebase_settings(ui);
+        
+        ui.add_space(20.0);
+        self.render_worker_settings(ui);
+        
+        ui.add_space(20.0);
+        self.render_device_settings(ui);
+        
         ui.add_space(20.0);
         ui.label("Sensor Thresholds");
         ui.add_space(10.0);
@@ -455,47 +1523,690 @@ impl SensorMonitorApp {
         }
         
         ui.add_space(20.0);
-        if ui.button("Delete All Data").clicked() {
-            // Hiển thị hộp thoại xác nhận
-            self.error_message = Some("Are you sure you want to delete all data? This action cannot be undone.".to_string());
+        self.render_rule_settings(ui);
+        
+        ui.add_space(20.0);
+        self.render_notification_settings(ui);
+        
+        ui.add_space(20.0);
+        self.render_data_settings(ui);
+        
+        ui.add_space(20.0);
+        self.render_import_settings(ui);
+    }
+    
+    fn render_import_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Import Readings");
+        ui.add_space(10.0);
+        
+        ui.horizontal(|ui| {
+            ui.label("File (CSV or JSON lines):");
+            ui.text_edit_singleline(&mut self.import_path);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Column mapping:");
+            ui.add(egui::TextEdit::singleline(&mut self.import_column_map).hint_text("temp=temperature, time=timestamp"));
+        });
+        ui.small(format!("Rows without a device id are imported for '{}'.", self.selected_device));
+        
+        ui.horizontal(|ui| {
+            let importing = self.import_request.is_some();
+            if ui.add_enabled(!importing, egui::Button::new("Import")).clicked() {
+                match model::import::ImportOptions::parse_column_map(&self.import_column_map) {
+                    Ok(column_map) => {
+                        self.import_summary = None;
+                        self.import_request = self.send(Command::ImportReadings {
+                            path: std::path::PathBuf::from(self.import_path.trim()),
+                            options: model::import::ImportOptions {
+                                default_device_id: self.selected_device.clone(),
+                                column_map,
+                            },
+                        });
+                    },
+                    Err(e) => self.error_message = Some(e),
+                }
+            }
+            if importing {
+                ui.spinner();
+            }
+        });
+        
+        if let Some(summary) = &self.import_summary {
+            ui.label(format!(
+                "{} rows read: {} readings imported, {} duplicates skipped, {} rows with errors",
+                summary.rows, summary.imported, summary.duplicates, summary.error_count
+            ));
+            
+            if !summary.errors.is_empty() {
+                egui::ScrollArea::vertical()
+                    .id_source("import_errors")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for error in &summary.errors {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(255, 100, 100),
+                                format!("Line {}: {}", error.line, error.message),
+                            );
+                        }
+                        if summary.error_count > summary.errors.len() {
+                            ui.label(format!("... and {} more", summary.error_count - summary.errors.len()));
+                        }
+                    });
+            }
         }
     }
     
-    fn render_threshold_settings(&mut self, ui: &mut egui::Ui, sensor_type: &str) {
-        let display_name = model::sensor_types::get_display_name(sensor_type);
-        let unit = model::sensor_types::get_unit(sensor_type);
+    fn render_data_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Data Retention");
+        ui.add_space(10.0);
+        
+        let mut retention_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Keep raw readings for (days):");
+            retention_changed |= ui.add(egui::DragValue::new(&mut self.raw_retention_days).clamp_range(1..=3650)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Then keep 5-minute averages for (months):");
+            retention_changed |= ui.add(egui::DragValue::new(&mut self.rollup_retention_months).clamp_range(1..=120)).changed();
+        });
+        ui.small("Older data is kept as hourly averages.");
+        
+        if retention_changed {
+            let result = util::preferences::save_raw_retention_days(self.raw_retention_days)
+                .and_then(|_| util::preferences::save_rollup_retention_months(self.rollup_retention_months));
+            if let Err(e) = result {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
+            }
+        }
         
-        // Lấy ngưỡng hiện tại
-        let threshold_result = || -> anyhow::Result<model::sensor_data::SensorThreshold> {
-            let result = repository::sensor_repository::get_threshold(sensor_type)?;
-            let (min_value, max_value) = model::sensor_types::get_default_threshold(sensor_type);
+        ui.horizontal(|ui| {
+            if ui.button("Compact Now").clicked() {
+                self.data_status = None;
+                self.compact_request = self.send(Command::CompactData);
+            }
             
-            Ok(model::sensor_data::SensorThreshold {
-                sensor_type: sensor_type.to_string(),
-                min_value,
-                max_value: result,
-            })
+            // Lưu trữ toàn bộ dữ liệu dạng Parquet, chia thư mục theo tháng
+            let exporting = self.export_request.is_some();
+            if ui.add_enabled(!exporting, egui::Button::new("Export Parquet Archive")).clicked() {
+                let dir_name = format!("archive-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+                self.export_status = None;
+                self.export_request = self.send(Command::ExportArchive {
+                    dir: repository::export_repository::default_export_dir().join(dir_name),
+                    filter: model::export::ReadingFilter::default(),
+                });
+            }
+            
+            if exporting {
+                ui.spinner();
+            } else if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+        
+        ui.add_space(10.0);
+        ui.label("Delete Data");
+        ui.horizontal(|ui| {
+            ui.label("From (YYYY-MM-DD):");
+            ui.add(egui::TextEdit::singleline(&mut self.delete_range_from).desired_width(110.0));
+            ui.label("To:");
+            ui.add(egui::TextEdit::singleline(&mut self.delete_range_to).desired_width(110.0));
+        });
+        ui.checkbox(&mut self.delete_range_all_devices, "All devices (otherwise only the selected device)");
+        
+        ui.horizontal(|ui| {
+            if ui.button("Delete Range").clicked() {
+                // Ngày kết thúc được tính trọn ngày
+                let from = util::date_converter::parse_date(&self.delete_range_from);
+                let to = util::date_converter::parse_date(&self.delete_range_to);
+                match (from, to) {
+                    (Some(from), Some(to)) => {
+                        let device_id = if self.delete_range_all_devices {
+                            None
+                        } else {
+                            Some(self.selected_device.clone())
+                        };
+                        self.pending_delete = Some(DeleteRequest::Range { device_id, from, to: to + 24 * 60 * 60 * 1000 });
+                    },
+                    _ => self.error_message = Some("Please enter dates as YYYY-MM-DD".to_string()),
+                }
+            }
+            
+            if ui.button("Delete All Data").clicked() {
+                self.pending_delete = Some(DeleteRequest::All);
+            }
+        });
+        
+        if let Some(status) = &self.data_status {
+            ui.label(status);
+        }
+    }
+    
+    fn render_delete_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(request) = self.pending_delete.clone() else {
+            return;
+        };
+        
+        let message = match &request {
+            DeleteRequest::All => "Delete all readings and alerts? Devices, thresholds and rules are kept.".to_string(),
+            DeleteRequest::Range { device_id, from, to } => format!(
+                "Delete readings from {} to {} for {}?",
+                util::date_converter::format_date(*from),
+                util::date_converter::format_date(*to - 1),
+                device_id.as_deref().unwrap_or("all devices"),
+            ),
         };
         
-        match threshold_result() {
-            Ok(mut threshold) => {
-                ui.collapsing(format!("{} Threshold", display_name), |ui| {
-                    ui.horizontal(|ui| {
-                        ui.label(format!("Max value ({}): ", unit));
-                        let mut value = threshold.max_value;
-                        if ui.add(egui::DragValue::new(&mut value).speed(0.1)).changed() {
-                            threshold.max_value = value;
-                            // Lưu ngưỡng
-                            if let Err(e) = repository::sensor_repository::set_threshold(sensor_type, value) {
-                                self.error_message = Some(format!("Failed to save threshold: {}", e));
+        egui::Window::new("Confirm Delete")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(message);
+                ui.label("This action cannot be undone.");
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        let command = match request {
+                            DeleteRequest::All => Command::DeleteAllData,
+                            DeleteRequest::Range { device_id, from, to } => Command::DeleteRange { device_id, from, to },
+                        };
+                        self.data_status = None;
+                        self.delete_request = self.send(command);
+                        self.pending_delete = None;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.pending_delete = None;
+                    }
+                });
+            });
+    }
+    
+    fn render_notification_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Notifications");
+        ui.add_space(10.0);
+        
+        if ui.checkbox(&mut self.desktop_notifications, "Show desktop notifications").changed() {
+            if let Err(e) = util::preferences::save_desktop_notifications(self.desktop_notifications) {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+        
+        let mut quiet_hours_changed = false;
+        ui.horizontal(|ui| {
+            quiet_hours_changed |= ui.checkbox(&mut self.quiet_hours_enabled, "Quiet hours from").changed();
+            quiet_hours_changed |= ui.add(egui::DragValue::new(&mut self.quiet_hours_start).clamp_range(0..=23).suffix(":00")).changed();
+            ui.label("to");
+            quiet_hours_changed |= ui.add(egui::DragValue::new(&mut self.quiet_hours_end).clamp_range(0..=23).suffix(":00")).changed();
+        });
+        ui.small("During quiet hours notifications only appear in the notification centre.");
+        
+        if quiet_hours_changed {
+            let result = util::preferences::save_quiet_hours_enabled(self.quiet_hours_enabled)
+                .and_then(|_| util::preferences::save_quiet_hours_start(self.quiet_hours_start))
+                .and_then(|_| util::preferences::save_quiet_hours_end(self.quiet_hours_end));
+            if let Err(e) = result {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+        
+        ui.add_space(5.0);
+        ui.label("Mute sensors:");
+        let mut muted_changed = false;
+        ui.horizontal_wrapped(|ui| {
+            for sensor_type in model::sensor_types::ALL {
+                let mut muted = self.muted_sensors.iter().any(|s| s == sensor_type);
+                if ui.checkbox(&mut muted, model::sensor_types::get_display_name(sensor_type)).changed() {
+                    if muted {
+                        self.muted_sensors.push(sensor_type.to_string());
+                    } else {
+                        self.muted_sensors.retain(|s| s != sensor_type);
+                    }
+                    muted_changed = true;
+                }
+            }
+        });
+        
+        if muted_changed {
+            if let Err(e) = util::preferences::save_muted_sensors(&self.muted_sensors) {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+    }
+    
+    fn render_firebase_settings(&mut self, ui: &mut egui::Ui) {
+        use api::firebase_auth::{AUTH_NONE, AUTH_SECRET, AUTH_SERVICE_ACCOUNT};
+        
+        ui.label("Firebase Realtime Database");
+        ui.add_space(10.0);
+        
+        // Lưu ngay khi sửa, kết nối lại khi rời ô nhập để không kết nối lại sau mỗi phím gõ
+        let mut apply = false;
+        
+        ui.horizontal(|ui| {
+            ui.label("Database URL:");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.firebase_url)
+                .hint_text("https://your-project-id.firebaseio.com"));
+            if response.changed() {
+                if let Err(e) = util::preferences::save_firebase_url(&self.firebase_url) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            apply |= response.lost_focus();
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Readings path:");
+            let response = ui.text_edit_singleline(&mut self.firebase_path);
+            if response.changed() {
+                if let Err(e) = util::preferences::save_firebase_path(&self.firebase_path) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            apply |= response.lost_focus();
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Authentication:");
+            for (mode, label) in [
+                (AUTH_NONE, "None"),
+                (AUTH_SECRET, "Database secret"),
+                (AUTH_SERVICE_ACCOUNT, "Service account"),
+            ] {
+                if ui.radio(self.firebase_auth_mode == mode, label).clicked() && self.firebase_auth_mode != mode {
+                    self.firebase_auth_mode = mode.to_string();
+                    if let Err(e) = util::preferences::save_firebase_auth_mode(mode) {
+                        self.error_message = Some(format!("Failed to save settings: {}", e));
+                    }
+                    apply = true;
+                }
+            }
+        });
+        
+        if self.firebase_auth_mode == AUTH_SECRET {
+            ui.horizontal(|ui| {
+                ui.label("Database secret:");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.firebase_secret).password(true));
+                if response.changed() {
+                    if let Err(e) = util::preferences::save_firebase_secret(&self.firebase_secret) {
+                        self.error_message = Some(format!("Failed to save settings: {}", e));
+                    }
+                }
+                apply |= response.lost_focus();
+            });
+        } else if self.firebase_auth_mode == AUTH_SERVICE_ACCOUNT {
+            ui.horizontal(|ui| {
+                ui.label("Service account key file:");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.firebase_service_account_file)
+                    .hint_text("/path/to/service-account.json"));
+                if response.changed() {
+                    if let Err(e) = util::preferences::save_firebase_service_account_file(&self.firebase_service_account_file) {
+                        self.error_message = Some(format!("Failed to save settings: {}", e));
+                    }
+                }
+                apply |= response.lost_focus();
+            });
+        }
+        
+        if apply {
+            api::firebase_api::reload_settings();
+        }
+        
+        if self.firebase_url.trim().is_empty() {
+            ui.label("Firebase is not used until a database URL is entered.");
+        }
+    }
+    
+    fn render_worker_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Background Polling");
+        ui.add_space(10.0);
+        
+        if ui.checkbox(&mut self.worker_enabled, "Start polling when the app launches").changed() {
+            if let Err(e) = util::preferences::save_worker_enabled(self.worker_enabled) {
+                self.error_message = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+        
+        ui.horizontal(|ui| {
+            ui.label("Poll interval (s):");
+            if ui.add(egui::DragValue::new(&mut self.poll_interval_secs).clamp_range(1..=86400)).changed() {
+                if let Err(e) = util::preferences::save_poll_interval_secs(self.poll_interval_secs) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Jitter (s):");
+            if ui.add(egui::DragValue::new(&mut self.poll_jitter_secs).clamp_range(0..=self.poll_interval_secs)).changed() {
+                if let Err(e) = util::preferences::save_poll_jitter_secs(self.poll_jitter_secs) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Retry attempts:");
+            if ui.add(egui::DragValue::new(&mut self.retry_max_attempts).clamp_range(1..=20)).changed() {
+                if let Err(e) = util::preferences::save_retry_max_attempts(self.retry_max_attempts) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            ui.label("Initial backoff (ms):");
+            if ui.add(egui::DragValue::new(&mut self.retry_base_delay_ms).speed(100.0).clamp_range(0..=60000)).changed() {
+                if let Err(e) = util::preferences::save_retry_base_delay_ms(self.retry_base_delay_ms) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            ui.label("Max backoff (ms):");
+            if ui.add(egui::DragValue::new(&mut self.retry_max_delay_ms).speed(100.0).clamp_range(0..=600000)).changed() {
+                if let Err(e) = util::preferences::save_retry_max_delay_ms(self.retry_max_delay_ms) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            ui.label("Mark offline after failures:");
+            if ui.add(egui::DragValue::new(&mut self.offline_after_failures).clamp_range(1..=100)).changed() {
+                if let Err(e) = util::preferences::save_offline_after_failures(self.offline_after_failures) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            ui.label("Probe offline devices every (s):");
+            if ui.add(egui::DragValue::new(&mut self.offline_retry_secs).clamp_range(0..=86400)).changed() {
+                if let Err(e) = util::preferences::save_offline_retry_secs(self.offline_retry_secs) {
+                    self.error_message = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+        });
+        
+        // Hiển thị trạng thái tiến trình nền
+        let status = worker::sensor_data_worker::worker_status();
+        ui.horizontal(|ui| {
+            if status.stopping {
+                // Chờ lượt lấy dữ liệu hiện tại kết thúc mà không chặn giao diện
+                ui.colored_label(egui::Color32::from_rgb(255, 200, 100), "● Stopping…");
+                ui.ctx().request_repaint_after(Duration::from_millis(250));
+            } else if status.running {
+                ui.colored_label(egui::Color32::from_rgb(100, 255, 100), "● Running");
+                if ui.button("Stop").clicked() {
+                    if let Err(e) = worker::sensor_data_worker::request_stop() {
+                        self.error_message = Some(format!("Failed to stop worker: {}", e));
+                    }
+                }
+                if ui.button("Poll Now").clicked() {
+                    worker::sensor_data_worker::request_poll();
+                }
+            } else {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), "● Stopped");
+                if ui.button("Start").clicked() {
+                    if let Err(e) = worker::sensor_data_worker::start_worker() {
+                        self.error_message = Some(format!("Failed to start worker: {}", e));
+                    }
+                }
+            }
+        });
+        
+        if let Some(last_run) = status.last_run {
+            ui.label(format!("Last poll: {} ({} polls)", util::date_converter::format_timestamp(last_run), status.run_count));
+        }
+        if let Some(last_error) = &status.last_error {
+            ui.colored_label(egui::Color32::from_rgb(255, 100, 100), format!("Last error: {}", last_error));
+        }
+    }
+    
+    fn render_device_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Devices");
+        ui.add_space(10.0);
+        
+        let mut device_to_delete = None;
+        
+        egui::Grid::new("devices_grid")
+            .striped(true)
+            .spacing([20.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("ID");
+                ui.label("Name");
+                ui.label("URL");
+                ui.label("Location");
+                ui.label("");
+                ui.end_row();
+                
+                for device in &self.devices {
+                    ui.label(&device.id);
+                    ui.label(&device.name);
+                    ui.label(&device.url);
+                    ui.label(&device.location);
+                    if device.id != model::device::DEFAULT_DEVICE_ID && ui.button("Remove").clicked() {
+                        device_to_delete = Some(device.id.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+        
+        if let Some(device_id) = device_to_delete {
+            self.devices_request = self.send(Command::DeleteDevice(device_id));
+        }
+        
+        ui.add_space(10.0);
+        ui.collapsing("Add Device", |ui| {
+            egui::Grid::new("new_device_grid")
+                .spacing([20.0, 8.0])
+                .show(ui, |ui| {
+                    ui.label("ID:");
+                    ui.text_edit_singleline(&mut self.new_device.id);
+                    ui.end_row();
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut self.new_device.name);
+                    ui.end_row();
+                    ui.label("URL:");
+                    ui.text_edit_singleline(&mut self.new_device.url);
+                    ui.end_row();
+                    ui.label("Location:");
+                    ui.text_edit_singleline(&mut self.new_device.location);
+                    ui.end_row();
+                });
+            
+            if ui.button("Save Device").clicked() {
+                // Lưu thiết bị mới vào danh sách
+                let device = std::mem::replace(&mut self.new_device, model::device::Device::new("", "", "", ""));
+                self.devices_request = self.send(Command::SaveDevice(device));
+            }
+        });
+    }
+    
+    fn render_rule_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Alert Rules");
+        ui.add_space(10.0);
+        
+        let mut command = None;
+        let mut edit_rule = None;
+        
+        // Danh sách quy tắc hiện có
+        if self.rules.is_empty() {
+            ui.label("No rules defined.");
+        }
+        for rule in &self.rules {
+            ui.horizontal(|ui| {
+                let mut enabled = rule.enabled;
+                if ui.checkbox(&mut enabled, "").changed() {
+                    let mut updated = rule.clone();
+                    updated.enabled = enabled;
+                    command = Some(Command::SaveRule(updated));
+                }
+                ui.label(egui::RichText::new(&rule.name).strong());
+                ui.label(format!("{} ({})", rule.describe(), rule.device_id.as_deref().unwrap_or("all devices")));
+                if ui.button("Edit").clicked() {
+                    edit_rule = Some(rule.clone());
+                }
+                if let Some(id) = rule.id {
+                    if ui.button("Delete").clicked() {
+                        command = Some(Command::DeleteRule(id));
+                    }
+                }
+            });
+        }
+        
+        if let Some(rule) = edit_rule {
+            self.rule_draft = rule;
+        }
+        
+        ui.add_space(10.0);
+        let editor_title = if self.rule_draft.id.is_some() { "Edit Rule" } else { "Add Rule" };
+        ui.collapsing(editor_title, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.rule_draft.name);
+            });
+            
+            ui.horizontal(|ui| {
+                ui.label("Device:");
+                let selected_text = self.rule_draft.device_id.clone().unwrap_or_else(|| "All devices".to_string());
+                egui::ComboBox::from_id_source("rule_device_selector")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(self.rule_draft.device_id.is_none(), "All devices").clicked() {
+                            self.rule_draft.device_id = None;
+                        }
+                        for device in &self.devices {
+                            let selected = self.rule_draft.device_id.as_deref() == Some(device.id.as_str());
+                            if ui.selectable_label(selected, device.display_name()).clicked() {
+                                self.rule_draft.device_id = Some(device.id.clone());
                             }
                         }
                     });
+            });
+            
+            // Các điều kiện đều phải đúng thì quy tắc mới kích hoạt
+            let mut remove_index = None;
+            for (index, condition) in self.rule_draft.conditions.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(if index == 0 { "When" } else { "and" });
+                    ui.label(condition.describe());
+                    if ui.small_button("✖").clicked() {
+                        remove_index = Some(index);
+                    }
                 });
-            },
-            Err(e) => {
-                ui.label(format!("Failed to load threshold for {}: {}", display_name, e));
             }
+            if let Some(index) = remove_index {
+                self.rule_draft.conditions.remove(index);
+            }
+            
+            ui.horizontal(|ui| {
+                let draft = &mut self.condition_draft;
+                egui::ComboBox::from_id_source("rule_condition_sensor")
+                    .selected_text(model::sensor_types::get_display_name(&draft.sensor_type))
+                    .show_ui(ui, |ui| {
+                        for sensor_type in model::sensor_types::ALL {
+                            let display_name = model::sensor_types::get_display_name(sensor_type);
+                            if ui.selectable_label(draft.sensor_type == sensor_type, display_name).clicked() {
+                                draft.sensor_type = sensor_type.to_string();
+                            }
+                        }
+                    });
+                egui::ComboBox::from_id_source("rule_condition_kind")
+                    .selected_text(draft.kind.label())
+                    .show_ui(ui, |ui| {
+                        for kind in ConditionKind::ALL {
+                            if ui.selectable_label(draft.kind == kind, kind.label()).clicked() {
+                                draft.kind = kind;
+                            }
+                        }
+                    });
+                ui.add(egui::DragValue::new(&mut draft.value).speed(0.1));
+                ui.label(model::sensor_types::get_unit(&draft.sensor_type));
+                if draft.kind.has_window() {
+                    ui.label(if draft.kind == ConditionKind::StaysBelow { "for" } else { "within" });
+                    ui.add(egui::DragValue::new(&mut draft.window_mins).clamp_range(1..=60 * 24 * 30));
+                    ui.label("min");
+                }
+                if ui.button("Add Condition").clicked() {
+                    self.rule_draft.conditions.push(self.condition_draft.build());
+                }
+            });
+            
+            let validation = repository::rule_engine::validate_rule(&self.rule_draft);
+            if let Err(e) = &validation {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), e.to_string());
+            }
+            
+            ui.horizontal(|ui| {
+                if ui.add_enabled(validation.is_ok(), egui::Button::new("Save Rule")).clicked() {
+                    let rule = std::mem::replace(&mut self.rule_draft, model::alert_rule::AlertRule::new(""));
+                    command = Some(Command::SaveRule(rule));
+                }
+                if ui.button("Clear").clicked() {
+                    self.rule_draft = model::alert_rule::AlertRule::new("");
+                }
+            });
+        });
+        
+        if let Some(command) = command {
+            self.rules_request = self.send(command);
+        }
+    }
+    
+    fn render_threshold_settings(&mut self, ui: &mut egui::Ui, sensor_type: &str) {
+        let display_name = model::sensor_types::get_display_name(sensor_type);
+        let unit = model::sensor_types::get_unit(sensor_type);
+        let (range_min, range_max) = model::sensor_types::get_valid_range(sensor_type);
+        
+        // Ngưỡng được tải sẵn qua luồng xử lý
+        let Some(saved) = self.thresholds.get(sensor_type).cloned() else {
+            ui.label(format!("Loading threshold for {}...", display_name));
+            return;
+        };
+        
+        let mut command = None;
+        
+        ui.collapsing(format!("{} Threshold", display_name), |ui| {
+            let draft = self.threshold_drafts
+                .entry(sensor_type.to_string())
+                .or_insert_with(|| saved.clone());
+            
+            ui.horizontal(|ui| {
+                ui.label(format!("Min value ({}): ", unit));
+                ui.add(egui::DragValue::new(&mut draft.min_value).speed(0.1).clamp_range(range_min..=range_max));
+                ui.label(format!("Max value ({}): ", unit));
+                ui.add(egui::DragValue::new(&mut draft.max_value).speed(0.1).clamp_range(range_min..=range_max));
+            });
+            
+            ui.horizontal(|ui| {
+                ui.label(format!("Hysteresis ({}): ", unit));
+                ui.add(egui::DragValue::new(&mut draft.hysteresis).speed(0.1).clamp_range(0.0..=range_max - range_min));
+            });
+            
+            // Cảnh báo chỉ được bật khi giá trị vượt ngưỡng đủ lâu
+            ui.horizontal(|ui| {
+                ui.label("Alert after readings: ");
+                ui.add(egui::DragValue::new(&mut draft.min_alert_readings).clamp_range(1..=1000));
+                ui.label("and minutes: ");
+                let mut minutes = draft.min_alert_duration_secs / 60;
+                if ui.add(egui::DragValue::new(&mut minutes).clamp_range(0..=1440)).changed() {
+                    draft.min_alert_duration_secs = minutes * 60;
+                }
+            });
+            
+            // Kiểm tra ngưỡng trước khi cho phép lưu
+            let validation = repository::sensor_repository::validate_threshold(draft);
+            if let Err(e) = &validation {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), e.to_string());
+            }
+            
+            ui.horizontal(|ui| {
+                let can_save = validation.is_ok() && *draft != saved;
+                if ui.add_enabled(can_save, egui::Button::new("Save")).clicked() {
+                    command = Some(Command::SetThreshold(draft.clone()));
+                }
+                if ui.add_enabled(*draft != saved, egui::Button::new("Revert")).clicked() {
+                    *draft = saved.clone();
+                }
+                if ui.button("Reset to default").clicked() {
+                    command = Some(Command::ResetThreshold(sensor_type.to_string()));
+                }
+            });
+        });
+        
+        if let Some(request_id) = command.and_then(|command| self.send(command)) {
+            self.threshold_save_requests.insert(sensor_type.to_string(), request_id);
         }
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use super::*;
+
+pub const BREACH_MIN: &str = "min";
+pub const BREACH_MAX: &str = "max";
+pub const BREACH_RULE: &str = "rule";
+
+/// One threshold excursion of a sensor, from the first alerting reading until values return to normal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub id: Option<i64>,
+    pub device_id: String,
+    pub sensor_type: String,
+    pub start_time: i64,
+    pub end_time: Option<i64>,
+    pub peak_value: f32,
+    pub threshold_breached: String,
+    pub threshold_value: f32,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<i64>,
+    /// Set when the event was raised by a user-defined rule rather than a threshold
+    #[serde(default)]
+    pub rule_id: Option<i64>,
+}
+
+impl AlertEvent {
+    pub fn open(device_id: &str, sensor_type: &str, start_time: i64, value: f32, threshold_breached: &str, threshold_value: f32) -> Self {
+        Self {
+            id: None,
+            device_id: device_id.to_string(),
+            sensor_type: sensor_type.to_string(),
+            start_time,
+            end_time: None,
+            peak_value: value,
+            threshold_breached: threshold_breached.to_string(),
+            threshold_value,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            rule_id: None,
+        }
+    }
+    
+    /// Event for a triggered rule; `sensor_type` and `value` refer to the rule's first condition
+    pub fn open_for_rule(device_id: &str, rule_id: i64, sensor_type: &str, start_time: i64, value: f32) -> Self {
+        let mut event = Self::open(device_id, sensor_type, start_time, value, BREACH_RULE, 0.0);
+        event.rule_id = Some(rule_id);
+        event
+    }
+    
+    /// Whether `value` is further past the breached threshold than the current peak
+    pub fn is_new_peak(&self, value: f32) -> bool {
+        if self.threshold_breached == BREACH_MIN {
+            value < self.peak_value
+        } else {
+            value > self.peak_value
+        }
+    }
+}
+
+/// Open alerts plus recent history, as shown on the Alerts tab
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertOverview {
+    pub open: Vec<AlertEvent>,
+    pub history: Vec<AlertEvent>,
+}
@@ -0,0 +1,94 @@
+use super::*;
+use super::sensor_types;
+
+/// A single test over one sensor's recent history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// Latest value is above `value`
+    Above { sensor_type: String, value: f32 },
+    /// Latest value is below `value`
+    Below { sensor_type: String, value: f32 },
+    /// Latest value is more than `delta` above the lowest value of the last `window_secs`
+    RisesBy { sensor_type: String, delta: f32, window_secs: i64 },
+    /// Latest value is more than `delta` below the highest value of the last `window_secs`
+    FallsBy { sensor_type: String, delta: f32, window_secs: i64 },
+    /// Every value of the last `window_secs` is below `value` (e.g. no rain for 24 h)
+    StaysBelow { sensor_type: String, value: f32, window_secs: i64 },
+}
+
+impl RuleCondition {
+    pub fn sensor_type(&self) -> &str {
+        match self {
+            Self::Above { sensor_type, .. }
+            | Self::Below { sensor_type, .. }
+            | Self::RisesBy { sensor_type, .. }
+            | Self::FallsBy { sensor_type, .. }
+            | Self::StaysBelow { sensor_type, .. } => sensor_type,
+        }
+    }
+    
+    /// History window the condition looks at, 0 for conditions on the latest value only
+    pub fn window_secs(&self) -> i64 {
+        match self {
+            Self::RisesBy { window_secs, .. }
+            | Self::FallsBy { window_secs, .. }
+            | Self::StaysBelow { window_secs, .. } => *window_secs,
+            Self::Above { .. } | Self::Below { .. } => 0,
+        }
+    }
+    
+    /// Human readable form, e.g. "Temperature rises by 5 °C within 10 min"
+    pub fn describe(&self) -> String {
+        let name = sensor_types::get_display_name(self.sensor_type());
+        let unit = sensor_types::get_unit(self.sensor_type());
+        match self {
+            Self::Above { value, .. } => format!("{} above {} {}", name, value, unit),
+            Self::Below { value, .. } => format!("{} below {} {}", name, value, unit),
+            Self::RisesBy { delta, window_secs, .. } => {
+                format!("{} rises by {} {} within {} min", name, delta, unit, window_secs / 60)
+            }
+            Self::FallsBy { delta, window_secs, .. } => {
+                format!("{} falls by {} {} within {} min", name, delta, unit, window_secs / 60)
+            }
+            Self::StaysBelow { value, window_secs, .. } => {
+                format!("{} stays below {} {} for {} min", name, value, unit, window_secs / 60)
+            }
+        }
+    }
+}
+
+/// User-defined alert rule; all conditions must hold for the rule to fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: Option<i64>,
+    pub name: String,
+    /// Device the rule applies to, or every device when `None`
+    pub device_id: Option<String>,
+    pub conditions: Vec<RuleCondition>,
+    pub enabled: bool,
+}
+
+impl AlertRule {
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: None,
+            name: name.to_string(),
+            device_id: None,
+            conditions: Vec::new(),
+            enabled: true,
+        }
+    }
+    
+    pub fn applies_to(&self, device_id: &str) -> bool {
+        self.device_id.as_deref().is_none_or(|id| id == device_id)
+    }
+    
+    pub fn describe(&self) -> String {
+        self.conditions
+            .iter()
+            .map(RuleCondition::describe)
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+}
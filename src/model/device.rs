@@ -0,0 +1,61 @@
+use super::*;
+
+/// Device id used for readings from nodes that do not report their own id
+pub const DEFAULT_DEVICE_ID: &str = "default";
+
+pub fn default_device_id() -> String {
+    DEFAULT_DEVICE_ID.to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub location: String,
+}
+
+impl Device {
+    pub fn new(id: &str, name: &str, url: &str, location: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            url: url.to_string(),
+            location: location.to_string(),
+        }
+    }
+    
+    /// Name shown in device pickers, falling back to the id
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() { &self.id } else { &self.name }
+    }
+}
+
+pub const STATUS_ONLINE: &str = "online";
+pub const STATUS_OFFLINE: &str = "offline";
+
+/// Recorded change in whether a device answers polls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStatusEvent {
+    pub id: Option<i64>,
+    pub device_id: String,
+    pub status: String,
+    pub timestamp: i64,
+    pub reason: String,
+}
+
+impl DeviceStatusEvent {
+    pub fn new(device_id: &str, status: &str, timestamp: i64, reason: &str) -> Self {
+        Self {
+            id: None,
+            device_id: device_id.to_string(),
+            status: status.to_string(),
+            timestamp,
+            reason: reason.to_string(),
+        }
+    }
+    
+    pub fn is_offline(&self) -> bool {
+        self.status == STATUS_OFFLINE
+    }
+}
@@ -0,0 +1,29 @@
+use super::*;
+
+/// Which readings an export includes; `None` fields do not filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReadingFilter {
+    pub device_id: Option<String>,
+    pub sensor_type: Option<String>,
+    /// Inclusive start timestamp (ms)
+    pub from: Option<i64>,
+    /// Exclusive end timestamp (ms)
+    pub to: Option<i64>,
+}
+
+impl ReadingFilter {
+    /// Sensor types the export has columns for
+    pub fn sensor_types(&self) -> Vec<&str> {
+        match &self.sensor_type {
+            Some(sensor_type) => vec![sensor_type.as_str()],
+            None => sensor_types::ALL.to_vec(),
+        }
+    }
+}
+
+/// Outcome of a finished export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub rows: usize,
+}
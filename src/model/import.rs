@@ -0,0 +1,53 @@
+use super::*;
+use std::collections::HashMap;
+
+/// A row of an import file that could not be used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRowError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub rows: usize,
+    pub imported: usize,
+    pub duplicates: usize,
+    pub error_count: usize,
+    /// The first errors, in file order; `error_count` has the full count
+    pub errors: Vec<ImportRowError>,
+}
+
+/// How to read an import file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// Device the readings belong to when a row has no `device_id`
+    pub default_device_id: String,
+    /// Source column or key name → field name (`device_id`, `sensor_type`, `value`,
+    /// `timestamp`, `timestamp_ms` or a sensor type)
+    pub column_map: HashMap<String, String>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            default_device_id: device::DEFAULT_DEVICE_ID.to_string(),
+            column_map: HashMap::new(),
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Parse a mapping written as `source=field, source2=field2`
+    pub fn parse_column_map(text: &str) -> Result<HashMap<String, String>, String> {
+        let mut map = HashMap::new();
+        for pair in text.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (source, field) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected source=field, got '{}'", pair))?;
+            map.insert(source.trim().to_lowercase(), field.trim().to_lowercase());
+        }
+        Ok(map)
+    }
+}
@@ -0,0 +1,74 @@
+use super::*;
+use crate::model::alert::AlertEvent;
+use crate::model::sensor_data::SensorReading;
+
+/// Something a live stream client is told about as it happens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveEvent {
+    /// Increases with every event; clients send the last one they saw to resume
+    pub id: u64,
+    pub device_id: String,
+    #[serde(flatten)]
+    pub data: LiveEventData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEventData {
+    /// A batch of readings was stored
+    Readings { readings: Vec<SensorReading> },
+    AlertOpened { alert: AlertEvent },
+    AlertClosed { alert: AlertEvent },
+}
+
+impl LiveEventData {
+    /// Event name used on the wire, e.g. the SSE `event:` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEventData::Readings { .. } => "readings",
+            LiveEventData::AlertOpened { .. } => "alert_opened",
+            LiveEventData::AlertClosed { .. } => "alert_closed",
+        }
+    }
+}
+
+/// Which events one client wants; empty fields do not filter
+#[derive(Debug, Clone, Default)]
+pub struct LiveFilter {
+    pub device_id: Option<String>,
+    pub sensor_types: Vec<String>,
+}
+
+impl LiveFilter {
+    fn wants_sensor(&self, sensor_type: &str) -> bool {
+        self.sensor_types.is_empty() || self.sensor_types.iter().any(|s| s == sensor_type)
+    }
+    
+    /// The event as this client should see it, with readings narrowed to the wanted sensors,
+    /// or `None` when nothing in it matches
+    pub fn apply(&self, event: &LiveEvent) -> Option<LiveEvent> {
+        if self.device_id.as_ref().is_some_and(|device_id| *device_id != event.device_id) {
+            return None;
+        }
+        
+        match &event.data {
+            LiveEventData::Readings { readings } => {
+                let readings: Vec<SensorReading> = readings
+                    .iter()
+                    .filter(|reading| self.wants_sensor(&reading.sensor_type))
+                    .cloned()
+                    .collect();
+                if readings.is_empty() {
+                    return None;
+                }
+                Some(LiveEvent {
+                    data: LiveEventData::Readings { readings },
+                    ..event.clone()
+                })
+            }
+            LiveEventData::AlertOpened { alert } | LiveEventData::AlertClosed { alert } => {
+                self.wants_sensor(&alert.sensor_type).then(|| event.clone())
+            }
+        }
+    }
+}
@@ -1,5 +1,12 @@
+pub mod alert;
+pub mod alert_rule;
+pub mod device;
+pub mod export;
+pub mod import;
+pub mod live_event;
 pub mod sensor_data;
 pub mod sensor_types;
+pub mod statistics;
 
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc}; 
\ No newline at end of file
@@ -1,7 +1,10 @@
 use super::*;
+use super::device::default_device_id;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ESP32SensorData {
+    #[serde(default = "default_device_id")]
+    pub device_id: String,
     pub temperature: f32,
     pub humidity: f32,
     pub water_level: f32,
@@ -15,16 +18,53 @@ pub struct ESP32SensorData {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorReading {
     pub id: Option<i64>,
+    #[serde(default = "default_device_id")]
+    pub device_id: String,
     pub sensor_type: String,
     pub value: f32,
     pub timestamp: i64,
     pub is_alert: bool,
 }
 
+/// Readings of one sensor summarised over a time bucket starting at `bucket_start`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregatedReading {
+    pub bucket_start: i64,
+    pub min_value: f32,
+    pub max_value: f32,
+    pub avg_value: f32,
+    pub count: i64,
+    pub alert_count: i64,
+}
+
+/// One row of a rollup table: a sensor's readings averaged over the bucket starting at `bucket_start`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupReading {
+    pub device_id: String,
+    pub sensor_type: String,
+    pub bucket_start: i64,
+    pub avg_value: f32,
+    pub min_value: f32,
+    pub max_value: f32,
+    pub sample_count: i64,
+}
+
+/// Bucketed history of one sensor over `[from, to)`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AggregatedHistory {
+    pub device_id: String,
+    pub sensor_type: String,
+    pub from: i64,
+    pub to: i64,
+    pub bucket_ms: i64,
+    pub buckets: Vec<AggregatedReading>,
+}
+
 impl SensorReading {
-    pub fn new(sensor_type: &str, value: f32, timestamp: i64, is_alert: bool) -> Self {
+    pub fn new(device_id: &str, sensor_type: &str, value: f32, timestamp: i64, is_alert: bool) -> Self {
         Self {
             id: None,
+            device_id: device_id.to_string(),
             sensor_type: sensor_type.to_string(),
             value,
             timestamp,
@@ -33,23 +73,55 @@ impl SensorReading {
     }
     
     pub fn from_esp32_data(data: &ESP32SensorData) -> Vec<Self> {
+        let device_id = data.device_id.as_str();
         let timestamp = data.timestamp;
         
         vec![
-            Self::new("temperature", data.temperature, timestamp, false),
-            Self::new("humidity", data.humidity, timestamp, false),
-            Self::new("water_level", data.water_level, timestamp, false),
-            Self::new("ph", data.ph, timestamp, false),
-            Self::new("salinity", data.salinity, timestamp, false),
-            Self::new("rain", if data.rain { 1.0 } else { 0.0 }, timestamp, false),
-            Self::new("soil_moisture", data.soil_moisture, timestamp, false),
+            Self::new(device_id, "temperature", data.temperature, timestamp, false),
+            Self::new(device_id, "humidity", data.humidity, timestamp, false),
+            Self::new(device_id, "water_level", data.water_level, timestamp, false),
+            Self::new(device_id, "ph", data.ph, timestamp, false),
+            Self::new(device_id, "salinity", data.salinity, timestamp, false),
+            Self::new(device_id, "rain", if data.rain { 1.0 } else { 0.0 }, timestamp, false),
+            Self::new(device_id, "soil_moisture", data.soil_moisture, timestamp, false),
         ]
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SensorThreshold {
     pub sensor_type: String,
     pub min_value: f32,
     pub max_value: f32,
-} 
\ No newline at end of file
+    /// An active alert only clears once the value is this far back inside the band
+    #[serde(default)]
+    pub hysteresis: f32,
+    /// Consecutive out-of-band readings required before an alert is raised
+    #[serde(default = "default_min_alert_readings")]
+    pub min_alert_readings: u32,
+    /// How long values must stay out of band before an alert is raised
+    #[serde(default)]
+    pub min_alert_duration_secs: i64,
+}
+
+fn default_min_alert_readings() -> u32 {
+    1
+}
+
+impl SensorThreshold {
+    pub fn new(sensor_type: &str, min_value: f32, max_value: f32) -> Self {
+        Self {
+            sensor_type: sensor_type.to_string(),
+            min_value,
+            max_value,
+            hysteresis: 0.0,
+            min_alert_readings: default_min_alert_readings(),
+            min_alert_duration_secs: 0,
+        }
+    }
+    
+    pub fn default_for(sensor_type: &str) -> Self {
+        let (min_value, max_value) = super::sensor_types::get_default_threshold(sensor_type);
+        Self::new(sensor_type, min_value, max_value)
+    }
+}
\ No newline at end of file
@@ -6,6 +6,17 @@ pub const SALINITY: &str = "salinity";
 pub const RAIN: &str = "rain";
 pub const SOIL_MOISTURE: &str = "soil_moisture";
 
+/// Every sensor type reported by the ESP32, in display order
+pub const ALL: [&str; 7] = [
+    TEMPERATURE,
+    HUMIDITY,
+    WATER_LEVEL,
+    PH,
+    SALINITY,
+    RAIN,
+    SOIL_MOISTURE,
+];
+
 pub fn get_display_name(sensor_type: &str) -> &'static str {
     match sensor_type {
         TEMPERATURE => "Temperature",
@@ -43,4 +54,18 @@ pub fn get_default_threshold(sensor_type: &str) -> (f32, f32) {
         SOIL_MOISTURE => (20.0, 80.0),
         _ => (0.0, 100.0),
     }
-} 
\ No newline at end of file
+} 
+
+/// Physically meaningful range for a sensor, used to validate thresholds
+pub fn get_valid_range(sensor_type: &str) -> (f32, f32) {
+    match sensor_type {
+        TEMPERATURE => (-40.0, 80.0),
+        HUMIDITY => (0.0, 100.0),
+        WATER_LEVEL => (0.0, 500.0),
+        PH => (0.0, 14.0),
+        SALINITY => (0.0, 300.0),
+        RAIN => (0.0, 1.0),
+        SOIL_MOISTURE => (0.0, 100.0),
+        _ => (f32::MIN, f32::MAX),
+    }
+}
\ No newline at end of file
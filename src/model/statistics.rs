@@ -0,0 +1,37 @@
+use super::*;
+
+/// Summary of one sensor's raw readings over `[from, to)`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorStatistics {
+    pub device_id: String,
+    pub sensor_type: String,
+    pub from: i64,
+    pub to: i64,
+    pub count: i64,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub mean: Option<f32>,
+    pub median: Option<f32>,
+    pub std_dev: Option<f32>,
+    pub p5: Option<f32>,
+    pub p95: Option<f32>,
+    /// Time spent above the sensor's maximum threshold
+    pub time_above_threshold_ms: i64,
+    /// Time spent below the sensor's minimum threshold
+    pub time_below_threshold_ms: i64,
+    /// Time during which readings were flagged as alerts
+    pub time_in_alert_ms: i64,
+    /// Total time covered by readings, the base for the durations above
+    pub covered_ms: i64,
+}
+
+impl SensorStatistics {
+    /// Share of the covered time spent in a state, as a percentage
+    pub fn percent_of_covered(&self, duration_ms: i64) -> f32 {
+        if self.covered_ms > 0 {
+            duration_ms as f32 * 100.0 / self.covered_ms as f32
+        } else {
+            0.0
+        }
+    }
+}
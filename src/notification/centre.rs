@@ -0,0 +1,51 @@
+use super::Notification;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Oldest notifications are dropped beyond this many
+const MAX_NOTIFICATIONS: usize = 200;
+
+struct Centre {
+    next_id: u64,
+    items: VecDeque<Notification>,
+}
+
+static CENTRE: Lazy<Mutex<Centre>> = Lazy::new(|| Mutex::new(Centre {
+    next_id: 1,
+    items: VecDeque::new(),
+}));
+
+/// Store a notification, assigning its id
+pub fn push(mut notification: Notification) -> Notification {
+    if let Ok(mut centre) = CENTRE.lock() {
+        notification.id = centre.next_id;
+        centre.next_id += 1;
+        centre.items.push_front(notification.clone());
+        centre.items.truncate(MAX_NOTIFICATIONS);
+    }
+    notification
+}
+
+/// All stored notifications, newest first
+pub fn notifications() -> Vec<Notification> {
+    CENTRE.lock().map(|centre| centre.items.iter().cloned().collect()).unwrap_or_default()
+}
+
+pub fn unread_count() -> usize {
+    CENTRE.lock().map(|centre| centre.items.iter().filter(|n| !n.read).count()).unwrap_or(0)
+}
+
+pub fn mark_all_read() {
+    if let Ok(mut centre) = CENTRE.lock() {
+        for notification in centre.items.iter_mut() {
+            notification.read = true;
+        }
+    }
+}
+
+pub fn clear() {
+    if let Ok(mut centre) = CENTRE.lock() {
+        centre.items.clear();
+    }
+}
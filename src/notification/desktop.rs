@@ -0,0 +1,33 @@
+use anyhow::Result;
+use super::{Notification, NotificationBackend};
+use notify_rust::{Notification as DesktopNotification, Timeout, Urgency};
+
+const APP_NAME: &str = "Sensor Monitor";
+
+/// Freedesktop notifications over the D-Bus session bus
+pub struct DesktopBackend;
+
+impl NotificationBackend for DesktopBackend {
+    fn name(&self) -> &str {
+        "freedesktop"
+    }
+    
+    fn show(&self, notification: &Notification) -> Result<()> {
+        let (urgency, icon) = if notification.urgent {
+            (Urgency::Critical, "dialog-warning")
+        } else {
+            (Urgency::Normal, "dialog-information")
+        };
+        
+        DesktopNotification::new()
+            .appname(APP_NAME)
+            .summary(&notification.title)
+            .body(&notification.body)
+            .icon(icon)
+            .urgency(urgency)
+            .timeout(Timeout::Default)
+            .show()?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::model::live_event::{LiveEvent, LiveEventData};
+use crate::util::date_converter;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Recent events kept so reconnecting clients can catch up
+const BACKLOG_SIZE: usize = 1000;
+
+/// Events a slow subscriber may fall behind by before it starts missing some
+const CHANNEL_CAPACITY: usize = 256;
+
+struct Feed {
+    next_id: u64,
+    backlog: VecDeque<LiveEvent>,
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+// Ids start at the current time in ms so they keep increasing across restarts
+static FEED: Lazy<Mutex<Feed>> = Lazy::new(|| Mutex::new(Feed {
+    next_id: date_converter::current_timestamp().max(1) as u64,
+    backlog: VecDeque::new(),
+    sender: broadcast::channel(CHANNEL_CAPACITY).0,
+}));
+
+/// Send an event to every connected live stream client, assigning its id
+pub fn publish(device_id: &str, data: LiveEventData) {
+    if let Ok(mut feed) = FEED.lock() {
+        let event = LiveEvent {
+            id: feed.next_id,
+            device_id: device_id.to_string(),
+            data,
+        };
+        feed.next_id += 1;
+        
+        if feed.backlog.len() >= BACKLOG_SIZE {
+            feed.backlog.pop_front();
+        }
+        feed.backlog.push_back(event.clone());
+        
+        // Sending only fails when nobody is listening
+        let _ = feed.sender.send(event);
+    }
+}
+
+/// Receiver for events published from now on
+pub fn subscribe() -> broadcast::Receiver<LiveEvent> {
+    match FEED.lock() {
+        Ok(feed) => feed.sender.subscribe(),
+        Err(poisoned) => poisoned.into_inner().sender.subscribe(),
+    }
+}
+
+/// Stored events newer than `last_id`, oldest first
+pub fn events_since(last_id: u64) -> Vec<LiveEvent> {
+    FEED.lock()
+        .map(|feed| feed.backlog.iter().filter(|event| event.id > last_id).cloned().collect())
+        .unwrap_or_default()
+}
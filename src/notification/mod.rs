@@ -0,0 +1,137 @@
+pub mod centre;
+pub mod live;
+#[cfg(target_os = "linux")]
+pub mod desktop;
+
+use anyhow::Result;
+use chrono::{Local, TimeZone, Timelike};
+use crate::util::{date_converter, preferences};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::RwLock;
+
+/// A message raised by the alert logic
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub id: u64,
+    pub title: String,
+    pub body: String,
+    pub device_id: String,
+    pub sensor_type: Option<String>,
+    pub timestamp: i64,
+    pub urgent: bool,
+    pub read: bool,
+}
+
+impl Notification {
+    pub fn new(title: &str, body: &str, device_id: &str, sensor_type: Option<&str>, urgent: bool) -> Self {
+        Self {
+            id: 0,
+            title: title.to_string(),
+            body: body.to_string(),
+            device_id: device_id.to_string(),
+            sensor_type: sensor_type.map(|s| s.to_string()),
+            timestamp: date_converter::current_timestamp(),
+            urgent,
+            read: false,
+        }
+    }
+}
+
+/// Somewhere notifications can be shown outside the app window
+pub trait NotificationBackend: Send + Sync {
+    fn name(&self) -> &str;
+    fn show(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Notification preferences, read fresh for every notification
+#[derive(Debug, Clone)]
+pub struct NotificationSettings {
+    pub desktop_enabled: bool,
+    pub quiet_hours_enabled: bool,
+    pub quiet_hours_start: u32,
+    pub quiet_hours_end: u32,
+    pub muted_sensors: Vec<String>,
+}
+
+impl NotificationSettings {
+    pub fn from_preferences() -> Self {
+        Self {
+            desktop_enabled: preferences::load_desktop_notifications().unwrap_or(true),
+            quiet_hours_enabled: preferences::load_quiet_hours_enabled().unwrap_or(false),
+            quiet_hours_start: preferences::load_quiet_hours_start().unwrap_or(22),
+            quiet_hours_end: preferences::load_quiet_hours_end().unwrap_or(7),
+            muted_sensors: preferences::load_muted_sensors().unwrap_or_default(),
+        }
+    }
+    
+    pub fn is_muted(&self, sensor_type: &str) -> bool {
+        self.muted_sensors.iter().any(|s| s == sensor_type)
+    }
+    
+    /// Whether a local hour falls inside quiet hours; the window may wrap past midnight
+    pub fn is_quiet_hour(&self, hour: u32) -> bool {
+        if !self.quiet_hours_enabled || self.quiet_hours_start == self.quiet_hours_end {
+            return false;
+        }
+        if self.quiet_hours_start < self.quiet_hours_end {
+            hour >= self.quiet_hours_start && hour < self.quiet_hours_end
+        } else {
+            hour >= self.quiet_hours_start || hour < self.quiet_hours_end
+        }
+    }
+}
+
+static BACKENDS: Lazy<RwLock<Vec<Box<dyn NotificationBackend>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Register the backends available on this platform
+pub fn init_backends() {
+    #[cfg(target_os = "linux")]
+    register_backend(Box::new(desktop::DesktopBackend));
+}
+
+pub fn register_backend(backend: Box<dyn NotificationBackend>) {
+    if let Ok(mut backends) = BACKENDS.write() {
+        log::info!("Registered notification backend: {}", backend.name());
+        backends.push(backend);
+    }
+}
+
+/// Deliver a notification.
+///
+/// - Muted sensors are dropped entirely.
+/// - Everything else goes to the in-app notification centre.
+/// - External backends are skipped during quiet hours or when desktop notifications are off.
+pub fn notify(notification: Notification) {
+    let settings = NotificationSettings::from_preferences();
+    
+    if let Some(sensor_type) = &notification.sensor_type {
+        if settings.is_muted(sensor_type) {
+            log::debug!("Notification for muted sensor {} dropped", sensor_type);
+            return;
+        }
+    }
+    
+    let notification = centre::push(notification);
+    
+    if !settings.desktop_enabled {
+        return;
+    }
+    
+    let hour = Local.timestamp_millis_opt(notification.timestamp)
+        .single()
+        .map(|time| time.hour())
+        .unwrap_or(0);
+    if settings.is_quiet_hour(hour) {
+        log::debug!("Quiet hours, not showing '{}' outside the app", notification.title);
+        return;
+    }
+    
+    if let Ok(backends) = BACKENDS.read() {
+        for backend in backends.iter() {
+            if let Err(e) = backend.show(&notification) {
+                log::warn!("Notification backend {} failed: {}", backend.name(), e);
+            }
+        }
+    }
+}
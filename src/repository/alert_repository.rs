@@ -0,0 +1,312 @@
+use anyhow::{Result, anyhow};
+use crate::data::dao::{alert_event_dao, sensor_reading_dao, sensor_threshold_dao};
+use crate::model::alert::{AlertEvent, AlertOverview, BREACH_MAX, BREACH_MIN};
+use crate::model::live_event::LiveEventData;
+use crate::model::sensor_data::{SensorReading, SensorThreshold};
+use crate::model::sensor_types;
+use crate::notification::{self, live, Notification};
+use crate::util::date_converter;
+
+/// Number of closed alert events shown in the history list
+const ALERT_HISTORY_LIMIT: i64 = 100;
+
+/// Upper bound on earlier readings inspected when checking a minimum-duration rule
+const MAX_PERSISTENCE_LOOKBACK: i64 = 1000;
+
+/// Which bound a value breaches, together with that bound's value
+pub fn threshold_breach(value: f32, threshold: &SensorThreshold) -> Option<(&'static str, f32)> {
+    if value < threshold.min_value {
+        Some((BREACH_MIN, threshold.min_value))
+    } else if value > threshold.max_value {
+        Some((BREACH_MAX, threshold.max_value))
+    } else {
+        None
+    }
+}
+
+/// Decide whether a reading is in alert, taking the sensor's previous state into account.
+///
+/// - While an alert is active it only clears once the value is back inside the band
+///   narrowed by `hysteresis` on both sides.
+/// - A new alert is only raised once the value has been out of band for at least
+///   `min_alert_readings` consecutive readings and `min_alert_duration_secs`.
+pub fn evaluate_alert(reading: &SensorReading, threshold: &SensorThreshold) -> Result<bool> {
+    let breached = threshold_breach(reading.value, threshold).is_some();
+    
+    let needs_history = threshold.hysteresis > 0.0
+        || threshold.min_alert_readings > 1
+        || threshold.min_alert_duration_secs > 0;
+    if !needs_history {
+        return Ok(breached);
+    }
+    
+    let lookback = if threshold.min_alert_duration_secs > 0 {
+        MAX_PERSISTENCE_LOOKBACK
+    } else {
+        (threshold.min_alert_readings as i64).max(1)
+    };
+    let previous = sensor_reading_dao::get_history_before(
+        &reading.device_id,
+        &reading.sensor_type,
+        reading.timestamp,
+        lookback,
+    )?;
+    
+    // An active alert stays active until the value clears the hysteresis band
+    if previous.first().is_some_and(|r| r.is_alert) {
+        let cleared = reading.value >= threshold.min_value + threshold.hysteresis
+            && reading.value <= threshold.max_value - threshold.hysteresis;
+        return Ok(!cleared);
+    }
+    
+    if !breached {
+        return Ok(false);
+    }
+    
+    // Count the current run of out-of-band readings, including this one
+    let mut streak_readings = 1;
+    let mut streak_start = reading.timestamp;
+    for earlier in &previous {
+        if threshold_breach(earlier.value, threshold).is_none() {
+            break;
+        }
+        streak_readings += 1;
+        streak_start = earlier.timestamp;
+    }
+    
+    let persisted_readings = streak_readings >= threshold.min_alert_readings.max(1);
+    let persisted_duration = reading.timestamp - streak_start >= threshold.min_alert_duration_secs * 1000;
+    
+    Ok(persisted_readings && persisted_duration)
+}
+
+/// Open, extend or close alert events for a batch of readings whose `is_alert` flags are already set.
+///
+/// For each device and sensor there is at most one open event:
+/// - normal → alert opens an event,
+/// - alert → alert keeps it open and tracks the peak value (switching side closes it and opens a new one),
+/// - alert → normal closes it at the time of the normal reading.
+pub fn update_alert_events(readings: &[SensorReading]) -> Result<()> {
+    for reading in readings {
+        let open_event = alert_event_dao::get_open_event(&reading.device_id, &reading.sensor_type)?;
+        
+        if !reading.is_alert {
+            if let Some(event) = open_event {
+                close_event(&event, reading.timestamp)?;
+            }
+            continue;
+        }
+        
+        let threshold = sensor_threshold_dao::get_threshold(&reading.sensor_type)?;
+        let Some((breached, threshold_value)) = threshold_breach(reading.value, &threshold) else {
+            continue;
+        };
+        
+        match open_event {
+            Some(event) if event.threshold_breached == breached => {
+                if event.is_new_peak(reading.value) {
+                    if let Some(id) = event.id {
+                        alert_event_dao::update_peak(id, reading.value)?;
+                    }
+                }
+            }
+            Some(event) => {
+                // The value jumped from one side of the band to the other
+                close_event(&event, reading.timestamp)?;
+                open_event_for(reading, breached, threshold_value)?;
+            }
+            None => {
+                open_event_for(reading, breached, threshold_value)?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn open_event_for(reading: &SensorReading, breached: &str, threshold_value: f32) -> Result<AlertEvent> {
+    let mut event = AlertEvent::open(
+        &reading.device_id,
+        &reading.sensor_type,
+        reading.timestamp,
+        reading.value,
+        breached,
+        threshold_value,
+    );
+    event.id = Some(alert_event_dao::insert(&event)?);
+    log::info!(
+        "Alert opened for {} on {}: {} {} threshold {}",
+        reading.sensor_type, reading.device_id, reading.value, breached, threshold_value
+    );
+    notify_opened(&event);
+    live::publish(&event.device_id, LiveEventData::AlertOpened { alert: event.clone() });
+    Ok(event)
+}
+
+fn close_event(event: &AlertEvent, end_time: i64) -> Result<()> {
+    let id = event.id.ok_or_else(|| anyhow!("Alert event has no id"))?;
+    alert_event_dao::close(id, end_time)?;
+    log::info!("Alert closed for {} on {}", event.sensor_type, event.device_id);
+    notify_closed(event);
+    live::publish(&event.device_id, LiveEventData::AlertClosed {
+        alert: AlertEvent { end_time: Some(end_time), ..event.clone() },
+    });
+    Ok(())
+}
+
+fn notify_opened(event: &AlertEvent) {
+    let name = sensor_types::get_display_name(&event.sensor_type);
+    let unit = sensor_types::get_unit(&event.sensor_type);
+    let direction = if event.threshold_breached == BREACH_MIN { "below" } else { "above" };
+    
+    notification::notify(Notification::new(
+        &format!("{} alert on {}", name, event.device_id),
+        &format!("{:.1}{} is {} the limit of {:.1}{}", event.peak_value, unit, direction, event.threshold_value, unit),
+        &event.device_id,
+        Some(&event.sensor_type),
+        true,
+    ));
+}
+
+fn notify_closed(event: &AlertEvent) {
+    let name = sensor_types::get_display_name(&event.sensor_type);
+    
+    notification::notify(Notification::new(
+        &format!("{} back to normal on {}", name, event.device_id),
+        &format!("Peak was {:.1}{}", event.peak_value, sensor_types::get_unit(&event.sensor_type)),
+        &event.device_id,
+        Some(&event.sensor_type),
+        false,
+    ));
+}
+
+/// Open alerts and recent closed ones
+pub fn fetch_alert_overview() -> Result<AlertOverview> {
+    Ok(AlertOverview {
+        open: alert_event_dao::get_open_events()?,
+        history: alert_event_dao::get_closed_events(ALERT_HISTORY_LIMIT)?,
+    })
+}
+
+/// Acknowledge an alert event on behalf of `acknowledged_by`
+pub fn acknowledge_alert(id: i64, acknowledged_by: &str) -> Result<()> {
+    let acknowledged_by = acknowledged_by.trim();
+    if acknowledged_by.is_empty() {
+        return Err(anyhow!("Please enter who is acknowledging the alert"));
+    }
+    
+    if !alert_event_dao::acknowledge(id, acknowledged_by, date_converter::current_timestamp())? {
+        return Err(anyhow!("Alert {} does not exist or was already acknowledged", id));
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    
+    const T0: i64 = 1_650_000_000_000;
+    
+    /// Evaluate and store readings one minute apart, returning the alert flag of each
+    fn evaluate_series(device_id: &str, threshold: &SensorThreshold, values: &[f32]) -> Vec<bool> {
+        values.iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let mut reading = SensorReading::new(device_id, &threshold.sensor_type, value, T0 + i as i64 * 60_000, false);
+                reading.is_alert = evaluate_alert(&reading, threshold).unwrap();
+                sensor_reading_dao::insert_batch(&[reading.clone()]).unwrap();
+                reading.is_alert
+            })
+            .collect()
+    }
+    
+    fn events_for(device_id: &str) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        alert_event_dao::for_each_event(Some(device_id), None, None, |event| {
+            events.push(event);
+            Ok(())
+        }).unwrap();
+        events
+    }
+    
+    #[test]
+    fn alert_clears_only_inside_the_hysteresis_band() {
+        data::init_test_database();
+        let mut threshold = SensorThreshold::new(sensor_types::TEMPERATURE, 0.0, 40.0);
+        threshold.hysteresis = 2.0;
+        
+        let flags = evaluate_series("hysteresis-test", &threshold, &[35.0, 41.0, 39.0, 38.5, 37.9, 39.0]);
+        
+        assert_eq!(flags, vec![false, true, true, true, false, false]);
+    }
+    
+    #[test]
+    fn alert_waits_for_min_alert_readings() {
+        data::init_test_database();
+        let mut threshold = SensorThreshold::new(sensor_types::TEMPERATURE, 0.0, 40.0);
+        threshold.min_alert_readings = 3;
+        
+        let flags = evaluate_series("min-readings-test", &threshold, &[41.0, 42.0, 43.0, 30.0, 41.0]);
+        
+        assert_eq!(flags, vec![false, false, true, false, false]);
+    }
+    
+    #[test]
+    fn alert_waits_for_min_alert_duration() {
+        data::init_test_database();
+        let mut threshold = SensorThreshold::new(sensor_types::TEMPERATURE, 0.0, 40.0);
+        threshold.min_alert_duration_secs = 120;
+        
+        let flags = evaluate_series("min-duration-test", &threshold, &[41.0, 41.0, 41.0, 20.0, 41.0, 41.0]);
+        
+        assert_eq!(flags, vec![false, false, true, false, false, false]);
+    }
+    
+    #[test]
+    fn persistence_settings_are_stored_with_the_threshold() {
+        data::init_test_database();
+        let mut threshold = SensorThreshold::default_for(sensor_types::SALINITY);
+        threshold.hysteresis = 1.5;
+        threshold.min_alert_readings = 4;
+        threshold.min_alert_duration_secs = 300;
+        
+        sensor_threshold_dao::set_threshold(&threshold).unwrap();
+        let stored = sensor_threshold_dao::get_threshold(sensor_types::SALINITY).unwrap();
+        
+        assert_eq!(stored.hysteresis, 1.5);
+        assert_eq!(stored.min_alert_readings, 4);
+        assert_eq!(stored.min_alert_duration_secs, 300);
+    }
+    
+    #[test]
+    fn events_open_track_the_peak_and_close() {
+        data::init_test_database();
+        let device_id = "alert-events-test";
+        let reading = |minute: i64, value: f32, is_alert: bool| {
+            SensorReading::new(device_id, sensor_types::TEMPERATURE, value, T0 + minute * 60_000, is_alert)
+        };
+        
+        update_alert_events(&[reading(0, 25.0, false), reading(1, 41.0, true), reading(2, 45.0, true), reading(3, 42.0, true)]).unwrap();
+        
+        let open = alert_event_dao::get_open_event(device_id, sensor_types::TEMPERATURE).unwrap().unwrap();
+        assert_eq!(open.threshold_breached, BREACH_MAX);
+        assert_eq!(open.start_time, T0 + 60_000);
+        assert_eq!(open.peak_value, 45.0);
+        
+        // Jumping below the band closes the event and opens one on the other side
+        update_alert_events(&[reading(4, 5.0, true), reading(5, 3.0, true), reading(6, 25.0, false)]).unwrap();
+        
+        assert!(alert_event_dao::get_open_event(device_id, sensor_types::TEMPERATURE).unwrap().is_none());
+        let events = events_for(device_id);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].end_time, Some(T0 + 4 * 60_000));
+        assert_eq!(events[0].peak_value, 45.0);
+        assert_eq!(events[0].threshold_value, 40.0);
+        assert_eq!(events[1].threshold_breached, BREACH_MIN);
+        assert_eq!((events[1].start_time, events[1].end_time), (T0 + 4 * 60_000, Some(T0 + 6 * 60_000)));
+        assert_eq!(events[1].peak_value, 3.0);
+        assert_eq!(events[1].threshold_value, 10.0);
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use crate::data::dao::{retention_dao, sensor_reading_dao};
+use crate::model::export::{ExportSummary, ReadingFilter};
+use crate::model::sensor_data::{RollupReading, SensorReading};
+use parquet::basic::{Compression, Encoding};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, FloatType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::ColumnPath;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Rows buffered in memory before they are written out as one row group
+const ROW_GROUP_SIZE: usize = 100_000;
+
+const READINGS_SCHEMA: &str = "
+    message sensor_reading {
+        REQUIRED INT64 id;
+        REQUIRED BYTE_ARRAY device_id (STRING);
+        REQUIRED BYTE_ARRAY sensor_type (STRING);
+        REQUIRED FLOAT value;
+        REQUIRED INT64 timestamp (TIMESTAMP(MILLIS, true));
+        REQUIRED BOOLEAN is_alert;
+    }
+";
+
+const ROLLUP_SCHEMA: &str = "
+    message sensor_reading_rollup {
+        REQUIRED BYTE_ARRAY device_id (STRING);
+        REQUIRED BYTE_ARRAY sensor_type (STRING);
+        REQUIRED INT64 bucket_start (TIMESTAMP(MILLIS, true));
+        REQUIRED INT64 bucket_ms;
+        REQUIRED FLOAT avg_value;
+        REQUIRED FLOAT min_value;
+        REQUIRED FLOAT max_value;
+        REQUIRED INT64 sample_count;
+    }
+";
+
+/// Write raw readings and both rollup tiers matching `filter` to Parquet files under `dir`.
+///
+/// Each table gets its own folder partitioned by UTC month in the Hive layout, e.g.
+/// `sensor_readings/month=2024-05/part-0.parquet`, so each table folder can be opened as one
+/// dataset. Only whole months of every device and sensor go there. Months an export narrows
+/// by device, sensor or time range are written to the same layout under
+/// `filtered/<hash of the filter>/` instead, so they neither replace nor duplicate rows of the
+/// main dataset. Timestamps carry the `TIMESTAMP(MILLIS)` logical type and the device and
+/// sensor-type columns are dictionary encoded.
+pub fn export_archive(dir: &Path, filter: &ReadingFilter) -> Result<ExportSummary> {
+    let mut rows = 0;
+    
+    if let Some((first, last)) = sensor_reading_dao::get_time_bounds(filter)? {
+        for partition in month_partitions(filter, first, last) {
+            let path = partition.path(dir, "sensor_readings");
+            let mut writer: Option<PartitionWriter<ReadingColumns>> = None;
+            
+            sensor_reading_dao::for_each_reading(&partition.filter, |reading| {
+                if writer.is_none() {
+                    writer = Some(PartitionWriter::create(&path, READINGS_SCHEMA)?);
+                }
+                writer.as_mut().map_or(Ok(()), |w| w.push(reading))
+            })?;
+            
+            if let Some(writer) = writer {
+                rows += writer.close()?;
+            }
+        }
+    }
+    
+    for (table, bucket_ms) in [
+        (retention_dao::RAW_ROLLUP_TABLE, retention_dao::RAW_ROLLUP_BUCKET_MS),
+        (retention_dao::HOURLY_ROLLUP_TABLE, retention_dao::HOURLY_ROLLUP_BUCKET_MS),
+    ] {
+        let Some((first, last)) = retention_dao::get_rollup_time_bounds(table, filter)? else {
+            continue;
+        };
+        
+        for partition in month_partitions(filter, first, last) {
+            let path = partition.path(dir, table);
+            let mut writer: Option<PartitionWriter<RollupColumns>> = None;
+            
+            retention_dao::for_each_rollup(table, &partition.filter, |rollup| {
+                if writer.is_none() {
+                    writer = Some(PartitionWriter::create(&path, ROLLUP_SCHEMA)?);
+                }
+                writer.as_mut().map_or(Ok(()), |w| w.push((rollup, bucket_ms)))
+            })?;
+            
+            if let Some(writer) = writer {
+                rows += writer.close()?;
+            }
+        }
+    }
+    
+    log::info!("Archived {} rows to {}", rows, dir.display());
+    
+    Ok(ExportSummary {
+        path: dir.display().to_string(),
+        rows,
+    })
+}
+
+/// The file one month of an export is written to
+struct MonthPartition {
+    /// `YYYY-MM`
+    month: String,
+    /// Folder under `filtered/` when the month is narrowed, `None` for a whole month
+    subset: Option<String>,
+    filter: ReadingFilter,
+}
+
+impl MonthPartition {
+    fn path(&self, dir: &Path, table: &str) -> PathBuf {
+        let root = match &self.subset {
+            Some(subset) => dir.join("filtered").join(subset),
+            None => dir.to_path_buf(),
+        };
+        root.join(table).join(format!("month={}", self.month)).join("part-0.parquet")
+    }
+}
+
+/// Stable name for the exports of `filter`.
+/// FNV-1a, so the name stays the same across builds and re-running an export replaces its own files.
+fn subset_name(filter: &ReadingFilter) -> String {
+    let key = format!("{:?}|{:?}|{:?}|{:?}", filter.device_id, filter.sensor_type, filter.from, filter.to);
+    let hash = key
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+    format!("{:016x}", hash)
+}
+
+/// Partition for every UTC month between `first` and `last`
+fn month_partitions(filter: &ReadingFilter, first: i64, last: i64) -> Vec<MonthPartition> {
+    let month_start = |timestamp: i64| {
+        let date = Utc.timestamp_millis_opt(timestamp).single().unwrap_or_default().date_naive();
+        NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap_or_default()
+    };
+    let to_millis = |date: NaiveDate| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp_millis();
+    
+    let mut partitions = Vec::new();
+    let mut start = month_start(first);
+    let end = month_start(last);
+    
+    while start <= end {
+        let next = if start.month() == 12 {
+            NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+        }
+        .unwrap_or_default();
+        
+        let from = to_millis(start);
+        let to = to_millis(next);
+        let month_filter = ReadingFilter {
+            from: Some(filter.from.map_or(from, |f| f.max(from))),
+            to: Some(filter.to.map_or(to, |t| t.min(to))),
+            ..filter.clone()
+        };
+        let whole_month = month_filter.device_id.is_none()
+            && month_filter.sensor_type.is_none()
+            && month_filter.from == Some(from)
+            && month_filter.to == Some(to);
+        partitions.push(MonthPartition {
+            month: start.format("%Y-%m").to_string(),
+            subset: (!whole_month).then(|| subset_name(filter)),
+            filter: month_filter,
+        });
+        start = next;
+    }
+    
+    partitions
+}
+
+/// Column buffers for one row group of a table
+trait Columns: Default {
+    type Row;
+    
+    fn push(&mut self, row: Self::Row);
+    fn len(&self) -> usize;
+    /// Write every column in schema order
+    fn write(&self, row_group: &mut SerializedRowGroupWriter<'_, File>) -> Result<()>;
+}
+
+#[derive(Default)]
+struct ReadingColumns {
+    id: Vec<i64>,
+    device_id: Vec<ByteArray>,
+    sensor_type: Vec<ByteArray>,
+    value: Vec<f32>,
+    timestamp: Vec<i64>,
+    is_alert: Vec<bool>,
+}
+
+impl Columns for ReadingColumns {
+    type Row = SensorReading;
+    
+    fn push(&mut self, reading: SensorReading) {
+        self.id.push(reading.id.unwrap_or_default());
+        self.device_id.push(ByteArray::from(reading.device_id.as_str()));
+        self.sensor_type.push(ByteArray::from(reading.sensor_type.as_str()));
+        self.value.push(reading.value);
+        self.timestamp.push(reading.timestamp);
+        self.is_alert.push(reading.is_alert);
+    }
+    
+    fn len(&self) -> usize {
+        self.id.len()
+    }
+    
+    fn write(&self, row_group: &mut SerializedRowGroupWriter<'_, File>) -> Result<()> {
+        write_column::<Int64Type>(row_group, &self.id)?;
+        write_column::<ByteArrayType>(row_group, &self.device_id)?;
+        write_column::<ByteArrayType>(row_group, &self.sensor_type)?;
+        write_column::<FloatType>(row_group, &self.value)?;
+        write_column::<Int64Type>(row_group, &self.timestamp)?;
+        write_column::<BoolType>(row_group, &self.is_alert)
+    }
+}
+
+#[derive(Default)]
+struct RollupColumns {
+    device_id: Vec<ByteArray>,
+    sensor_type: Vec<ByteArray>,
+    bucket_start: Vec<i64>,
+    bucket_ms: Vec<i64>,
+    avg_value: Vec<f32>,
+    min_value: Vec<f32>,
+    max_value: Vec<f32>,
+    sample_count: Vec<i64>,
+}
+
+impl Columns for RollupColumns {
+    type Row = (RollupReading, i64);
+    
+    fn push(&mut self, (rollup, bucket_ms): (RollupReading, i64)) {
+        self.device_id.push(ByteArray::from(rollup.device_id.as_str()));
+        self.sensor_type.push(ByteArray::from(rollup.sensor_type.as_str()));
+        self.bucket_start.push(rollup.bucket_start);
+        self.bucket_ms.push(bucket_ms);
+        self.avg_value.push(rollup.avg_value);
+        self.min_value.push(rollup.min_value);
+        self.max_value.push(rollup.max_value);
+        self.sample_count.push(rollup.sample_count);
+    }
+    
+    fn len(&self) -> usize {
+        self.device_id.len()
+    }
+    
+    fn write(&self, row_group: &mut SerializedRowGroupWriter<'_, File>) -> Result<()> {
+        write_column::<ByteArrayType>(row_group, &self.device_id)?;
+        write_column::<ByteArrayType>(row_group, &self.sensor_type)?;
+        write_column::<Int64Type>(row_group, &self.bucket_start)?;
+        write_column::<Int64Type>(row_group, &self.bucket_ms)?;
+        write_column::<FloatType>(row_group, &self.avg_value)?;
+        write_column::<FloatType>(row_group, &self.min_value)?;
+        write_column::<FloatType>(row_group, &self.max_value)?;
+        write_column::<Int64Type>(row_group, &self.sample_count)
+    }
+}
+
+fn write_column<T: DataType>(row_group: &mut SerializedRowGroupWriter<'_, File>, values: &[T::T]) -> Result<()> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| anyhow!("Parquet schema has fewer columns than the data"))?;
+    column.typed::<T>().write_batch(values, None, None)?;
+    column.close()?;
+    Ok(())
+}
+
+/// One Parquet file that buffers rows and flushes them a row group at a time
+struct PartitionWriter<C: Columns> {
+    writer: SerializedFileWriter<File>,
+    columns: C,
+    rows: usize,
+}
+
+impl<C: Columns> PartitionWriter<C> {
+    fn create(path: &Path, schema: &str) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        // Only the repeated string columns are dictionary encoded; timestamps use delta encoding
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_dictionary_enabled(false)
+            .set_column_dictionary_enabled(ColumnPath::from("device_id"), true)
+            .set_column_dictionary_enabled(ColumnPath::from("sensor_type"), true)
+            .set_column_encoding(ColumnPath::from("timestamp"), Encoding::DELTA_BINARY_PACKED)
+            .set_column_encoding(ColumnPath::from("bucket_start"), Encoding::DELTA_BINARY_PACKED)
+            .build();
+        
+        let schema = Arc::new(parse_message_type(schema)?);
+        let writer = SerializedFileWriter::new(File::create(path)?, schema, Arc::new(properties))?;
+        
+        Ok(Self {
+            writer,
+            columns: C::default(),
+            rows: 0,
+        })
+    }
+    
+    fn push(&mut self, row: C::Row) -> Result<()> {
+        self.columns.push(row);
+        self.rows += 1;
+        if self.columns.len() >= ROW_GROUP_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+    
+    fn flush(&mut self) -> Result<()> {
+        if self.columns.len() == 0 {
+            return Ok(());
+        }
+        
+        let mut row_group = self.writer.next_row_group()?;
+        self.columns.write(&mut row_group)?;
+        row_group.close()?;
+        self.columns = C::default();
+        Ok(())
+    }
+    
+    /// Flush the last row group and write the footer; returns the rows written
+    fn close(mut self) -> Result<usize> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use parquet::file::reader::SerializedFileReader;
+    use parquet::record::RowAccessor;
+    
+    /// 2019-03-01 and 2019-04-01 UTC, a month no other test stores readings in
+    const MARCH: i64 = 1_551_398_400_000;
+    const APRIL: i64 = 1_554_076_800_000;
+    
+    /// `(device_id, timestamp)` of every reading in a dataset folder and the folders below it
+    fn dataset_rows(dir: &Path) -> Vec<(String, i64)> {
+        let mut rows = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                rows.extend(dataset_rows(&path));
+                continue;
+            }
+            for row in SerializedFileReader::try_from(path.as_path()).unwrap() {
+                let row = row.unwrap();
+                rows.push((row.get_string(1).unwrap().clone(), row.get_timestamp_millis(4).unwrap()));
+            }
+        }
+        rows.sort();
+        rows
+    }
+    
+    #[test]
+    fn narrowed_export_stays_out_of_the_dataset() {
+        data::init_test_database();
+        let readings: Vec<SensorReading> = ["archive-a", "archive-b"]
+            .into_iter()
+            .map(|device_id| SensorReading::new(device_id, "temperature", 21.0, MARCH + 60_000, false))
+            .collect();
+        sensor_reading_dao::insert_batch(&readings).unwrap();
+        
+        let dir = std::env::temp_dir().join(format!("sensor_monitor_archive_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let month = ReadingFilter {
+            from: Some(MARCH),
+            to: Some(APRIL),
+            ..Default::default()
+        };
+        let one_device = ReadingFilter {
+            device_id: Some("archive-a".to_string()),
+            ..month.clone()
+        };
+        let expected = vec![("archive-a".to_string(), MARCH + 60_000), ("archive-b".to_string(), MARCH + 60_000)];
+        
+        assert_eq!(export_archive(&dir, &month).unwrap().rows, 2);
+        assert_eq!(export_archive(&dir, &one_device).unwrap().rows, 1);
+        
+        // The main dataset holds every reading exactly once, whatever order the exports ran in
+        assert_eq!(dataset_rows(&dir.join("sensor_readings")), expected);
+        export_archive(&dir, &month).unwrap();
+        assert_eq!(dataset_rows(&dir.join("sensor_readings")), expected);
+        
+        // The narrowed export is its own dataset, replaced when it is run again
+        let narrowed = dir.join("filtered").join(subset_name(&one_device)).join("sensor_readings");
+        export_archive(&dir, &one_device).unwrap();
+        assert_eq!(dataset_rows(&narrowed), expected[..1]);
+        assert_eq!(std::fs::read_dir(dir.join("filtered")).unwrap().count(), 1);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use crate::data::dao::{alert_event_dao, sensor_reading_dao};
+use crate::model::alert::AlertEvent;
+use crate::model::export::{ExportSummary, ReadingFilter};
+use crate::model::sensor_data::SensorReading;
+use crate::model::sensor_types;
+use crate::util::csv;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Folder exports are written to when the caller does not pick one
+pub fn default_export_dir() -> PathBuf {
+    dirs_next::document_dir()
+        .or_else(dirs_next::data_local_dir)
+        .map(|dir| dir.join("sensor_monitor").join("exports"))
+        .unwrap_or_else(|| PathBuf::from("exports"))
+}
+
+/// Timestamp as ISO-8601 in UTC with millisecond precision
+pub fn iso_timestamp(timestamp_ms: i64) -> String {
+    Utc.timestamp_millis_opt(timestamp_ms)
+        .single()
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+/// Column header for a sensor's values, with its unit when it has one
+pub fn value_header(sensor_type: &str) -> String {
+    match sensor_types::get_unit(sensor_type) {
+        "" => sensor_type.to_string(),
+        unit => format!("{} ({})", sensor_type, unit),
+    }
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+/// Readings taken by one device at one moment, one column per sensor
+struct Row {
+    device_id: String,
+    timestamp: i64,
+    values: HashMap<String, f32>,
+    alerts: Vec<String>,
+}
+
+impl Row {
+    fn start(reading: &SensorReading) -> Self {
+        Self {
+            device_id: reading.device_id.clone(),
+            timestamp: reading.timestamp,
+            values: HashMap::new(),
+            alerts: Vec::new(),
+        }
+    }
+    
+    fn add(&mut self, reading: SensorReading) {
+        if reading.is_alert {
+            self.alerts.push(reading.sensor_type.clone());
+        }
+        self.values.insert(reading.sensor_type, reading.value);
+    }
+    
+    fn write<W: Write>(&self, out: &mut W, sensor_types: &[&str]) -> Result<()> {
+        let mut fields = vec![
+            iso_timestamp(self.timestamp),
+            self.timestamp.to_string(),
+            self.device_id.clone(),
+        ];
+        for sensor_type in sensor_types {
+            fields.push(self.values.get(*sensor_type).map_or(String::new(), |v| v.to_string()));
+        }
+        fields.push(self.alerts.join(" "));
+        
+        csv::write_record(out, &fields)?;
+        Ok(())
+    }
+}
+
+/// Stream readings matching `filter` to a CSV file, one row per device and timestamp,
+/// oldest first and ordered by device within a timestamp.
+///
+/// Columns: `timestamp` (ISO-8601, UTC), `timestamp_ms`, `device_id`, one value column per
+/// sensor with its unit in the header, and `alerts` listing the sensors in alert.
+pub fn export_readings_csv(path: &Path, filter: &ReadingFilter) -> Result<ExportSummary> {
+    let sensor_types = filter.sensor_types();
+    let mut out = create_file(path)?;
+    
+    let mut header = vec!["timestamp".to_string(), "timestamp_ms".to_string(), "device_id".to_string()];
+    header.extend(sensor_types.iter().map(|sensor_type| value_header(sensor_type)));
+    header.push("alerts".to_string());
+    csv::write_record(&mut out, &header)?;
+    
+    let mut rows = 0;
+    // Rows of the timestamp being read, by device; several devices can report at the same moment
+    let mut current: BTreeMap<String, Row> = BTreeMap::new();
+    
+    sensor_reading_dao::for_each_reading(filter, |reading| {
+        if current.values().next().is_some_and(|row| row.timestamp != reading.timestamp) {
+            for row in std::mem::take(&mut current).into_values() {
+                row.write(&mut out, &sensor_types)?;
+                rows += 1;
+            }
+        }
+        current
+            .entry(reading.device_id.clone())
+            .or_insert_with(|| Row::start(&reading))
+            .add(reading);
+        Ok(())
+    })?;
+    
+    for row in current.into_values() {
+        row.write(&mut out, &sensor_types)?;
+        rows += 1;
+    }
+    
+    out.flush()?;
+    log::info!("Exported {} rows of readings to {}", rows, path.display());
+    
+    Ok(ExportSummary {
+        path: path.display().to_string(),
+        rows,
+    })
+}
+
+/// Stream alert events to a CSV file, optionally limited to a device and start-time range
+pub fn export_alerts_csv(path: &Path, filter: &ReadingFilter) -> Result<ExportSummary> {
+    let mut out = create_file(path)?;
+    
+    csv::write_record(&mut out, &[
+        "id",
+        "device_id",
+        "sensor_type",
+        "rule_id",
+        "start",
+        "start_ms",
+        "end",
+        "end_ms",
+        "peak_value",
+        "threshold_breached",
+        "threshold_value",
+        "unit",
+        "acknowledged_by",
+        "acknowledged_at",
+    ])?;
+    
+    let mut rows = 0;
+    alert_event_dao::for_each_event(filter.device_id.as_deref(), filter.from, filter.to, |event: AlertEvent| {
+        let optional = |value: Option<i64>| value.map_or(String::new(), |v| v.to_string());
+        
+        csv::write_record(&mut out, &[
+            optional(event.id),
+            event.device_id.clone(),
+            event.sensor_type.clone(),
+            optional(event.rule_id),
+            iso_timestamp(event.start_time),
+            event.start_time.to_string(),
+            event.end_time.map_or(String::new(), iso_timestamp),
+            optional(event.end_time),
+            event.peak_value.to_string(),
+            event.threshold_breached.clone(),
+            event.threshold_value.to_string(),
+            sensor_types::get_unit(&event.sensor_type).to_string(),
+            event.acknowledged_by.clone().unwrap_or_default(),
+            event.acknowledged_at.map_or(String::new(), iso_timestamp),
+        ])?;
+        rows += 1;
+        Ok(())
+    })?;
+    
+    out.flush()?;
+    log::info!("Exported {} alert events to {}", rows, path.display());
+    
+    Ok(ExportSummary {
+        path: path.display().to_string(),
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{self, get_database};
+    
+    /// Before the readings of other tests, so a time range selects only these
+    const START: i64 = 1_500_000_000_000;
+    const MINUTES: i64 = 800;
+    const DEVICES: [&str; 2] = ["export-test-b", "export-test-a"];
+    
+    /// Readings of both devices at every minute, inserted interleaved so neither device's
+    /// readings of a moment have consecutive ids
+    fn seed() -> ReadingFilter {
+        static SEED: std::sync::Once = std::sync::Once::new();
+        SEED.call_once(|| {
+            data::init_test_database();
+            let mut batch = Vec::new();
+            for minute in 0..MINUTES {
+                for sensor_type in sensor_types::ALL {
+                    for device_id in DEVICES {
+                        batch.push(SensorReading::new(device_id, sensor_type, minute as f32, START + minute * 60_000, false));
+                    }
+                }
+            }
+            sensor_reading_dao::insert_batch(&batch).unwrap();
+        });
+        
+        ReadingFilter {
+            from: Some(START),
+            to: Some(START + MINUTES * 60_000),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn readings_are_paged_in_order_without_holding_the_lock() {
+        let filter = seed();
+        let db = get_database().unwrap();
+        let mut count = 0;
+        let mut previous = (i64::MIN, i64::MIN);
+        
+        sensor_reading_dao::for_each_reading(&filter, |reading| {
+            let position = (reading.timestamp, reading.id.unwrap());
+            assert!(position > previous, "{:?} came after {:?}", position, previous);
+            previous = position;
+            count += 1;
+            
+            // Other tests may hold the lock briefly, but the export itself must not
+            let free = (0..1000).any(|_| {
+                let locked = db.try_lock().is_ok();
+                if !locked {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                locked
+            });
+            assert!(free, "database stayed locked during the callback");
+            Ok(())
+        })
+        .unwrap();
+        
+        assert_eq!(count, MINUTES as usize * DEVICES.len() * sensor_types::ALL.len());
+    }
+    
+    #[test]
+    fn csv_has_one_row_per_device_and_timestamp() {
+        let filter = seed();
+        let path = std::env::temp_dir().join(format!("sensor_monitor_export_test_{}.csv", std::process::id()));
+        
+        let summary = export_readings_csv(&path, &filter).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        
+        assert_eq!(summary.rows, MINUTES as usize * DEVICES.len());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), summary.rows + 1);
+        
+        // Oldest first, devices in order within a timestamp, every sensor filled in
+        let first: Vec<&str> = lines[1].split(',').collect();
+        let second: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!((first[1], first[2]), ("1500000000000", "export-test-a"));
+        assert_eq!((second[1], second[2]), ("1500000000000", "export-test-b"));
+        assert_eq!(first.len(), 3 + sensor_types::ALL.len() + 1);
+        assert!(first[3..3 + sensor_types::ALL.len()].iter().all(|value| *value == "0"));
+        
+        let last: Vec<&str> = lines[lines.len() - 1].split(',').collect();
+        assert_eq!(last[1], (START + (MINUTES - 1) * 60_000).to_string());
+        assert_eq!(last[2], "export-test-b");
+    }
+}
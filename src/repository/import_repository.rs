@@ -0,0 +1,351 @@
+use anyhow::{Result, anyhow};
+use crate::data::dao::{device_dao, retention_dao, sensor_reading_dao};
+use crate::model::import::{ImportOptions, ImportRowError, ImportSummary};
+use crate::model::sensor_data::SensorReading;
+use crate::model::sensor_types;
+use crate::repository::{retention_repository, sensor_repository};
+use crate::util::csv::CsvReader;
+use crate::util::date_converter;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Only this many row errors are kept in the summary
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Readings collected from the file before they are stored
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// One input row with its keys mapped to field names
+type Record = HashMap<String, String>;
+
+/// Import readings from a CSV file (with a header row) or a JSON-lines file.
+///
+/// Rows may have the `SensorReading` shape (`sensor_type` and `value`) or the
+/// `ESP32SensorData` shape (one column per sensor). Readings that already exist for the
+/// same device, sensor and timestamp are skipped. The file is read in batches; alert flags
+/// are recomputed against the current thresholds in time order within each batch.
+///
+/// Readings older than the raw retention period are folded straight into the five-minute or
+/// hourly averages, as compaction would have done. No raw rows are kept for them, so
+/// importing the same old rows again counts them twice in the averages. Imported readings
+/// never open or close alert events; those only follow live data.
+pub fn import_readings(path: &Path, options: &ImportOptions) -> Result<ImportSummary> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut summary = ImportSummary::default();
+    let (raw_cutoff, rollup_cutoff) = retention_repository::cutoffs(date_converter::current_timestamp())?;
+    let mut devices = HashSet::new();
+    let mut pending = Vec::new();
+    let mut expired = Vec::new();
+    
+    let mut on_row = |line: usize, record: Result<Record, String>| -> Result<()> {
+        summary.rows += 1;
+        match record.and_then(|record| record_to_readings(&record, options)) {
+            Ok(row_readings) => {
+                for reading in row_readings {
+                    if reading.timestamp < raw_cutoff {
+                        expired.push(reading);
+                    } else {
+                        pending.push(reading);
+                    }
+                }
+                if pending.len() >= IMPORT_BATCH_SIZE {
+                    store_readings(std::mem::take(&mut pending), &mut devices, &mut summary)?;
+                }
+                if expired.len() >= IMPORT_BATCH_SIZE {
+                    fold_readings(std::mem::take(&mut expired), rollup_cutoff, &mut devices, &mut summary)?;
+                }
+            }
+            Err(message) => {
+                summary.error_count += 1;
+                if summary.errors.len() < MAX_REPORTED_ERRORS {
+                    summary.errors.push(ImportRowError { line, message });
+                }
+            }
+        }
+        Ok(())
+    };
+    
+    if is_json_lines(path, &mut reader)? {
+        read_json_lines(reader, options, &mut on_row)?;
+    } else {
+        read_csv(reader, options, &mut on_row)?;
+    }
+    store_readings(pending, &mut devices, &mut summary)?;
+    fold_readings(expired, rollup_cutoff, &mut devices, &mut summary)?;
+    
+    log::info!(
+        "Imported {} readings from {} ({} duplicates, {} errors)",
+        summary.imported, path.display(), summary.duplicates, summary.error_count
+    );
+    
+    Ok(summary)
+}
+
+/// Store a batch of imported readings, registering devices seen for the first time
+fn store_readings(mut readings: Vec<SensorReading>, devices: &mut HashSet<String>, summary: &mut ImportSummary) -> Result<()> {
+    // Alert evaluation looks at earlier readings, so store them oldest first
+    readings.sort_by(|a, b| {
+        (&a.device_id, a.timestamp, &a.sensor_type).cmp(&(&b.device_id, b.timestamp, &b.sensor_type))
+    });
+    
+    let mut batch: Vec<SensorReading> = Vec::new();
+    
+    for reading in readings {
+        if devices.insert(reading.device_id.clone()) {
+            device_dao::ensure_device(&reading.device_id)?;
+        }
+        
+        if let Some(first) = batch.first() {
+            if first.device_id != reading.device_id || first.timestamp != reading.timestamp {
+                store_batch(std::mem::take(&mut batch), summary)?;
+            }
+        }
+        batch.push(reading);
+    }
+    
+    store_batch(batch, summary)
+}
+
+/// Fold readings past the raw retention period into the tier compaction would have put them in
+fn fold_readings(readings: Vec<SensorReading>, rollup_cutoff: i64, devices: &mut HashSet<String>, summary: &mut ImportSummary) -> Result<()> {
+    for reading in &readings {
+        if devices.insert(reading.device_id.clone()) {
+            device_dao::ensure_device(&reading.device_id)?;
+        }
+    }
+    
+    let (recent, oldest): (Vec<_>, Vec<_>) = readings.into_iter().partition(|reading| reading.timestamp >= rollup_cutoff);
+    summary.imported += retention_dao::fold_readings(retention_dao::RAW_ROLLUP_TABLE, retention_dao::RAW_ROLLUP_BUCKET_MS, &recent)?;
+    summary.imported += retention_dao::fold_readings(retention_dao::HOURLY_ROLLUP_TABLE, retention_dao::HOURLY_ROLLUP_BUCKET_MS, &oldest)?;
+    Ok(())
+}
+
+/// Evaluate thresholds for readings sharing a device and timestamp, then store them.
+/// Readings already stored, or repeated within the file, are counted as duplicates.
+fn store_batch(batch: Vec<SensorReading>, summary: &mut ImportSummary) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    
+    let count = batch.len();
+    let readings = sensor_repository::check_thresholds(batch)?;
+    let inserted = sensor_reading_dao::insert_batch(&readings)?.len();
+    
+    summary.imported += inserted;
+    summary.duplicates += count - inserted;
+    Ok(())
+}
+
+/// JSON-lines files are recognised by extension, otherwise by a leading `{`
+fn is_json_lines<R: BufRead>(path: &Path, reader: &mut R) -> Result<bool> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "json" | "jsonl" | "ndjson" => Ok(true),
+        "csv" => Ok(false),
+        _ => {
+            let buffer = reader.fill_buf()?;
+            Ok(buffer.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{'))
+        }
+    }
+}
+
+/// Field name for a column or key: the user mapping wins, then a trailing ` (unit)` is dropped
+fn field_name(source: &str, options: &ImportOptions) -> String {
+    let source = source.trim().trim_start_matches('\u{feff}').to_lowercase();
+    if let Some(field) = options.column_map.get(&source) {
+        return field.clone();
+    }
+    match source.find(" (") {
+        Some(index) => source[..index].to_string(),
+        None => source,
+    }
+}
+
+fn read_csv<R: BufRead, F: FnMut(usize, Result<Record, String>) -> Result<()>>(reader: R, options: &ImportOptions, on_row: &mut F) -> Result<()> {
+    let mut csv = CsvReader::new(reader);
+    
+    let (_, header) = csv.next_record()?.ok_or_else(|| anyhow!("The file is empty"))?;
+    let fields: Vec<String> = header.iter().map(|column| field_name(column, options)).collect();
+    
+    while let Some((line, values)) = csv.next_record()? {
+        if values.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        if values.len() != fields.len() {
+            on_row(line, Err(format!("Expected {} columns, found {}", fields.len(), values.len())))?;
+            continue;
+        }
+        
+        let record = fields.iter().cloned().zip(values.into_iter().map(|v| v.trim().to_string())).collect();
+        on_row(line, Ok(record))?;
+    }
+    
+    Ok(())
+}
+
+fn read_json_lines<R: BufRead, F: FnMut(usize, Result<Record, String>) -> Result<()>>(reader: R, options: &ImportOptions, on_row: &mut F) -> Result<()> {
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let record = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(serde_json::Value::Object(object)) => {
+                let mut record = Record::new();
+                for (key, value) in object {
+                    let text = match value {
+                        serde_json::Value::String(s) => s,
+                        serde_json::Value::Null => continue,
+                        other => other.to_string(),
+                    };
+                    record.insert(field_name(&key, options), text);
+                }
+                Ok(record)
+            }
+            Ok(_) => Err("Expected a JSON object".to_string()),
+            Err(e) => Err(format!("Invalid JSON: {}", e)),
+        };
+        on_row(index + 1, record)?;
+    }
+    
+    Ok(())
+}
+
+/// Turn a mapped row into readings, one per sensor value it holds
+fn record_to_readings(record: &Record, options: &ImportOptions) -> Result<Vec<SensorReading>, String> {
+    let field = |name: &str| record.get(name).map(String::as_str).filter(|v| !v.is_empty());
+    
+    let device_id = field("device_id").unwrap_or(&options.default_device_id);
+    let timestamp = match (field("timestamp_ms"), field("timestamp")) {
+        (Some(ms), _) => ms.parse::<i64>().map_err(|_| format!("Invalid timestamp_ms '{}'", ms))?,
+        (None, Some(text)) => parse_timestamp(text)?,
+        (None, None) => return Err("Missing timestamp".to_string()),
+    };
+    
+    // SensorReading shape: one sensor per row
+    if let Some(sensor_type) = field("sensor_type") {
+        if !sensor_types::ALL.contains(&sensor_type) {
+            return Err(format!("Unknown sensor type '{}'", sensor_type));
+        }
+        let value = field("value").ok_or_else(|| "Missing value".to_string())?;
+        let value = parse_value(sensor_type, value)?;
+        return Ok(vec![SensorReading::new(device_id, sensor_type, value, timestamp, false)]);
+    }
+    
+    // ESP32SensorData shape: one column per sensor
+    let mut readings = Vec::new();
+    for sensor_type in sensor_types::ALL {
+        if let Some(value) = field(sensor_type) {
+            let value = parse_value(sensor_type, value)?;
+            readings.push(SensorReading::new(device_id, sensor_type, value, timestamp, false));
+        }
+    }
+    
+    if readings.is_empty() {
+        let unknown: Vec<&str> = record
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !["device_id", "timestamp", "timestamp_ms", "alerts", "is_alert", "id"].contains(key))
+            .collect();
+        return Err(if unknown.is_empty() {
+            "No sensor values".to_string()
+        } else {
+            format!("No known sensor columns (found: {})", unknown.join(", "))
+        });
+    }
+    
+    Ok(readings)
+}
+
+/// Epoch milliseconds, RFC 3339, or a local date and time
+fn parse_timestamp(text: &str) -> Result<i64, String> {
+    date_converter::parse_datetime(text).ok_or_else(|| format!("Invalid timestamp '{}'", text))
+}
+
+fn parse_value(sensor_type: &str, text: &str) -> Result<f32, String> {
+    let value = match text.to_lowercase().as_str() {
+        // Rain is a boolean in the ESP32 payload
+        "true" | "yes" => 1.0,
+        "false" | "no" => 0.0,
+        _ => text.parse::<f32>().map_err(|_| format!("Invalid {} value '{}'", sensor_type, text))?,
+    };
+    
+    if !value.is_finite() {
+        return Err(format!("Invalid {} value '{}'", sensor_type, text));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::model::export::ReadingFilter;
+    use std::fmt::Write;
+    
+    const ROWS: i64 = 6000;
+    const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+    
+    /// Samples stored for one sensor of the test device in a rollup table
+    fn rollup_samples(table: &str) -> Vec<(i64, i64)> {
+        let filter = ReadingFilter {
+            device_id: Some("import-test".to_string()),
+            sensor_type: Some(sensor_types::TEMPERATURE.to_string()),
+            ..Default::default()
+        };
+        let mut samples = Vec::new();
+        retention_dao::for_each_rollup(table, &filter, |rollup| {
+            samples.push((rollup.bucket_start, rollup.sample_count));
+            Ok(())
+        }).unwrap();
+        samples
+    }
+    
+    #[test]
+    fn imports_in_batches_and_folds_expired_rows_into_averages() {
+        data::init_test_database();
+        let start = date_converter::current_timestamp() - 2 * 60 * 60 * 1000;
+        let two_months_ago = start - 60 * DAY_MS;
+        let ten_years_ago = start - 10 * 365 * DAY_MS;
+        
+        // Two readings per row, so the rows span more than one batch
+        let mut text = String::from("timestamp_ms,device_id,temperature,humidity\n");
+        for row in 0..ROWS {
+            writeln!(text, "{},import-test,{},60", start + row * 1000, 20 + row % 10).unwrap();
+        }
+        writeln!(text, "{},import-test,21,60", start).unwrap();
+        writeln!(text, "{},import-test,21,60", two_months_ago).unwrap();
+        writeln!(text, "{},import-test,21,60", ten_years_ago).unwrap();
+        writeln!(text, "{},import-test,warm,60", start).unwrap();
+        
+        let path = std::env::temp_dir().join(format!("sensor_monitor_import_test_{}.csv", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        
+        let first = import_readings(&path, &ImportOptions::default()).unwrap();
+        
+        assert_eq!(first.rows, ROWS as usize + 4);
+        assert_eq!(first.imported, ROWS as usize * 2 + 4);
+        assert_eq!(first.duplicates, 2);
+        assert_eq!(first.error_count, 1);
+        assert_eq!(first.errors[0].line, ROWS as usize + 5);
+        
+        let five_minutes = rollup_samples(retention_dao::RAW_ROLLUP_TABLE);
+        let hourly = rollup_samples(retention_dao::HOURLY_ROLLUP_TABLE);
+        assert_eq!(five_minutes, vec![(two_months_ago - two_months_ago.rem_euclid(retention_dao::RAW_ROLLUP_BUCKET_MS), 1)]);
+        assert_eq!(hourly, vec![(ten_years_ago - ten_years_ago.rem_euclid(retention_dao::HOURLY_ROLLUP_BUCKET_MS), 1)]);
+        
+        // Raw rows are deduplicated; expired rows have no raw row left to compare against
+        let second = import_readings(&path, &ImportOptions::default()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        
+        assert_eq!(second.imported, 4);
+        assert_eq!(second.duplicates, ROWS as usize * 2 + 2);
+        assert_eq!(rollup_samples(retention_dao::RAW_ROLLUP_TABLE)[0].1, 2);
+        
+        let stored = sensor_reading_dao::get_history_before("import-test", sensor_types::TEMPERATURE, i64::MAX, ROWS * 2).unwrap();
+        assert_eq!(stored.len(), ROWS as usize);
+    }
+}
@@ -1 +1,8 @@
-pub mod sensor_repository; 
\ No newline at end of file
+pub mod alert_repository;
+pub mod archive_repository;
+pub mod export_repository;
+pub mod import_repository;
+pub mod retention_repository;
+pub mod sensor_repository;
+pub mod rule_engine;
+pub mod statistics;
\ No newline at end of file
@@ -0,0 +1,56 @@
+use anyhow::{Result, anyhow};
+use crate::data::dao::retention_dao::{self, CompactionCounts};
+use crate::util::preferences;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Months are counted as 30 days for retention purposes
+const MONTH_MS: i64 = 30 * DAY_MS;
+
+/// Apply the retention policy from preferences as of `now`:
+/// raw readings for N days, five-minute averages for M months, hourly averages forever
+pub fn compact(now: i64) -> Result<CompactionCounts> {
+    let (raw_cutoff, rollup_cutoff) = cutoffs(now)?;
+    
+    let counts = retention_dao::compact(raw_cutoff, rollup_cutoff)?;
+    if counts.raw_rows > 0 || counts.rollup_rows > 0 {
+        log::info!(
+            "Compacted {} raw readings and {} five-minute buckets",
+            counts.raw_rows, counts.rollup_rows
+        );
+    }
+    
+    Ok(counts)
+}
+
+/// Timestamps as of `now` before which readings are only kept as five-minute averages,
+/// and before which those are only kept as hourly averages
+pub fn cutoffs(now: i64) -> Result<(i64, i64)> {
+    let raw_days = preferences::load_raw_retention_days()? as i64;
+    let rollup_months = preferences::load_rollup_retention_months()? as i64;
+    
+    let raw_cutoff = now - raw_days * DAY_MS;
+    // Five-minute buckets are kept for M months after the raw data expired
+    Ok((raw_cutoff, raw_cutoff - rollup_months * MONTH_MS))
+}
+
+/// Delete every reading, rollup and alert
+pub fn delete_all_data() -> Result<usize> {
+    let deleted = retention_dao::delete_all_data()?;
+    log::info!("Deleted all sensor data ({} readings)", deleted);
+    Ok(deleted)
+}
+
+/// Delete data with `from <= timestamp < to`, for one device or all devices when `device_id` is `None`
+pub fn delete_range(device_id: Option<&str>, from: i64, to: i64) -> Result<usize> {
+    if from >= to {
+        return Err(anyhow!("The start of the range must be before its end"));
+    }
+    
+    let deleted = retention_dao::delete_range(device_id, from, to)?;
+    log::info!(
+        "Deleted {} rows between {} and {} for {}",
+        deleted, from, to, device_id.unwrap_or("all devices")
+    );
+    Ok(deleted)
+}
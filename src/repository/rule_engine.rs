@@ -0,0 +1,321 @@
+use anyhow::{Result, anyhow};
+use crate::data::dao::{alert_event_dao, alert_rule_dao, sensor_reading_dao};
+use crate::model::alert::AlertEvent;
+use crate::model::alert_rule::{AlertRule, RuleCondition};
+use crate::model::live_event::LiveEventData;
+use crate::model::sensor_types;
+use crate::notification::{self, live, Notification};
+use crate::util::date_converter;
+
+/// Evaluate every enabled rule for a device at time `now`, opening or closing rule alert events
+pub fn evaluate_rules(device_id: &str, now: i64) -> Result<()> {
+    for rule in alert_rule_dao::get_enabled_rules()? {
+        if !rule.applies_to(device_id) {
+            continue;
+        }
+        let Some(rule_id) = rule.id else {
+            continue;
+        };
+
+        let triggered = evaluate_rule(&rule, device_id, now)?;
+        let open_event = alert_event_dao::get_open_rule_event(device_id, rule_id)?;
+
+        match (triggered, open_event) {
+            (Some(value), None) => {
+                let sensor_type = rule.conditions[0].sensor_type();
+                let mut event = AlertEvent::open_for_rule(device_id, rule_id, sensor_type, now, value);
+                event.id = Some(alert_event_dao::insert(&event)?);
+                log::info!("Rule '{}' triggered on {}", rule.name, device_id);
+                notification::notify(Notification::new(
+                    &format!("Rule '{}' triggered on {}", rule.name, device_id),
+                    &rule.describe(),
+                    device_id,
+                    Some(sensor_type),
+                    true,
+                ));
+                live::publish(device_id, LiveEventData::AlertOpened { alert: event });
+            }
+            (Some(value), Some(event)) => {
+                if let Some(id) = event.id {
+                    if event.is_new_peak(value) {
+                        alert_event_dao::update_peak(id, value)?;
+                    }
+                }
+            }
+            (None, Some(event)) => {
+                if let Some(id) = event.id {
+                    alert_event_dao::close(id, now)?;
+                    log::info!("Rule '{}' cleared on {}", rule.name, device_id);
+                    notification::notify(Notification::new(
+                        &format!("Rule '{}' cleared on {}", rule.name, device_id),
+                        &rule.describe(),
+                        device_id,
+                        Some(&event.sensor_type),
+                        false,
+                    ));
+                    live::publish(device_id, LiveEventData::AlertClosed {
+                        alert: AlertEvent { end_time: Some(now), ..event },
+                    });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Check all conditions of a rule; returns the latest value of the first condition's sensor when it fires
+pub fn evaluate_rule(rule: &AlertRule, device_id: &str, now: i64) -> Result<Option<f32>> {
+    if rule.conditions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut first_value = None;
+    for condition in &rule.conditions {
+        match evaluate_condition(condition, device_id, now)? {
+            Some(value) => {
+                if first_value.is_none() {
+                    first_value = Some(value);
+                }
+            }
+            None => return Ok(None),
+        }
+    }
+
+    Ok(first_value)
+}
+
+/// Evaluate one condition against the sensor's history, returning the latest value if it holds
+fn evaluate_condition(condition: &RuleCondition, device_id: &str, now: i64) -> Result<Option<f32>> {
+    let sensor_type = condition.sensor_type();
+    let window_start = now - condition.window_secs() * 1000;
+
+    let Some(latest) = sensor_reading_dao::get_history_before(device_id, sensor_type, now + 1, 1)?.pop() else {
+        return Ok(None);
+    };
+    let value = latest.value;
+
+    let holds = match condition {
+        RuleCondition::Above { value: limit, .. } => value > *limit,
+        RuleCondition::Below { value: limit, .. } => value < *limit,
+        RuleCondition::RisesBy { delta, .. } => {
+            let window = sensor_reading_dao::get_readings_in_range(device_id, sensor_type, window_start, now + 1)?;
+            let lowest = window.iter().map(|r| r.value).fold(f32::INFINITY, f32::min);
+            window.len() > 1 && value - lowest > *delta
+        }
+        RuleCondition::FallsBy { delta, .. } => {
+            let window = sensor_reading_dao::get_readings_in_range(device_id, sensor_type, window_start, now + 1)?;
+            let highest = window.iter().map(|r| r.value).fold(f32::NEG_INFINITY, f32::max);
+            window.len() > 1 && highest - value > *delta
+        }
+        RuleCondition::StaysBelow { value: limit, .. } => {
+            // Only meaningful once there is data from before the window started
+            let covered = !sensor_reading_dao::get_history_before(device_id, sensor_type, window_start + 1, 1)?.is_empty();
+            let window = sensor_reading_dao::get_readings_in_range(device_id, sensor_type, window_start, now + 1)?;
+            covered && window.iter().all(|r| r.value < *limit)
+        }
+    };
+
+    Ok(if holds { Some(value) } else { None })
+}
+
+/// Check that a rule can be evaluated
+pub fn validate_rule(rule: &AlertRule) -> Result<()> {
+    if rule.name.trim().is_empty() {
+        return Err(anyhow!("Rule name must not be empty"));
+    }
+    if rule.conditions.is_empty() {
+        return Err(anyhow!("Rule needs at least one condition"));
+    }
+
+    for condition in &rule.conditions {
+        if !sensor_types::ALL.contains(&condition.sensor_type()) {
+            return Err(anyhow!("Unknown sensor type: {}", condition.sensor_type()));
+        }
+        match condition {
+            RuleCondition::RisesBy { delta, window_secs, .. } | RuleCondition::FallsBy { delta, window_secs, .. } => {
+                if *delta <= 0.0 {
+                    return Err(anyhow!("Change must be greater than 0 in '{}'", condition.describe()));
+                }
+                if *window_secs <= 0 {
+                    return Err(anyhow!("Time window must be greater than 0 in '{}'", condition.describe()));
+                }
+            }
+            RuleCondition::StaysBelow { window_secs, .. } => {
+                if *window_secs <= 0 {
+                    return Err(anyhow!("Time window must be greater than 0 in '{}'", condition.describe()));
+                }
+            }
+            RuleCondition::Above { .. } | RuleCondition::Below { .. } => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// All rules, enabled or not
+pub fn get_rules() -> Result<Vec<AlertRule>> {
+    alert_rule_dao::get_all_rules()
+}
+
+/// Validate and store a rule, returning its id
+pub fn save_rule(rule: &AlertRule) -> Result<i64> {
+    validate_rule(rule)?;
+    let id = alert_rule_dao::save_rule(rule)?;
+
+    // A disabled rule should not leave alerts open
+    if !rule.enabled {
+        alert_event_dao::close_rule_events(id, date_converter::current_timestamp())?;
+    }
+
+    Ok(id)
+}
+
+/// Delete a rule and close any alerts it still has open
+pub fn delete_rule(id: i64) -> Result<()> {
+    alert_event_dao::close_rule_events(id, date_converter::current_timestamp())?;
+    alert_rule_dao::delete_rule(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::model::sensor_data::SensorReading;
+
+    const T0: i64 = 1_660_000_000_000;
+    const MINUTE: i64 = 60_000;
+
+    fn store(device_id: &str, sensor_type: &str, values: &[(i64, f32)]) {
+        let readings: Vec<_> = values.iter()
+            .map(|&(offset, value)| SensorReading::new(device_id, sensor_type, value, T0 + offset, false))
+            .collect();
+        sensor_reading_dao::insert_batch(&readings).unwrap();
+    }
+
+    fn rule(device_id: &str, condition: RuleCondition) -> AlertRule {
+        let mut rule = AlertRule::new("test rule");
+        rule.device_id = Some(device_id.to_string());
+        rule.conditions.push(condition);
+        rule
+    }
+
+    fn temperature(device_id: &str, condition: fn(String) -> RuleCondition, now: i64) -> Option<f32> {
+        evaluate_rule(&rule(device_id, condition(sensor_types::TEMPERATURE.to_string())), device_id, now).unwrap()
+    }
+
+    #[test]
+    fn above_compares_the_latest_value() {
+        data::init_test_database();
+        let device_id = "rule-above-test";
+        store(device_id, sensor_types::TEMPERATURE, &[(0, 40.0), (MINUTE, 30.0)]);
+
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Above { sensor_type, value: 25.0 }, T0 + MINUTE), Some(30.0));
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Above { sensor_type, value: 35.0 }, T0 + MINUTE), None);
+        // Readings after `now` are not looked at
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Above { sensor_type, value: 35.0 }, T0), Some(40.0));
+    }
+
+    #[test]
+    fn below_compares_the_latest_value() {
+        data::init_test_database();
+        let device_id = "rule-below-test";
+        store(device_id, sensor_types::TEMPERATURE, &[(0, 10.0), (MINUTE, 30.0)]);
+
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Below { sensor_type, value: 35.0 }, T0 + MINUTE), Some(30.0));
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Below { sensor_type, value: 25.0 }, T0 + MINUTE), None);
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::Below { sensor_type, value: 25.0 }, T0 - 1), None);
+    }
+
+    #[test]
+    fn rises_by_compares_with_the_lowest_value_in_the_window() {
+        data::init_test_database();
+        let device_id = "rule-rises-test";
+        store(device_id, sensor_types::TEMPERATURE, &[(0, 20.0), (5 * MINUTE, 23.0), (10 * MINUTE, 26.0)]);
+        let now = T0 + 10 * MINUTE;
+
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::RisesBy { sensor_type, delta: 5.0, window_secs: 600 }, now), Some(26.0));
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::RisesBy { sensor_type, delta: 7.0, window_secs: 600 }, now), None);
+        // The 20 °C reading is outside a five minute window
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::RisesBy { sensor_type, delta: 5.0, window_secs: 300 }, now), None);
+    }
+
+    #[test]
+    fn falls_by_compares_with_the_highest_value_in_the_window() {
+        data::init_test_database();
+        let device_id = "rule-falls-test";
+        store(device_id, sensor_types::TEMPERATURE, &[(0, 30.0), (5 * MINUTE, 27.0), (10 * MINUTE, 24.0)]);
+        let now = T0 + 10 * MINUTE;
+
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::FallsBy { sensor_type, delta: 5.0, window_secs: 600 }, now), Some(24.0));
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::FallsBy { sensor_type, delta: 7.0, window_secs: 600 }, now), None);
+        assert_eq!(temperature(device_id, |sensor_type| RuleCondition::FallsBy { sensor_type, delta: 5.0, window_secs: 300 }, now), None);
+    }
+
+    #[test]
+    fn stays_below_needs_the_whole_window() {
+        data::init_test_database();
+        let device_id = "rule-stays-test";
+        let hour = 60 * MINUTE;
+        store(device_id, sensor_types::RAIN, &[(0, 0.0), (12 * hour, 0.0), (24 * hour, 0.0), (30 * hour, 1.0), (36 * hour, 0.0)]);
+        let no_rain = rule(device_id, RuleCondition::StaysBelow {
+            sensor_type: sensor_types::RAIN.to_string(),
+            value: 0.5,
+            window_secs: 24 * 3600,
+        });
+
+        assert_eq!(evaluate_rule(&no_rain, device_id, T0 + 24 * hour).unwrap(), Some(0.0));
+        // No data from before the window yet
+        assert_eq!(evaluate_rule(&no_rain, device_id, T0 + 12 * hour).unwrap(), None);
+        // It rained at 30 h
+        assert_eq!(evaluate_rule(&no_rain, device_id, T0 + 36 * hour).unwrap(), None);
+    }
+
+    #[test]
+    fn every_condition_must_hold() {
+        data::init_test_database();
+        let device_id = "rule-compound-test";
+        store(device_id, sensor_types::SALINITY, &[(0, 20.0)]);
+        store(device_id, sensor_types::WATER_LEVEL, &[(0, 10.0)]);
+        let compound = |water_below| {
+            let mut rule = rule(device_id, RuleCondition::Above { sensor_type: sensor_types::SALINITY.to_string(), value: 15.0 });
+            rule.conditions.push(RuleCondition::Below { sensor_type: sensor_types::WATER_LEVEL.to_string(), value: water_below });
+            rule
+        };
+
+        // The value reported is the first condition's
+        assert_eq!(evaluate_rule(&compound(20.0), device_id, T0).unwrap(), Some(20.0));
+        assert_eq!(evaluate_rule(&compound(5.0), device_id, T0).unwrap(), None);
+    }
+
+    #[test]
+    fn firing_rule_keeps_one_event_open_until_it_clears() {
+        data::init_test_database();
+        let device_id = "rule-events-test";
+        let rule_id = save_rule(&rule(device_id, RuleCondition::Above {
+            sensor_type: sensor_types::TEMPERATURE.to_string(),
+            value: 35.0,
+        })).unwrap();
+
+        for (minute, value) in [(0, 36.0), (1, 38.0), (2, 37.0)] {
+            store(device_id, sensor_types::TEMPERATURE, &[(minute * MINUTE, value)]);
+            evaluate_rules(device_id, T0 + minute * MINUTE).unwrap();
+        }
+
+        let open = alert_event_dao::get_open_rule_event(device_id, rule_id).unwrap().unwrap();
+        assert_eq!((open.start_time, open.peak_value), (T0, 38.0));
+
+        store(device_id, sensor_types::TEMPERATURE, &[(3 * MINUTE, 30.0)]);
+        evaluate_rules(device_id, T0 + 3 * MINUTE).unwrap();
+        delete_rule(rule_id).unwrap();
+
+        let mut events = Vec::new();
+        alert_event_dao::for_each_event(Some(device_id), None, None, |event| {
+            events.push(event);
+            Ok(())
+        }).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].end_time, Some(T0 + 3 * MINUTE));
+    }
+}
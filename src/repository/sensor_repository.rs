@@ -1,76 +1,205 @@
 use anyhow::{Result, anyhow};
 use serde_json::json;
 use std::collections::HashMap;
-use crate::api::{esp32_api, firebase_api};
-use crate::data::dao::{sensor_reading_dao, sensor_threshold_dao};
-use crate::model::sensor_data::{ESP32SensorData, SensorReading, SensorThreshold};
+use crate::api::esp32_api;
+use crate::api::firebase_api::FirebaseSink;
+use crate::data::dao::{device_dao, firebase_sync_dao, sensor_reading_dao, sensor_threshold_dao};
+use crate::model::device::{Device, DeviceStatusEvent, DEFAULT_DEVICE_ID};
+use crate::model::live_event::LiveEventData;
+use crate::model::sensor_data::{AggregatedHistory, ESP32SensorData, SensorReading, SensorThreshold};
 use crate::model::sensor_types;
+use crate::notification::live;
+use crate::repository::{alert_repository, rule_engine};
+use crate::util::date_converter;
 
-/// Fetch latest readings from database, Firebase, and ESP32
-pub fn fetch_latest_readings() -> Result<String> {
-    // First, try to fetch from database
-    let db_readings = sensor_reading_dao::get_latest_readings()?;
+/// Newest stored reading of every sensor of a device
+pub fn get_latest_readings(device_id: &str) -> Result<Vec<SensorReading>> {
+    sensor_reading_dao::get_latest_readings(device_id)
+}
+
+/// Newest stored reading of every sensor of every registered device
+pub fn get_all_latest_readings() -> Result<Vec<SensorReading>> {
+    let mut readings = Vec::new();
+    for device in get_devices()? {
+        readings.extend(get_latest_readings(&device.id)?);
+    }
+    Ok(readings)
+}
+
+/// Fetch latest readings for a device from database
+pub fn fetch_latest_readings(device_id: &str) -> Result<String> {
+    let db_readings = get_latest_readings(device_id)?;
     
     // Convert to a map by sensor type
-    let mut readings_map: HashMap<String, SensorReading> = db_readings
+    let readings_map: HashMap<String, SensorReading> = db_readings
         .into_iter()
         .map(|r| (r.sensor_type.clone(), r))
         .collect();
     
-    // Try to fetch from Firebase as well
-    match firebase_api::fetch_latest_readings() {
-        Ok(firebase_readings) => {
-            if let Some(latest) = firebase_readings.first() {
-                // Process the latest reading from Firebase
-                process_esp32_data(latest)?;
-                
-                // Update the map with new readings
-                for reading in sensor_reading_dao::get_latest_readings()? {
-                    readings_map.insert(reading.sensor_type.clone(), reading);
-                }
-            }
-        }
-        Err(e) => {
-            log::warn!("Failed to fetch from Firebase: {}", e);
-        }
-    }
-    
     // Convert to JSON
     let json = serde_json::to_string(&readings_map)?;
     Ok(json)
 }
 
-/// Fetch reading history for a specific sensor type
-pub fn fetch_reading_history(sensor_type: &str, limit: i64) -> Result<String> {
-    let readings = sensor_reading_dao::get_history_by_type(sensor_type, limit)?;
-    let json = serde_json::to_string(&readings)?;
-    Ok(json)
+/// Stores readings from Firebase and remembers how far each database has been synced
+pub struct FirebaseIngest;
+
+impl FirebaseSink for FirebaseIngest {
+    fn last_key(&self, source: &str) -> Result<Option<String>> {
+        firebase_sync_dao::get_last_key(source)
+    }
+    
+    fn store(&self, source: &str, key: &str, data: ESP32SensorData) -> Result<()> {
+        process_esp32_data(&data)?;
+        firebase_sync_dao::save_position(source, key, data.timestamp)
+    }
+}
+
+/// URL to poll for a device, if it can be polled directly.
+/// The default device uses `fallback_url` (the ESP32 URL from settings) when it has no URL of its own.
+pub fn resolve_device_url(device: &Device, fallback_url: &str) -> Option<String> {
+    let url = if device.url.is_empty() && device.id == DEFAULT_DEVICE_ID {
+        fallback_url
+    } else {
+        device.url.as_str()
+    };
+    
+    if url.is_empty() { None } else { Some(url.to_string()) }
+}
+
+/// Parse a JSON payload returned by a polled device and store it
+pub fn process_device_payload(device: &Device, json: &str) -> Result<()> {
+    let mut data = esp32_api::parse_esp32_data(json)?;
+    
+    // Nodes that do not report an id are attributed to the registry entry that was polled
+    if data.device_id == DEFAULT_DEVICE_ID {
+        data.device_id = device.id.clone();
+    }
+    
+    process_esp32_data(&data)
+}
+
+/// Bucket sizes offered for aggregated history, smallest first
+const HISTORY_BUCKETS_MS: [i64; 9] = [
+    60_000,
+    5 * 60_000,
+    15 * 60_000,
+    30 * 60_000,
+    60 * 60_000,
+    3 * 60 * 60_000,
+    6 * 60 * 60_000,
+    12 * 60 * 60_000,
+    24 * 60 * 60_000,
+];
+
+/// Upper bound on points returned for one history range
+const MAX_HISTORY_POINTS: i64 = 500;
+
+/// Smallest bucket size that keeps a range under `MAX_HISTORY_POINTS` buckets
+pub fn choose_bucket_ms(from: i64, to: i64) -> i64 {
+    let span = (to - from).max(1);
+    HISTORY_BUCKETS_MS
+        .iter()
+        .copied()
+        .find(|bucket| span / bucket <= MAX_HISTORY_POINTS)
+        .unwrap_or(HISTORY_BUCKETS_MS[HISTORY_BUCKETS_MS.len() - 1])
+}
+
+/// Aggregated history of a sensor over `[from, to)` with an automatically chosen bucket size
+pub fn get_aggregated_history(device_id: &str, sensor_type: &str, from: i64, to: i64) -> Result<AggregatedHistory> {
+    get_history_in_buckets(device_id, sensor_type, from, to, choose_bucket_ms(from, to))
+}
+
+/// Aggregated history of a sensor over `[from, to)` in buckets of `bucket_ms`
+pub fn get_history_in_buckets(device_id: &str, sensor_type: &str, from: i64, to: i64, bucket_ms: i64) -> Result<AggregatedHistory> {
+    if from >= to {
+        return Err(anyhow!("The start of the range must be before its end"));
+    }
+    if bucket_ms <= 0 {
+        return Err(anyhow!("Bucket size must be positive"));
+    }
+    
+    let buckets = sensor_reading_dao::get_aggregated_in_range(device_id, sensor_type, from, to, bucket_ms)?;
+    
+    Ok(AggregatedHistory {
+        device_id: device_id.to_string(),
+        sensor_type: sensor_type.to_string(),
+        from,
+        to,
+        bucket_ms,
+        buckets,
+    })
+}
+
+/// Get all registered devices
+pub fn get_devices() -> Result<Vec<Device>> {
+    device_dao::get_all_devices()
 }
 
-/// Process ESP32 sensor data and save to database
+/// Add or update a device in the registry
+pub fn save_device(device: &Device) -> Result<()> {
+    if device.id.trim().is_empty() {
+        return Err(anyhow!("Device id must not be empty"));
+    }
+    device_dao::save_device(device)
+}
+
+/// Latest online/offline status recorded for a device
+pub fn get_device_status(device_id: &str) -> Result<Option<DeviceStatusEvent>> {
+    device_dao::get_latest_status(device_id)
+}
+
+/// Record that a device went offline or came back online
+pub fn record_device_status(device_id: &str, status: &str, reason: &str) -> Result<()> {
+    let event = DeviceStatusEvent::new(device_id, status, date_converter::current_timestamp(), reason);
+    device_dao::insert_status_event(&event)?;
+    log::info!("Device {} is now {}", device_id, status);
+    Ok(())
+}
+
+/// Remove a device from the registry, keeping its stored readings
+pub fn delete_device(device_id: &str) -> Result<()> {
+    device_dao::delete_device(device_id)
+}
+
+/// Process ESP32 sensor data and save to database.
+/// Readings already stored are skipped, so delivering the same data twice has no effect.
 pub fn process_esp32_data(data: &ESP32SensorData) -> Result<()> {
+    // Make sure the reporting node is in the device registry
+    device_dao::ensure_device(&data.device_id)?;
+    
     // Convert ESP32 data to sensor readings
     let readings = SensorReading::from_esp32_data(data);
     
     // Check thresholds and set alerts
     let readings_with_alerts = check_thresholds(readings)?;
     
-    // Save to database
-    sensor_reading_dao::insert_batch(&readings_with_alerts)?;
+    // Save to database; only readings that were not stored before go on to alerts and rules
+    let stored = sensor_reading_dao::insert_batch(&readings_with_alerts)?;
+    if stored.is_empty() {
+        log::debug!("Readings from {} at {} are already stored", data.device_id, data.timestamp);
+        return Ok(());
+    }
+    live::publish(&data.device_id, LiveEventData::Readings { readings: stored.clone() });
+    
+    // Open or close alert events for sensors crossing their thresholds
+    alert_repository::update_alert_events(&stored)?;
+    
+    // Evaluate user-defined rules over the device's recent history
+    rule_engine::evaluate_rules(&data.device_id, data.timestamp)?;
     
     Ok(())
 }
 
 /// Check sensor thresholds and set alerts
-fn check_thresholds(readings: Vec<SensorReading>) -> Result<Vec<SensorReading>> {
+pub(crate) fn check_thresholds(readings: Vec<SensorReading>) -> Result<Vec<SensorReading>> {
     let mut result = Vec::new();
     
     for mut reading in readings {
         let threshold = sensor_threshold_dao::get_threshold(&reading.sensor_type)?;
         
-        // Check if value is outside threshold
-        let is_alert = reading.value < threshold.min_value || reading.value > threshold.max_value;
-        reading.is_alert = is_alert;
+        // Check if value is outside threshold, honouring hysteresis and persistence rules
+        reading.is_alert = alert_repository::evaluate_alert(&reading, &threshold)?;
         
         result.push(reading);
     }
@@ -79,15 +208,107 @@ fn check_thresholds(readings: Vec<SensorReading>) -> Result<Vec<SensorReading>>
 }
 
 /// Get threshold for a sensor type
-pub fn get_threshold(sensor_type: &str) -> Result<f32> {
-    let threshold = sensor_threshold_dao::get_threshold(sensor_type)?;
-    Ok(threshold.max_value) // For simplicity, just return max value
+pub fn get_threshold(sensor_type: &str) -> Result<SensorThreshold> {
+    sensor_threshold_dao::get_threshold(sensor_type)
+}
+
+/// Get thresholds for every known sensor type
+pub fn get_all_thresholds() -> Result<Vec<SensorThreshold>> {
+    sensor_types::ALL.iter().map(|sensor_type| get_threshold(sensor_type)).collect()
+}
+
+/// Check that a threshold is usable: min below max and both inside the sensor's physical range
+pub fn validate_threshold(threshold: &SensorThreshold) -> Result<()> {
+    let unit = sensor_types::get_unit(&threshold.sensor_type);
+    let (range_min, range_max) = sensor_types::get_valid_range(&threshold.sensor_type);
+    
+    if !threshold.min_value.is_finite() || !threshold.max_value.is_finite() {
+        return Err(anyhow!("Threshold values must be numbers"));
+    }
+    if threshold.min_value >= threshold.max_value {
+        return Err(anyhow!(
+            "Min value ({} {}) must be less than max value ({} {})",
+            threshold.min_value, unit, threshold.max_value, unit
+        ));
+    }
+    if threshold.hysteresis < 0.0 || threshold.hysteresis * 2.0 >= threshold.max_value - threshold.min_value {
+        return Err(anyhow!(
+            "Hysteresis must be at least 0 and less than half the band ({} {})",
+            (threshold.max_value - threshold.min_value) / 2.0, unit
+        ));
+    }
+    if threshold.min_alert_readings < 1 {
+        return Err(anyhow!("At least one reading is required to raise an alert"));
+    }
+    if threshold.min_alert_duration_secs < 0 {
+        return Err(anyhow!("Minimum alert duration cannot be negative"));
+    }
+    if threshold.min_value < range_min || threshold.max_value > range_max {
+        return Err(anyhow!(
+            "{} thresholds must be between {} and {} {}",
+            sensor_types::get_display_name(&threshold.sensor_type), range_min, range_max, unit
+        ));
+    }
+    
+    Ok(())
 }
 
 /// Set threshold for a sensor type
-pub fn set_threshold(sensor_type: &str, value: f32) -> Result<()> {
-    let mut threshold = sensor_threshold_dao::get_threshold(sensor_type)?;
-    threshold.max_value = value;
-    sensor_threshold_dao::set_threshold(&threshold)?;
+pub fn set_threshold(threshold: &SensorThreshold) -> Result<()> {
+    validate_threshold(threshold)?;
+    sensor_threshold_dao::set_threshold(threshold)?;
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Restore the default threshold for a sensor type
+pub fn reset_threshold(sensor_type: &str) -> Result<SensorThreshold> {
+    let threshold = SensorThreshold::default_for(sensor_type);
+    sensor_threshold_dao::set_threshold(&threshold)?;
+    Ok(threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    
+    fn esp32_data(device_id: &str, timestamp: i64) -> ESP32SensorData {
+        ESP32SensorData {
+            device_id: device_id.to_string(),
+            temperature: 25.0,
+            humidity: 60.0,
+            water_level: 50.0,
+            ph: 7.0,
+            salinity: 10.0,
+            rain: false,
+            soil_moisture: 40.0,
+            timestamp,
+        }
+    }
+    
+    #[test]
+    fn data_delivered_twice_at_once_is_stored_and_published_once() {
+        data::init_test_database();
+        let device_id = "dedupe-test";
+        let data = esp32_data(device_id, 1_700_000_000_000);
+        
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let data = data.clone();
+                std::thread::spawn(move || process_esp32_data(&data))
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+        
+        let stored = sensor_reading_dao::get_history_before(device_id, sensor_types::TEMPERATURE, i64::MAX, 10).unwrap();
+        assert_eq!(stored.len(), 1);
+        
+        let published = live::events_since(0)
+            .into_iter()
+            .filter(|event| event.device_id == device_id)
+            .count();
+        assert_eq!(published, 1);
+    }
+}
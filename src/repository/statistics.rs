@@ -0,0 +1,149 @@
+use anyhow::{Result, anyhow};
+use crate::data::dao::{sensor_reading_dao, sensor_threshold_dao};
+use crate::model::statistics::SensorStatistics;
+
+/// A reading is assumed to hold for at most this long; longer gaps count as no data
+const MAX_READING_GAP_MS: i64 = 15 * 60 * 1000;
+
+/// Compute summary statistics for a sensor over `[from, to)` from its raw readings.
+///
+/// Ranges reaching past the raw retention period only cover what is still stored raw.
+/// Percentiles use the nearest-rank method.
+pub fn compute_statistics(device_id: &str, sensor_type: &str, from: i64, to: i64) -> Result<SensorStatistics> {
+    if from >= to {
+        return Err(anyhow!("The start of the range must be before its end"));
+    }
+    
+    let mut stats = SensorStatistics {
+        device_id: device_id.to_string(),
+        sensor_type: sensor_type.to_string(),
+        from,
+        to,
+        ..Default::default()
+    };
+    
+    let summary = sensor_reading_dao::get_range_summary(device_id, sensor_type, from, to)?;
+    let count = summary.count;
+    stats.count = count;
+    if count == 0 {
+        return Ok(stats);
+    }
+    
+    stats.min = summary.min.map(|v| v as f32);
+    stats.max = summary.max.map(|v| v as f32);
+    stats.mean = summary.mean.map(|v| v as f32);
+    if let (Some(mean), Some(mean_of_squares)) = (summary.mean, summary.mean_of_squares) {
+        // Population standard deviation; rounding can make the variance slightly negative
+        stats.std_dev = Some((mean_of_squares - mean * mean).max(0.0).sqrt() as f32);
+    }
+    
+    stats.median = percentile(device_id, sensor_type, from, to, count, 50.0)?;
+    stats.p5 = percentile(device_id, sensor_type, from, to, count, 5.0)?;
+    stats.p95 = percentile(device_id, sensor_type, from, to, count, 95.0)?;
+    
+    let threshold = sensor_threshold_dao::get_threshold(sensor_type)?;
+    let (above, below, in_alert, covered) = sensor_reading_dao::get_state_durations(
+        device_id,
+        sensor_type,
+        from,
+        to,
+        threshold.min_value,
+        threshold.max_value,
+        MAX_READING_GAP_MS,
+    )?;
+    stats.time_above_threshold_ms = above;
+    stats.time_below_threshold_ms = below;
+    stats.time_in_alert_ms = in_alert;
+    stats.covered_ms = covered;
+    
+    Ok(stats)
+}
+
+fn percentile(device_id: &str, sensor_type: &str, from: i64, to: i64, count: i64, percent: f64) -> Result<Option<f32>> {
+    let rank = ((percent / 100.0 * count as f64).ceil() as i64 - 1).clamp(0, count - 1);
+    sensor_reading_dao::get_value_at_rank(device_id, sensor_type, from, to, rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::model::sensor_data::SensorReading;
+    use crate::model::sensor_types;
+    
+    const T0: i64 = 1_670_000_000_000;
+    const MINUTE: i64 = 60_000;
+    
+    /// Humidity readings on a device of their own; the default humidity band is 20–80
+    fn seed(device_id: &str, readings: &[(i64, f32, bool)]) {
+        let readings: Vec<_> = readings.iter()
+            .map(|&(minute, value, is_alert)| {
+                SensorReading::new(device_id, sensor_types::HUMIDITY, value, T0 + minute * MINUTE, is_alert)
+            })
+            .collect();
+        sensor_reading_dao::insert_batch(&readings).unwrap();
+    }
+    
+    #[test]
+    fn summarises_readings_in_range() {
+        data::init_test_database();
+        let device_id = "statistics-test";
+        seed(device_id, &[
+            (0, 50.0, false),
+            (1, 90.0, true),
+            (2, 85.0, true),
+            (3, 10.0, true),
+            (4, 60.0, false),
+            (64, 40.0, false),
+            // Outside the range
+            (70, 0.0, true),
+        ]);
+        
+        let stats = compute_statistics(device_id, sensor_types::HUMIDITY, T0, T0 + 70 * MINUTE).unwrap();
+        
+        assert_eq!(stats.count, 6);
+        assert_eq!((stats.min, stats.max), (Some(10.0), Some(90.0)));
+        let mean = 335.0 / 6.0;
+        assert!((stats.mean.unwrap() - mean).abs() < 1e-4);
+        let variance = [50.0, 90.0, 85.0, 10.0, 60.0, 40.0].iter().map(|v: &f32| (v - mean).powi(2)).sum::<f32>() / 6.0;
+        assert!((stats.std_dev.unwrap() - variance.sqrt()).abs() < 1e-3);
+        
+        // Nearest rank: values are picked from the data, never interpolated
+        assert_eq!(stats.median, Some(50.0));
+        assert_eq!(stats.p5, Some(10.0));
+        assert_eq!(stats.p95, Some(90.0));
+        
+        // The hour-long gap after 60 only counts for 15 minutes; the last reading lasts until the end
+        assert_eq!(stats.time_above_threshold_ms, 2 * MINUTE);
+        assert_eq!(stats.time_below_threshold_ms, MINUTE);
+        assert_eq!(stats.time_in_alert_ms, 3 * MINUTE);
+        assert_eq!(stats.covered_ms, (4 + 15 + 6) * MINUTE);
+    }
+    
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        data::init_test_database();
+        let device_id = "percentile-test";
+        seed(device_id, &(1..=20).map(|i| (i as i64, i as f32, false)).collect::<Vec<_>>());
+        let (from, to) = (T0, T0 + 30 * MINUTE);
+        
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 5.0).unwrap(), Some(1.0));
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 50.0).unwrap(), Some(10.0));
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 51.0).unwrap(), Some(11.0));
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 95.0).unwrap(), Some(19.0));
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 100.0).unwrap(), Some(20.0));
+        assert_eq!(percentile(device_id, sensor_types::HUMIDITY, from, to, 20, 0.0).unwrap(), Some(1.0));
+    }
+    
+    #[test]
+    fn empty_and_invalid_ranges() {
+        data::init_test_database();
+        
+        let stats = compute_statistics("statistics-empty-test", sensor_types::HUMIDITY, T0, T0 + MINUTE).unwrap();
+        assert_eq!(stats.count, 0);
+        assert!(stats.mean.is_none() && stats.median.is_none());
+        assert_eq!(stats.covered_ms, 0);
+        
+        assert!(compute_statistics("statistics-empty-test", sensor_types::HUMIDITY, T0, T0).is_err());
+    }
+}
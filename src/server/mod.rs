@@ -0,0 +1,38 @@
+pub mod routes;
+pub mod stream;
+
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::Server;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+
+/// Bind the REST API to `addr` and serve it in the background until `shutdown` completes.
+///
+/// Binding happens before this returns, so a port that is already taken is reported
+/// to the caller instead of in the log.
+pub fn start<F>(addr: SocketAddr, shutdown: F) -> Result<JoinHandle<Result<()>>>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let make_service = make_service_fn(|_connection| async {
+        Ok::<_, Infallible>(service_fn(routes::handle))
+    });
+    
+    // Open live streams would otherwise hold the graceful shutdown forever
+    let shutdown = async move {
+        shutdown.await;
+        stream::close_all();
+    };
+    
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    log::info!("REST API listening on http://{}", server.local_addr());
+    
+    Ok(tokio::spawn(async move {
+        server.with_graceful_shutdown(shutdown).await?;
+        log::info!("REST API stopped");
+        Ok(())
+    }))
+}
@@ -0,0 +1,424 @@
+use anyhow::anyhow;
+use crate::model::device::default_device_id;
+use crate::model::sensor_data::SensorThreshold;
+use crate::model::sensor_types;
+use crate::repository::{alert_repository, sensor_repository};
+use crate::util::date_converter;
+use super::stream;
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// History range used when a request gives no `from`
+const DEFAULT_HISTORY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Most buckets one history request may return
+const MAX_HISTORY_BUCKETS: i64 = 10_000;
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+const RESOURCES: [&str; 7] = ["latest", "history", "thresholds", "alerts", "devices", "stream", "ws"];
+
+/// Failed request, answered as `{"error": "..."}` with `status`
+pub(super) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+    
+    pub(super) fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        log::error!("REST API request failed: {}", error);
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    }
+}
+
+pub(super) type ApiResult = Result<Response<Body>, ApiError>;
+
+/// Answer one request; every response apart from the live streams, including errors, is JSON
+pub async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    
+    let response = match route(request).await {
+        Ok(response) => response,
+        Err(error) => json_response(error.status, &json!({ "error": error.message })),
+    };
+    
+    log::debug!("{} {} -> {}", method, path, response.status());
+    Ok(response)
+}
+
+async fn route(request: Request<Body>) -> ApiResult {
+    let method = request.method().clone();
+    let path = request.uri().path().trim_end_matches('/').to_string();
+    let query = parse_query(request.uri().query().unwrap_or(""));
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["api", "latest"]) => latest(&query).await,
+        (&Method::GET, ["api", "history", sensor_type]) => history(sensor_type, &query).await,
+        (&Method::GET, ["api", "thresholds"]) => blocking(sensor_repository::get_all_thresholds).await,
+        (&Method::PUT, ["api", "thresholds"]) => put_threshold(request).await,
+        (&Method::GET, ["api", "alerts"]) => blocking(alert_repository::fetch_alert_overview).await,
+        (&Method::GET, ["api", "devices"]) => blocking(sensor_repository::get_devices).await,
+        (&Method::GET, ["api", "stream"]) => stream::sse(&request, &query),
+        (&Method::GET, ["api", "ws"]) => stream::websocket(request, &query),
+        // CORS preflight so browser dashboards on another origin can PUT
+        (&Method::OPTIONS, ["api", ..]) => Ok(cors(Response::builder().status(StatusCode::NO_CONTENT))
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET, PUT, OPTIONS")
+            .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type")
+            .body(Body::empty())
+            .unwrap_or_default()),
+        (_, ["api", resource, ..]) if RESOURCES.contains(resource) => {
+            Err(ApiError::new(StatusCode::METHOD_NOT_ALLOWED, format!("{} is not allowed on {}", method, path)))
+        }
+        _ => Err(ApiError::new(StatusCode::NOT_FOUND, format!("No such endpoint: {}", path))),
+    }
+}
+
+/// Run a blocking repository call off the async executor and answer with its result
+async fn blocking<T, F>(f: F) -> ApiResult
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let value = tokio::task::spawn_blocking(f).await.map_err(|e| anyhow!(e))??;
+    Ok(json_response(StatusCode::OK, &value))
+}
+
+/// `GET /api/latest?device=<id>`: newest reading of every sensor, for one device or all of them
+async fn latest(query: &HashMap<String, String>) -> ApiResult {
+    match query.get("device").cloned() {
+        Some(device_id) => blocking(move || sensor_repository::get_latest_readings(&device_id)).await,
+        None => blocking(sensor_repository::get_all_latest_readings).await,
+    }
+}
+
+/// `GET /api/history/{sensor}?device=<id>&from=<time>&to=<time>&bucket=<size>`
+async fn history(sensor_type: &str, query: &HashMap<String, String>) -> ApiResult {
+    let sensor_type = sensor_types::ALL
+        .iter()
+        .copied()
+        .find(|known| *known == sensor_type)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("Unknown sensor '{}'", sensor_type)))?;
+    
+    let device_id = query.get("device").cloned().unwrap_or_else(default_device_id);
+    let to = time_param(query, "to")?.unwrap_or_else(date_converter::current_timestamp);
+    let from = time_param(query, "from")?.unwrap_or(to - DEFAULT_HISTORY_MS);
+    if from >= to {
+        return Err(ApiError::bad_request("from must be before to"));
+    }
+    
+    let bucket_ms = match query.get("bucket") {
+        Some(text) => parse_bucket(text)
+            .ok_or_else(|| ApiError::bad_request(format!("Invalid bucket '{}'; use ms or a size like 5m, 1h, 1d", text)))?,
+        None => sensor_repository::choose_bucket_ms(from, to),
+    };
+    if (to - from) / bucket_ms > MAX_HISTORY_BUCKETS {
+        return Err(ApiError::bad_request(format!(
+            "Range would return more than {} buckets; use a larger bucket",
+            MAX_HISTORY_BUCKETS
+        )));
+    }
+    
+    blocking(move || sensor_repository::get_history_in_buckets(&device_id, sensor_type, from, to, bucket_ms)).await
+}
+
+/// `PUT /api/thresholds` with a `SensorThreshold` body; answers with the saved threshold
+async fn put_threshold(request: Request<Body>) -> ApiResult {
+    let too_large = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok())
+        .is_some_and(|length| length > MAX_BODY_BYTES);
+    if too_large {
+        return Err(ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large"));
+    }
+    
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Failed to read request body: {}", e)))?;
+    let threshold: SensorThreshold = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid threshold: {}", e)))?;
+    
+    if !sensor_types::ALL.contains(&threshold.sensor_type.as_str()) {
+        return Err(ApiError::bad_request(format!("Unknown sensor '{}'", threshold.sensor_type)));
+    }
+    sensor_repository::validate_threshold(&threshold).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    
+    blocking(move || {
+        sensor_repository::set_threshold(&threshold)?;
+        log::info!("Threshold for {} updated over the REST API", threshold.sensor_type);
+        Ok(threshold)
+    }).await
+}
+
+fn time_param(query: &HashMap<String, String>, name: &str) -> Result<Option<i64>, ApiError> {
+    match query.get(name) {
+        Some(text) => date_converter::parse_datetime(text)
+            .map(Some)
+            .ok_or_else(|| ApiError::bad_request(format!("Invalid {} '{}'", name, text))),
+        None => Ok(None),
+    }
+}
+
+/// Bucket size in milliseconds, given as plain milliseconds or with an `s`, `m`, `h` or `d` suffix
+fn parse_bucket(text: &str) -> Option<i64> {
+    let (number, unit_ms) = match text.char_indices().last()? {
+        (index, 's') => (&text[..index], 1000),
+        (index, 'm') => (&text[..index], 60_000),
+        (index, 'h') => (&text[..index], 3_600_000),
+        (index, 'd') => (&text[..index], 86_400_000),
+        _ => (text, 1),
+    };
+    number
+        .parse::<i64>()
+        .ok()
+        .filter(|value| *value > 0)
+        .and_then(|value| value.checked_mul(unit_ms))
+}
+
+/// Query string pairs with `%XX` escapes decoded; `+` is kept so time zone offsets survive
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%' && index + 2 < bytes.len())
+            .then(|| std::str::from_utf8(&bytes[index + 1..index + 3]).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub(super) fn cors(builder: hyper::http::response::Builder) -> hyper::http::response::Builder {
+    builder.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+}
+
+fn json_response<T: Serialize + ?Sized>(status: StatusCode, value: &T) -> Response<Body> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    cors(Response::builder().status(status))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::model::sensor_data::ESP32SensorData;
+    use serde_json::Value;
+    
+    const DEVICE: &str = "api-test";
+    
+    /// Start of a minute, so one-minute buckets line up with the seeded readings
+    const T0: i64 = 1_699_999_980_000;
+    
+    /// Three readings a minute apart; the first temperature is above the default maximum of 40
+    fn seed() {
+        static SEED: std::sync::Once = std::sync::Once::new();
+        SEED.call_once(|| {
+            data::init_test_database();
+            for (minute, temperature) in [45.0, 25.0, 30.0].into_iter().enumerate() {
+                let reading = ESP32SensorData {
+                    device_id: DEVICE.to_string(),
+                    temperature,
+                    humidity: 60.0,
+                    water_level: 50.0,
+                    ph: 7.0,
+                    salinity: 10.0,
+                    rain: false,
+                    soil_moisture: 40.0,
+                    timestamp: T0 + minute as i64 * 60_000,
+                };
+                sensor_repository::process_esp32_data(&reading).unwrap();
+            }
+        });
+    }
+    
+    async fn call(method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        seed();
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let request = Request::builder().method(method).uri(uri).body(body).unwrap();
+        
+        let response = handle(request).await.unwrap();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+    
+    async fn get(uri: &str) -> (StatusCode, Value) {
+        call(Method::GET, uri, None).await
+    }
+    
+    fn assert_error(response: (StatusCode, Value), status: StatusCode) {
+        assert_eq!(response.0, status, "unexpected response {}", response.1);
+        assert!(response.1["error"].is_string());
+    }
+    
+    #[tokio::test]
+    async fn latest_returns_newest_reading_per_sensor() {
+        let (status, body) = get(&format!("/api/latest?device={}", DEVICE)).await;
+        
+        assert_eq!(status, StatusCode::OK);
+        let readings = body.as_array().unwrap();
+        assert_eq!(readings.len(), sensor_types::ALL.len());
+        for reading in readings {
+            assert_eq!(reading["device_id"], DEVICE);
+            assert_eq!(reading["timestamp"], T0 + 120_000);
+            assert!(reading["value"].is_number());
+            assert!(reading["is_alert"].is_boolean());
+        }
+        let temperature = readings.iter().find(|r| r["sensor_type"] == "temperature").unwrap();
+        assert_eq!(temperature["value"], 30.0);
+    }
+    
+    #[tokio::test]
+    async fn history_returns_buckets() {
+        let uri = format!("/api/history/temperature?device={}&from={}&to={}&bucket=1m", DEVICE, T0, T0 + 180_000);
+        let (status, body) = get(&uri).await;
+        
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["device_id"], DEVICE);
+        assert_eq!(body["sensor_type"], "temperature");
+        assert_eq!(body["from"], T0);
+        assert_eq!(body["to"], T0 + 180_000);
+        assert_eq!(body["bucket_ms"], 60_000);
+        
+        let buckets = body["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[0]["bucket_start"], T0);
+        assert_eq!(buckets[0]["max_value"], 45.0);
+        assert_eq!(buckets[0]["count"], 1);
+        assert_eq!(buckets[0]["alert_count"], 1);
+        assert_eq!(buckets[1]["avg_value"], 25.0);
+        assert_eq!(buckets[1]["alert_count"], 0);
+    }
+    
+    #[tokio::test]
+    async fn history_rejects_bad_input() {
+        assert_error(get("/api/history/pressure").await, StatusCode::NOT_FOUND);
+        assert_error(get(&format!("/api/history/temperature?from={}&to={}", T0, T0)).await, StatusCode::BAD_REQUEST);
+        assert_error(get("/api/history/temperature?from=yesterday").await, StatusCode::BAD_REQUEST);
+        assert_error(get("/api/history/temperature?bucket=5x").await, StatusCode::BAD_REQUEST);
+        assert_error(
+            get(&format!("/api/history/temperature?from={}&to={}&bucket=1s", T0, T0 + 86_400_000)).await,
+            StatusCode::BAD_REQUEST,
+        );
+    }
+    
+    #[tokio::test]
+    async fn thresholds_round_trip() {
+        let (status, body) = get("/api/thresholds").await;
+        assert_eq!(status, StatusCode::OK);
+        let thresholds = body.as_array().unwrap();
+        assert_eq!(thresholds.len(), sensor_types::ALL.len());
+        for field in ["sensor_type", "min_value", "max_value", "hysteresis", "min_alert_readings", "min_alert_duration_secs"] {
+            assert!(thresholds.iter().all(|t| !t[field].is_null()), "missing {}", field);
+        }
+        
+        let threshold = json!({ "sensor_type": "ph", "min_value": 6.0, "max_value": 8.5, "hysteresis": 0.25 });
+        let (status, body) = call(Method::PUT, "/api/thresholds", Some(threshold)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["min_value"], 6.0);
+        assert_eq!(body["min_alert_readings"], 1);
+        
+        let (_, body) = get("/api/thresholds").await;
+        let ph = body.as_array().unwrap().iter().find(|t| t["sensor_type"] == "ph").unwrap().clone();
+        assert_eq!(ph["min_value"], 6.0);
+        assert_eq!(ph["max_value"], 8.5);
+        assert_eq!(ph["hysteresis"], 0.25);
+    }
+    
+    #[tokio::test]
+    async fn thresholds_reject_bad_input() {
+        let inverted = json!({ "sensor_type": "ph", "min_value": 9.0, "max_value": 6.0 });
+        assert_error(call(Method::PUT, "/api/thresholds", Some(inverted)).await, StatusCode::BAD_REQUEST);
+        
+        let unknown = json!({ "sensor_type": "pressure", "min_value": 1.0, "max_value": 2.0 });
+        assert_error(call(Method::PUT, "/api/thresholds", Some(unknown)).await, StatusCode::BAD_REQUEST);
+        
+        let incomplete = json!({ "sensor_type": "ph" });
+        assert_error(call(Method::PUT, "/api/thresholds", Some(incomplete)).await, StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn alerts_lists_open_and_closed_events() {
+        let (status, body) = get("/api/alerts").await;
+        
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["open"].is_array());
+        let event = body["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|event| event["device_id"] == DEVICE)
+            .unwrap();
+        assert_eq!(event["sensor_type"], "temperature");
+        assert_eq!(event["start_time"], T0);
+        assert_eq!(event["end_time"], T0 + 60_000);
+        assert_eq!(event["peak_value"], 45.0);
+        assert_eq!(event["threshold_breached"], "max");
+    }
+    
+    #[tokio::test]
+    async fn devices_lists_registered_devices() {
+        let (status, body) = get("/api/devices").await;
+        
+        assert_eq!(status, StatusCode::OK);
+        let device = body.as_array().unwrap().iter().find(|d| d["id"] == DEVICE).unwrap();
+        for field in ["name", "url", "location"] {
+            assert!(device[field].is_string(), "missing {}", field);
+        }
+    }
+    
+    #[tokio::test]
+    async fn unknown_routes_are_rejected() {
+        assert_error(get("/api/nothing").await, StatusCode::NOT_FOUND);
+        assert_error(get("/").await, StatusCode::NOT_FOUND);
+        assert_error(call(Method::DELETE, "/api/latest", None).await, StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
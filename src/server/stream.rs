@@ -0,0 +1,326 @@
+use super::routes::{cors, ApiError, ApiResult};
+use crate::model::live_event::{LiveEvent, LiveFilter};
+use crate::model::sensor_types;
+use crate::notification::live;
+use futures::{SinkExt, StreamExt};
+use hyper::upgrade::Upgraded;
+use hyper::{header, Body, Request, Response, StatusCode};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// How often an idle stream sends something, so proxies keep it open and dead clients are noticed
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Delay browsers wait before reconnecting an SSE stream
+const SSE_RETRY_MS: u64 = 3000;
+
+/// Flipped to `true` when the server shuts down so open streams end
+static CLOSING: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+/// End every open stream; the server cannot finish a graceful shutdown while they are open
+pub fn close_all() {
+    CLOSING.send_replace(true);
+}
+
+/// Resolves once the server starts shutting down
+async fn server_closing(closing: &mut watch::Receiver<bool>) {
+    let _ = closing.wait_for(|closing| *closing).await;
+}
+
+/// Events for one client: the backlog after its resume point, then new events, filtered
+struct Subscription {
+    receiver: broadcast::Receiver<LiveEvent>,
+    pending: VecDeque<LiveEvent>,
+    last_id: u64,
+    filter: LiveFilter,
+}
+
+impl Subscription {
+    fn new(filter: LiveFilter, last_id: Option<u64>) -> Self {
+        // Subscribe before reading the backlog so nothing published in between is lost
+        let receiver = live::subscribe();
+        let pending = last_id.map(live::events_since).unwrap_or_default().into();
+        
+        Self {
+            receiver,
+            pending,
+            last_id: last_id.unwrap_or(0),
+            filter,
+        }
+    }
+    
+    /// Next event this client wants; `None` once the feed is gone.
+    /// Safe to cancel, so it can be raced against other futures.
+    async fn next(&mut self) -> Option<LiveEvent> {
+        loop {
+            let event = match self.pending.pop_front() {
+                Some(event) => event,
+                None => match self.receiver.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Live stream client fell {} events behind, catching up from the backlog", skipped);
+                        self.pending = live::events_since(self.last_id).into();
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+            };
+            
+            // Events already sent from the backlog also arrive through the channel
+            if event.id <= self.last_id {
+                continue;
+            }
+            self.last_id = event.id;
+            
+            if let Some(event) = self.filter.apply(&event) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// Filter and resume point from `?device=<id>&sensor=<a,b>&last_event_id=<id>`,
+/// or the `Last-Event-ID` header browsers send when an SSE stream reconnects
+fn subscription(request: &Request<Body>, query: &HashMap<String, String>) -> Result<Subscription, ApiError> {
+    let sensor_types: Vec<String> = query
+        .get("sensor")
+        .map(|list| list.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect())
+        .unwrap_or_default();
+    if let Some(unknown) = sensor_types.iter().find(|s| !sensor_types::ALL.contains(&s.as_str())) {
+        return Err(ApiError::bad_request(format!("Unknown sensor '{}'", unknown)));
+    }
+    
+    let last_id = request
+        .headers()
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| query.get("last_event_id").map(String::as_str));
+    let last_id = match last_id {
+        Some(text) => Some(
+            text.trim()
+                .parse::<u64>()
+                .map_err(|_| ApiError::bad_request(format!("Invalid last event id '{}'", text)))?,
+        ),
+        None => None,
+    };
+    
+    let filter = LiveFilter {
+        device_id: query.get("device").cloned(),
+        sensor_types,
+    };
+    Ok(Subscription::new(filter, last_id))
+}
+
+/// `GET /api/stream`: live events as Server-Sent Events
+pub(super) fn sse(request: &Request<Body>, query: &HashMap<String, String>) -> ApiResult {
+    let mut subscription = subscription(request, query)?;
+    let (mut sender, body) = Body::channel();
+    
+    tokio::spawn(async move {
+        let mut closing = CLOSING.subscribe();
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+        let mut frame = format!("retry: {}\n\n", SSE_RETRY_MS);
+        
+        log::debug!("SSE client connected");
+        loop {
+            if sender.send_data(frame.into()).await.is_err() {
+                break;
+            }
+            
+            frame = tokio::select! {
+                event = subscription.next() => match event {
+                    Some(event) => sse_frame(&event),
+                    None => break,
+                },
+                _ = heartbeat.tick() => ": ping\n\n".to_string(),
+                _ = server_closing(&mut closing) => break,
+            };
+        }
+        log::debug!("SSE client disconnected");
+    });
+    
+    Ok(cors(Response::builder().status(StatusCode::OK))
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(body)
+        .unwrap_or_default())
+}
+
+fn sse_frame(event: &LiveEvent) -> String {
+    format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id,
+        event.data.name(),
+        serde_json::to_string(event).unwrap_or_default()
+    )
+}
+
+/// `GET /api/ws`: live events as WebSocket text messages, one JSON event per message
+pub(super) fn websocket(request: Request<Body>, query: &HashMap<String, String>) -> ApiResult {
+    let is_upgrade = request
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let key = request.headers().get(header::SEC_WEBSOCKET_KEY).cloned();
+    let Some(key) = key.filter(|_| is_upgrade) else {
+        return Err(ApiError::bad_request("Expected a WebSocket upgrade request"));
+    };
+    
+    let subscription = subscription(&request, query)?;
+    
+    tokio::spawn(async move {
+        match hyper::upgrade::on(request).await {
+            Ok(upgraded) => {
+                let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                log::debug!("WebSocket client connected");
+                serve_websocket(socket, subscription).await;
+                log::debug!("WebSocket client disconnected");
+            }
+            Err(e) => log::warn!("WebSocket upgrade failed: {}", e),
+        }
+    });
+    
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::UPGRADE, "websocket")
+        .header(header::CONNECTION, "upgrade")
+        .header(header::SEC_WEBSOCKET_ACCEPT, derive_accept_key(key.as_bytes()))
+        .body(Body::empty())
+        .unwrap_or_default())
+}
+
+async fn serve_websocket(mut socket: WebSocketStream<Upgraded>, mut subscription: Subscription) {
+    let mut closing = CLOSING.subscribe();
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    
+    loop {
+        let message = tokio::select! {
+            event = subscription.next() => match event {
+                Some(event) => Message::Text(serde_json::to_string(&event).unwrap_or_default()),
+                None => break,
+            },
+            _ = heartbeat.tick() => Message::Ping(Vec::new()),
+            // Clients only send control frames; pings are answered by the socket itself
+            incoming = socket.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            _ = server_closing(&mut closing) => {
+                let _ = socket.close(None).await;
+                break;
+            }
+        };
+        
+        if socket.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::live_event::LiveEventData;
+    use crate::model::sensor_data::SensorReading;
+    use hyper::body::HttpBody;
+    use hyper::Method;
+    use serde_json::Value;
+    
+    /// Open `/api/stream` through the router, returning the status and the event stream body
+    async fn open(uri: &str, last_event_id: Option<&str>) -> (StatusCode, Body) {
+        let mut request = Request::builder().method(Method::GET).uri(uri);
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+        
+        let response = crate::server::routes::handle(request.body(Body::empty()).unwrap()).await.unwrap();
+        (response.status(), response.into_body())
+    }
+    
+    /// Next event frame as `(id, event name, data)`, skipping the retry hint and heartbeats
+    async fn next_event(body: &mut Body) -> (u64, String, Value) {
+        loop {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.data())
+                .await
+                .expect("no event within 5 s")
+                .expect("stream ended")
+                .unwrap();
+            let frame = String::from_utf8(chunk.to_vec()).unwrap();
+            
+            let field = |name: &str| {
+                frame.lines().find_map(|line| line.strip_prefix(name)).map(str::to_string)
+            };
+            if let (Some(id), Some(event), Some(data)) = (field("id: "), field("event: "), field("data: ")) {
+                return (id.parse().unwrap(), event, serde_json::from_str(&data).unwrap());
+            }
+        }
+    }
+    
+    /// Publish a readings event with one reading per sensor type and return its id
+    fn publish(device_id: &str, sensor_types: &[&str]) -> u64 {
+        let readings = sensor_types.iter()
+            .map(|sensor_type| SensorReading::new(device_id, sensor_type, 1.0, 0, false))
+            .collect();
+        live::publish(device_id, LiveEventData::Readings { readings });
+        
+        live::events_since(0).iter().rev().find(|event| event.device_id == device_id).unwrap().id
+    }
+    
+    fn sensors(data: &Value) -> Vec<&str> {
+        data["readings"].as_array().unwrap().iter().map(|r| r["sensor_type"].as_str().unwrap()).collect()
+    }
+    
+    #[tokio::test]
+    async fn resumes_after_last_event_id() {
+        let device_id = "stream-resume-test";
+        let first = publish(device_id, &["temperature"]);
+        let second = publish(device_id, &["humidity"]);
+        let third = publish(device_id, &["ph"]);
+        
+        let uri = format!("/api/stream?device={}", device_id);
+        let (status, mut body) = open(&uri, Some(&first.to_string())).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        // The backlog after the resume point, then events published while connected
+        let (id, event, data) = next_event(&mut body).await;
+        assert_eq!((id, event.as_str()), (second, "readings"));
+        assert_eq!(sensors(&data), vec!["humidity"]);
+        assert_eq!(next_event(&mut body).await.0, third);
+        let fourth = publish(device_id, &["rain"]);
+        assert_eq!(next_event(&mut body).await.0, fourth);
+        
+        // The query parameter works the same for clients that cannot set headers
+        let (_, mut body) = open(&format!("{}&last_event_id={}", uri, third), None).await;
+        assert_eq!(next_event(&mut body).await.0, fourth);
+    }
+    
+    #[tokio::test]
+    async fn filters_by_device_and_sensor() {
+        let device_id = "stream-filter-test";
+        let (status, mut body) = open(&format!("/api/stream?device={}&sensor=humidity,ph", device_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        publish("stream-filter-other", &["humidity"]);
+        publish(device_id, &["temperature"]);
+        let wanted = publish(device_id, &["temperature", "humidity", "ph"]);
+        
+        // Only the matching readings of the matching device get through
+        let (id, _, data) = next_event(&mut body).await;
+        assert_eq!(id, wanted);
+        assert_eq!(data["device_id"], device_id);
+        assert_eq!(sensors(&data), vec!["humidity", "ph"]);
+    }
+    
+    #[tokio::test]
+    async fn bad_parameters_are_rejected() {
+        assert_eq!(open("/api/stream?sensor=wind", None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(open("/api/stream", Some("yesterday")).await.0, StatusCode::BAD_REQUEST);
+    }
+}
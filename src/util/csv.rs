@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+
+/// Quote a field as RFC 4180 requires: fields containing a comma, quote or line break
+/// are wrapped in double quotes with inner quotes doubled
+pub fn escape_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Write one record terminated by CRLF
+pub fn write_record<W: Write, S: AsRef<str>>(out: &mut W, fields: &[S]) -> io::Result<()> {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(escape_field(field.as_ref()).as_bytes())?;
+    }
+    out.write_all(b"\r\n")
+}
+
+/// Reads RFC 4180 records one at a time; quoted fields may span lines
+pub struct CsvReader<R: BufRead> {
+    input: R,
+    line: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(input: R) -> Self {
+        Self { input, line: 0 }
+    }
+    
+    /// Next record with the line number it started on, or `None` at end of input
+    pub fn next_record(&mut self) -> io::Result<Option<(usize, Vec<String>)>> {
+        let mut buffer = String::new();
+        if self.input.read_line(&mut buffer)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        let start_line = self.line;
+        
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars: Vec<char> = buffer.chars().collect();
+        let mut i = 0;
+        
+        loop {
+            if i == chars.len() {
+                if !in_quotes {
+                    break;
+                }
+                // Quoted field continues on the next line
+                buffer.clear();
+                if self.input.read_line(&mut buffer)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unterminated quoted field starting on line {}", start_line),
+                    ));
+                }
+                self.line += 1;
+                chars = buffer.chars().collect();
+                i = 0;
+                continue;
+            }
+            
+            let c = chars[i];
+            if in_quotes {
+                if c == '"' {
+                    if chars.get(i + 1) == Some(&'"') {
+                        field.push('"');
+                        i += 1;
+                    } else {
+                        in_quotes = false;
+                    }
+                } else {
+                    field.push(c);
+                }
+            } else {
+                match c {
+                    '"' if field.is_empty() => in_quotes = true,
+                    ',' => fields.push(std::mem::take(&mut field)),
+                    '\r' | '\n' => {}
+                    _ => field.push(c),
+                }
+            }
+            i += 1;
+        }
+        
+        fields.push(field);
+        Ok(Some((start_line, fields)))
+    }
+}
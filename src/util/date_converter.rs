@@ -1,4 +1,4 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 
 /// Convert timestamp to local date time string
 pub fn format_timestamp(timestamp_ms: i64) -> String {
@@ -27,4 +27,25 @@ pub fn format_time(timestamp_ms: i64) -> String {
 /// Get current timestamp in milliseconds
 pub fn current_timestamp() -> i64 {
     Utc::now().timestamp_millis()
-} 
\ No newline at end of file
+} 
+
+/// Parse epoch milliseconds, RFC 3339, a local `YYYY-MM-DD HH:MM:SS` or a local `YYYY-MM-DD` date
+pub fn parse_datetime(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Ok(ms) = text.parse::<i64>() {
+        return Some(ms);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.timestamp_millis());
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+        return Local.from_local_datetime(&naive).earliest().map(|time| time.timestamp_millis());
+    }
+    parse_date(text)
+}
+
+/// Parse a local `YYYY-MM-DD` date into the timestamp of its midnight
+pub fn parse_date(date: &str) -> Option<i64> {
+    let naive = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?;
+    Local.from_local_datetime(&naive).earliest().map(|time| time.timestamp_millis())
+}
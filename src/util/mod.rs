@@ -1,2 +1,3 @@
+pub mod csv;
 pub mod date_converter;
 pub mod preferences; 
\ No newline at end of file
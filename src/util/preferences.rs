@@ -1,13 +1,69 @@
 use anyhow::Result;
+use once_cell::sync::OnceCell;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 
 const DEFAULT_ESP32_URL: &str = "localhost";
-const PREFERENCES_FILE: &str = "sensor_monitor_preferences.json";
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+const DEFAULT_POLL_JITTER_SECS: u64 = 5;
+const MAX_POLL_INTERVAL_SECS: u64 = 86400;
+const DEFAULT_WORKER_ENABLED: bool = true;
+const DEFAULT_HTTP_CONNECT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_HTTP_REQUEST_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 1000;
+const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 30000;
+const DEFAULT_OFFLINE_AFTER_FAILURES: u32 = 5;
+const DEFAULT_OFFLINE_RETRY_SECS: u64 = 300;
+const DEFAULT_DESKTOP_NOTIFICATIONS: bool = true;
+const DEFAULT_QUIET_HOURS_ENABLED: bool = false;
+const DEFAULT_QUIET_HOURS_START: u32 = 22;
+const DEFAULT_QUIET_HOURS_END: u32 = 7;
+const DEFAULT_RAW_RETENTION_DAYS: u64 = 30;
+const DEFAULT_ROLLUP_RETENTION_MONTHS: u64 = 12;
+const DEFAULT_FIREBASE_PATH: &str = "sensor_readings";
+const DEFAULT_FIREBASE_AUTH_MODE: &str = "none";
+
+const POLL_INTERVAL_KEY: &str = "poll_interval_secs";
+const POLL_JITTER_KEY: &str = "poll_jitter_secs";
+const WORKER_ENABLED_KEY: &str = "worker_enabled";
+const HTTP_CONNECT_TIMEOUT_KEY: &str = "http_connect_timeout_secs";
+const HTTP_REQUEST_TIMEOUT_KEY: &str = "http_request_timeout_secs";
+const RETRY_MAX_ATTEMPTS_KEY: &str = "retry_max_attempts";
+const RETRY_BASE_DELAY_KEY: &str = "retry_base_delay_ms";
+const RETRY_MAX_DELAY_KEY: &str = "retry_max_delay_ms";
+const OFFLINE_AFTER_FAILURES_KEY: &str = "offline_after_failures";
+const OFFLINE_RETRY_KEY: &str = "offline_retry_secs";
+const OPERATOR_NAME_KEY: &str = "operator_name";
+const DESKTOP_NOTIFICATIONS_KEY: &str = "desktop_notifications";
+const QUIET_HOURS_ENABLED_KEY: &str = "quiet_hours_enabled";
+const QUIET_HOURS_START_KEY: &str = "quiet_hours_start";
+const QUIET_HOURS_END_KEY: &str = "quiet_hours_end";
+const MUTED_SENSORS_KEY: &str = "muted_sensors";
+const RAW_RETENTION_DAYS_KEY: &str = "raw_retention_days";
+const ROLLUP_RETENTION_MONTHS_KEY: &str = "rollup_retention_months";
+const FIREBASE_URL_KEY: &str = "firebase_url";
+const FIREBASE_PATH_KEY: &str = "firebase_path";
+const FIREBASE_AUTH_MODE_KEY: &str = "firebase_auth_mode";
+const FIREBASE_SECRET_KEY: &str = "firebase_database_secret";
+const FIREBASE_SERVICE_ACCOUNT_KEY: &str = "firebase_service_account_file";
+pub const PREFERENCES_FILE: &str = "sensor_monitor_preferences.json";
+
+// Tệp cài đặt được chọn khi khởi động, ví dụ tệp cạnh cơ sở dữ liệu của chế độ headless
+static PREFERENCES_PATH_OVERRIDE: OnceCell<PathBuf> = OnceCell::new();
+
+// Đọc và ghi cài đặt từ một tệp cụ thể thay vì tệp trong thư mục dữ liệu người dùng
+pub fn use_preferences_file(path: PathBuf) {
+    let _ = PREFERENCES_PATH_OVERRIDE.set(path);
+}
 
 // Lấy đường dẫn đến tệp cài đặt
 fn get_preferences_path() -> PathBuf {
+    if let Some(path) = PREFERENCES_PATH_OVERRIDE.get() {
+        return path.clone();
+    }
+    
     let mut path = match std::env::var("ANDROID_DATA") {
         Ok(data_dir) => PathBuf::from(format!("{}/data/com.example.sensormonitor/files", data_dir)),
         Err(_) => {
@@ -48,12 +104,29 @@ pub fn save_preferences(prefs: &serde_json::Value) -> Result<()> {
     let path = get_preferences_path();
     let json_string = serde_json::to_string_pretty(prefs)?;
     
-    let mut file = fs::File::create(path)?;
+    // Ghi ra tệp tạm rồi đổi tên để tệp cài đặt không bao giờ bị ghi dở
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path)?;
     file.write_all(json_string.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, &path)?;
     
     Ok(())
 }
 
+// Ghi đè các khóa trong `values` vào tệp cài đặt, giữ nguyên các khóa khác
+pub fn merge_preferences(values: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    
+    let mut prefs = load_preferences()?;
+    for (key, value) in values {
+        prefs[key.as_str()] = value.clone();
+    }
+    save_preferences(&prefs)
+}
+
 // Lấy URL ESP32
 pub fn load_esp32_url() -> Result<String> {
     let prefs = load_preferences()?;
@@ -88,4 +161,303 @@ pub fn set_boolean(key: &str, value: bool) -> Result<()> {
     let mut prefs = load_preferences()?;
     prefs[key] = serde_json::Value::Bool(value);
     save_preferences(&prefs)
-} 
\ No newline at end of file
+} 
+
+pub fn get_integer(key: &str, default_value: i64) -> Result<i64> {
+    let prefs = load_preferences()?;
+    
+    let value = match &prefs[key] {
+        serde_json::Value::Number(n) => n.as_i64().unwrap_or(default_value),
+        _ => default_value,
+    };
+    
+    Ok(value)
+}
+
+pub fn set_integer(key: &str, value: i64) -> Result<()> {
+    let mut prefs = load_preferences()?;
+    prefs[key] = serde_json::Value::from(value);
+    save_preferences(&prefs)
+}
+
+// Lấy chu kỳ thu thập dữ liệu (giây)
+pub fn load_poll_interval_secs() -> Result<u64> {
+    let secs = get_integer(POLL_INTERVAL_KEY, DEFAULT_POLL_INTERVAL_SECS as i64)?;
+    Ok(secs.clamp(1, MAX_POLL_INTERVAL_SECS as i64) as u64)
+}
+
+// Lưu chu kỳ thu thập dữ liệu (giây)
+pub fn save_poll_interval_secs(secs: u64) -> Result<()> {
+    set_integer(POLL_INTERVAL_KEY, secs.clamp(1, MAX_POLL_INTERVAL_SECS) as i64)
+}
+
+// Lấy độ lệch ngẫu nhiên tối đa giữa các lần thu thập (giây), không vượt quá chu kỳ thu thập
+pub fn load_poll_jitter_secs() -> Result<u64> {
+    let secs = get_integer(POLL_JITTER_KEY, DEFAULT_POLL_JITTER_SECS as i64)?;
+    Ok(secs.clamp(0, load_poll_interval_secs()? as i64) as u64)
+}
+
+// Lưu độ lệch ngẫu nhiên tối đa giữa các lần thu thập (giây), không vượt quá chu kỳ thu thập
+pub fn save_poll_jitter_secs(secs: u64) -> Result<()> {
+    set_integer(POLL_JITTER_KEY, secs.min(load_poll_interval_secs()?) as i64)
+}
+
+// Kiểm tra tiến trình nền có được bật hay không
+pub fn load_worker_enabled() -> Result<bool> {
+    get_boolean(WORKER_ENABLED_KEY, DEFAULT_WORKER_ENABLED)
+}
+
+// Bật hoặc tắt tiến trình nền
+pub fn save_worker_enabled(enabled: bool) -> Result<()> {
+    set_boolean(WORKER_ENABLED_KEY, enabled)
+}
+
+// Lấy thời gian chờ kết nối HTTP (giây)
+pub fn load_http_connect_timeout_secs() -> Result<u64> {
+    let secs = get_integer(HTTP_CONNECT_TIMEOUT_KEY, DEFAULT_HTTP_CONNECT_TIMEOUT_SECS as i64)?;
+    Ok(secs.max(1) as u64)
+}
+
+// Lưu thời gian chờ kết nối HTTP (giây)
+pub fn save_http_connect_timeout_secs(secs: u64) -> Result<()> {
+    set_integer(HTTP_CONNECT_TIMEOUT_KEY, secs.max(1) as i64)
+}
+
+// Lấy thời gian chờ tối đa cho mỗi yêu cầu HTTP (giây)
+pub fn load_http_request_timeout_secs() -> Result<u64> {
+    let secs = get_integer(HTTP_REQUEST_TIMEOUT_KEY, DEFAULT_HTTP_REQUEST_TIMEOUT_SECS as i64)?;
+    Ok(secs.max(1) as u64)
+}
+
+// Lưu thời gian chờ tối đa cho mỗi yêu cầu HTTP (giây)
+pub fn save_http_request_timeout_secs(secs: u64) -> Result<()> {
+    set_integer(HTTP_REQUEST_TIMEOUT_KEY, secs.max(1) as i64)
+}
+
+// Lấy số lần thử tối đa cho mỗi yêu cầu đến ESP32
+pub fn load_retry_max_attempts() -> Result<u32> {
+    let attempts = get_integer(RETRY_MAX_ATTEMPTS_KEY, DEFAULT_RETRY_MAX_ATTEMPTS as i64)?;
+    Ok(attempts.clamp(1, 20) as u32)
+}
+
+// Lưu số lần thử tối đa cho mỗi yêu cầu đến ESP32
+pub fn save_retry_max_attempts(attempts: u32) -> Result<()> {
+    set_integer(RETRY_MAX_ATTEMPTS_KEY, attempts.clamp(1, 20) as i64)
+}
+
+// Lấy thời gian chờ ban đầu giữa các lần thử (mili giây)
+pub fn load_retry_base_delay_ms() -> Result<u64> {
+    let ms = get_integer(RETRY_BASE_DELAY_KEY, DEFAULT_RETRY_BASE_DELAY_MS as i64)?;
+    Ok(ms.max(0) as u64)
+}
+
+// Lưu thời gian chờ ban đầu giữa các lần thử (mili giây)
+pub fn save_retry_base_delay_ms(ms: u64) -> Result<()> {
+    set_integer(RETRY_BASE_DELAY_KEY, ms as i64)
+}
+
+// Lấy thời gian chờ tối đa giữa các lần thử (mili giây)
+pub fn load_retry_max_delay_ms() -> Result<u64> {
+    let ms = get_integer(RETRY_MAX_DELAY_KEY, DEFAULT_RETRY_MAX_DELAY_MS as i64)?;
+    Ok(ms.max(0) as u64)
+}
+
+// Lưu thời gian chờ tối đa giữa các lần thử (mili giây)
+pub fn save_retry_max_delay_ms(ms: u64) -> Result<()> {
+    set_integer(RETRY_MAX_DELAY_KEY, ms as i64)
+}
+
+// Lấy số lần thất bại liên tiếp trước khi đánh dấu thiết bị ngoại tuyến
+pub fn load_offline_after_failures() -> Result<u32> {
+    let failures = get_integer(OFFLINE_AFTER_FAILURES_KEY, DEFAULT_OFFLINE_AFTER_FAILURES as i64)?;
+    Ok(failures.max(1) as u32)
+}
+
+// Lưu số lần thất bại liên tiếp trước khi đánh dấu thiết bị ngoại tuyến
+pub fn save_offline_after_failures(failures: u32) -> Result<()> {
+    set_integer(OFFLINE_AFTER_FAILURES_KEY, failures.max(1) as i64)
+}
+
+// Lấy khoảng thời gian thử lại thiết bị ngoại tuyến (giây)
+pub fn load_offline_retry_secs() -> Result<u64> {
+    let secs = get_integer(OFFLINE_RETRY_KEY, DEFAULT_OFFLINE_RETRY_SECS as i64)?;
+    Ok(secs.max(0) as u64)
+}
+
+// Lưu khoảng thời gian thử lại thiết bị ngoại tuyến (giây)
+pub fn save_offline_retry_secs(secs: u64) -> Result<()> {
+    set_integer(OFFLINE_RETRY_KEY, secs as i64)
+}
+
+pub fn get_string(key: &str, default_value: &str) -> Result<String> {
+    let prefs = load_preferences()?;
+    
+    let value = match &prefs[key] {
+        serde_json::Value::String(s) => s.clone(),
+        _ => default_value.to_string(),
+    };
+    
+    Ok(value)
+}
+
+pub fn set_string(key: &str, value: &str) -> Result<()> {
+    let mut prefs = load_preferences()?;
+    prefs[key] = serde_json::Value::String(value.to_string());
+    save_preferences(&prefs)
+}
+
+// Lấy tên người vận hành dùng khi xác nhận cảnh báo
+pub fn load_operator_name() -> Result<String> {
+    get_string(OPERATOR_NAME_KEY, "")
+}
+
+// Lưu tên người vận hành dùng khi xác nhận cảnh báo
+pub fn save_operator_name(name: &str) -> Result<()> {
+    set_string(OPERATOR_NAME_KEY, name)
+}
+
+pub fn get_string_list(key: &str) -> Result<Vec<String>> {
+    let prefs = load_preferences()?;
+    
+    let values = match &prefs[key] {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    
+    Ok(values)
+}
+
+pub fn set_string_list(key: &str, values: &[String]) -> Result<()> {
+    let mut prefs = load_preferences()?;
+    prefs[key] = serde_json::Value::from(values.to_vec());
+    save_preferences(&prefs)
+}
+
+// Lấy trạng thái bật/tắt thông báo trên màn hình
+pub fn load_desktop_notifications() -> Result<bool> {
+    get_boolean(DESKTOP_NOTIFICATIONS_KEY, DEFAULT_DESKTOP_NOTIFICATIONS)
+}
+
+// Lưu trạng thái bật/tắt thông báo trên màn hình
+pub fn save_desktop_notifications(enabled: bool) -> Result<()> {
+    set_boolean(DESKTOP_NOTIFICATIONS_KEY, enabled)
+}
+
+// Lấy trạng thái bật/tắt giờ yên lặng
+pub fn load_quiet_hours_enabled() -> Result<bool> {
+    get_boolean(QUIET_HOURS_ENABLED_KEY, DEFAULT_QUIET_HOURS_ENABLED)
+}
+
+// Lưu trạng thái bật/tắt giờ yên lặng
+pub fn save_quiet_hours_enabled(enabled: bool) -> Result<()> {
+    set_boolean(QUIET_HOURS_ENABLED_KEY, enabled)
+}
+
+// Lấy giờ bắt đầu giờ yên lặng (0-23, giờ địa phương)
+pub fn load_quiet_hours_start() -> Result<u32> {
+    let hour = get_integer(QUIET_HOURS_START_KEY, DEFAULT_QUIET_HOURS_START as i64)?;
+    Ok(hour.clamp(0, 23) as u32)
+}
+
+// Lưu giờ bắt đầu giờ yên lặng
+pub fn save_quiet_hours_start(hour: u32) -> Result<()> {
+    set_integer(QUIET_HOURS_START_KEY, hour.min(23) as i64)
+}
+
+// Lấy giờ kết thúc giờ yên lặng (0-23, giờ địa phương)
+pub fn load_quiet_hours_end() -> Result<u32> {
+    let hour = get_integer(QUIET_HOURS_END_KEY, DEFAULT_QUIET_HOURS_END as i64)?;
+    Ok(hour.clamp(0, 23) as u32)
+}
+
+// Lưu giờ kết thúc giờ yên lặng
+pub fn save_quiet_hours_end(hour: u32) -> Result<()> {
+    set_integer(QUIET_HOURS_END_KEY, hour.min(23) as i64)
+}
+
+// Lấy danh sách cảm biến đã tắt thông báo
+pub fn load_muted_sensors() -> Result<Vec<String>> {
+    get_string_list(MUTED_SENSORS_KEY)
+}
+
+// Lưu danh sách cảm biến đã tắt thông báo
+pub fn save_muted_sensors(sensor_types: &[String]) -> Result<()> {
+    set_string_list(MUTED_SENSORS_KEY, sensor_types)
+}
+
+// Lấy số ngày giữ dữ liệu gốc trước khi gộp thành trung bình 5 phút
+pub fn load_raw_retention_days() -> Result<u64> {
+    let days = get_integer(RAW_RETENTION_DAYS_KEY, DEFAULT_RAW_RETENTION_DAYS as i64)?;
+    Ok(days.max(1) as u64)
+}
+
+// Lưu số ngày giữ dữ liệu gốc
+pub fn save_raw_retention_days(days: u64) -> Result<()> {
+    set_integer(RAW_RETENTION_DAYS_KEY, days.max(1) as i64)
+}
+
+// Lấy số tháng giữ trung bình 5 phút trước khi gộp thành trung bình theo giờ
+pub fn load_rollup_retention_months() -> Result<u64> {
+    let months = get_integer(ROLLUP_RETENTION_MONTHS_KEY, DEFAULT_ROLLUP_RETENTION_MONTHS as i64)?;
+    Ok(months.max(1) as u64)
+}
+
+// Lưu số tháng giữ trung bình 5 phút
+pub fn save_rollup_retention_months(months: u64) -> Result<()> {
+    set_integer(ROLLUP_RETENTION_MONTHS_KEY, months.max(1) as i64)
+}
+
+// Lấy URL Firebase Realtime Database, để trống nếu không dùng Firebase
+pub fn load_firebase_url() -> Result<String> {
+    get_string(FIREBASE_URL_KEY, "")
+}
+
+// Lưu URL Firebase Realtime Database
+pub fn save_firebase_url(url: &str) -> Result<()> {
+    set_string(FIREBASE_URL_KEY, url.trim())
+}
+
+// Lấy đường dẫn dữ liệu cảm biến trong Firebase
+pub fn load_firebase_path() -> Result<String> {
+    let path = get_string(FIREBASE_PATH_KEY, DEFAULT_FIREBASE_PATH)?;
+    let path = path.trim().trim_matches('/');
+    Ok(if path.is_empty() { DEFAULT_FIREBASE_PATH.to_string() } else { path.to_string() })
+}
+
+// Lưu đường dẫn dữ liệu cảm biến trong Firebase
+pub fn save_firebase_path(path: &str) -> Result<()> {
+    set_string(FIREBASE_PATH_KEY, path.trim())
+}
+
+// Lấy kiểu xác thực Firebase: "none", "secret" hoặc "service_account"
+pub fn load_firebase_auth_mode() -> Result<String> {
+    get_string(FIREBASE_AUTH_MODE_KEY, DEFAULT_FIREBASE_AUTH_MODE)
+}
+
+// Lưu kiểu xác thực Firebase
+pub fn save_firebase_auth_mode(mode: &str) -> Result<()> {
+    set_string(FIREBASE_AUTH_MODE_KEY, mode)
+}
+
+// Lấy database secret của Firebase
+pub fn load_firebase_secret() -> Result<String> {
+    get_string(FIREBASE_SECRET_KEY, "")
+}
+
+// Lưu database secret của Firebase
+pub fn save_firebase_secret(secret: &str) -> Result<()> {
+    set_string(FIREBASE_SECRET_KEY, secret.trim())
+}
+
+// Lấy đường dẫn tệp khóa JSON của service account
+pub fn load_firebase_service_account_file() -> Result<String> {
+    get_string(FIREBASE_SERVICE_ACCOUNT_KEY, "")
+}
+
+// Lưu đường dẫn tệp khóa JSON của service account
+pub fn save_firebase_service_account_file(path: &str) -> Result<()> {
+    set_string(FIREBASE_SERVICE_ACCOUNT_KEY, path.trim())
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+/// Change in a device's reachability reported by the circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    WentOffline,
+    CameOnline,
+}
+
+#[derive(Debug, Default)]
+struct DeviceCircuit {
+    consecutive_failures: u32,
+    offline: bool,
+    opened_at: i64,
+}
+
+/// Per-device circuit breaker: a device is marked offline after `failure_threshold`
+/// consecutive failed polls and is then only probed once every `retry_after_ms`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    retry_after_ms: i64,
+    devices: HashMap<String, DeviceCircuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, retry_after_ms: i64) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            retry_after_ms,
+            devices: HashMap::new(),
+        }
+    }
+    
+    /// Update the thresholds without losing per-device state
+    pub fn configure(&mut self, failure_threshold: u32, retry_after_ms: i64) {
+        self.failure_threshold = failure_threshold.max(1);
+        self.retry_after_ms = retry_after_ms;
+    }
+    
+    /// Seed a device's state, e.g. from the last status stored in the database
+    pub fn restore(&mut self, device_id: &str, offline: bool, since: i64) {
+        let circuit = self.devices.entry(device_id.to_string()).or_default();
+        circuit.offline = offline;
+        circuit.opened_at = since;
+        circuit.consecutive_failures = if offline { self.failure_threshold } else { 0 };
+    }
+    
+    /// Whether the device should be polled now; offline devices are only probed after the retry delay
+    pub fn allow(&self, device_id: &str, now: i64) -> bool {
+        match self.devices.get(device_id) {
+            Some(circuit) if circuit.offline => now - circuit.opened_at >= self.retry_after_ms,
+            _ => true,
+        }
+    }
+    
+    pub fn record_success(&mut self, device_id: &str) -> Option<Transition> {
+        let circuit = self.devices.entry(device_id.to_string()).or_default();
+        circuit.consecutive_failures = 0;
+        
+        if circuit.offline {
+            circuit.offline = false;
+            Some(Transition::CameOnline)
+        } else {
+            None
+        }
+    }
+    
+    pub fn record_failure(&mut self, device_id: &str, now: i64) -> Option<Transition> {
+        let threshold = self.failure_threshold;
+        let circuit = self.devices.entry(device_id.to_string()).or_default();
+        circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+        
+        if circuit.offline {
+            // Failed probe: wait a full retry delay before the next one
+            circuit.opened_at = now;
+            None
+        } else if circuit.consecutive_failures >= threshold {
+            circuit.offline = true;
+            circuit.opened_at = now;
+            Some(Transition::WentOffline)
+        } else {
+            None
+        }
+    }
+}
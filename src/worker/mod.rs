@@ -1 +1,3 @@
+pub mod circuit_breaker;
+pub mod repository_worker;
 pub mod sensor_data_worker; 
\ No newline at end of file
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use crate::api::esp32_api;
+use crate::model::alert::AlertOverview;
+use crate::model::alert_rule::AlertRule;
+use crate::model::device::{Device, DeviceStatusEvent};
+use crate::model::export::{ExportSummary, ReadingFilter};
+use crate::model::import::{ImportOptions, ImportSummary};
+use crate::model::sensor_data::{AggregatedHistory, SensorReading, SensorThreshold};
+use crate::model::statistics::SensorStatistics;
+use crate::data::dao::retention_dao::CompactionCounts;
+use crate::repository::{alert_repository, archive_repository, export_repository, import_repository, retention_repository, rule_engine, sensor_repository, statistics};
+use crate::util::date_converter;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+
+pub type RequestId = u64;
+
+/// Work the UI asks the repository thread to do
+#[derive(Debug)]
+pub enum Command {
+    LoadLatest { device_id: String },
+    LoadHistory { device_id: String, sensor_type: String, from: i64, to: i64 },
+    LoadStatistics { device_id: String, sensor_type: String, from: i64, to: i64 },
+    LoadDevices,
+    LoadDeviceStatus { device_id: String },
+    SaveDevice(Device),
+    DeleteDevice(String),
+    LoadThresholds,
+    SetThreshold(SensorThreshold),
+    ResetThreshold(String),
+    TestConnection { url: String },
+    LoadAlerts,
+    AcknowledgeAlert { id: i64, acknowledged_by: String },
+    LoadRules,
+    SaveRule(AlertRule),
+    DeleteRule(i64),
+    CompactData,
+    DeleteAllData,
+    DeleteRange { device_id: Option<String>, from: i64, to: i64 },
+    ExportReadings { path: PathBuf, filter: ReadingFilter },
+    ExportAlerts { path: PathBuf, filter: ReadingFilter },
+    ExportArchive { dir: PathBuf, filter: ReadingFilter },
+    ImportReadings { path: PathBuf, options: ImportOptions },
+}
+
+/// Outcome of a command, tagged with the id returned by `RepositoryWorker::send`
+#[derive(Debug)]
+pub enum Response {
+    Latest(RequestId, Result<HashMap<String, SensorReading>, String>),
+    History(RequestId, Result<AggregatedHistory, String>),
+    Statistics(RequestId, Result<SensorStatistics, String>),
+    Devices(RequestId, Result<Vec<Device>, String>),
+    DeviceStatus(RequestId, Result<Option<DeviceStatusEvent>, String>),
+    Thresholds(RequestId, Result<Vec<SensorThreshold>, String>),
+    ThresholdSaved(RequestId, Result<SensorThreshold, String>),
+    ConnectionTested(RequestId, Result<(), String>),
+    Alerts(RequestId, Result<AlertOverview, String>),
+    Rules(RequestId, Result<Vec<AlertRule>, String>),
+    Compacted(RequestId, Result<CompactionCounts, String>),
+    /// Number of rows removed by a delete command
+    DataDeleted(RequestId, Result<usize, String>),
+    Exported(RequestId, Result<ExportSummary, String>),
+    Imported(RequestId, Result<ImportSummary, String>),
+    /// A history or statistics load was skipped because a newer one was requested before it started
+    Cancelled(RequestId),
+}
+
+/// Handle owned by the UI; commands run in order on a dedicated thread
+pub struct RepositoryWorker {
+    commands: Sender<(RequestId, Command)>,
+    responses: Receiver<Response>,
+    next_id: RequestId,
+    latest_history_id: Arc<AtomicU64>,
+    latest_statistics_id: Arc<AtomicU64>,
+}
+
+impl RepositoryWorker {
+    /// Spawn the repository thread. `wake` is called after every response so the UI can repaint.
+    pub fn start<F>(wake: F) -> Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (command_tx, command_rx) = mpsc::channel::<(RequestId, Command)>();
+        let (response_tx, response_rx) = mpsc::channel::<Response>();
+        let latest_history_id = Arc::new(AtomicU64::new(0));
+        let thread_latest_history_id = latest_history_id.clone();
+        let latest_statistics_id = Arc::new(AtomicU64::new(0));
+        let thread_latest_statistics_id = latest_statistics_id.clone();
+
+        std::thread::Builder::new()
+            .name("repository-worker".to_string())
+            .spawn(move || {
+                for (request_id, command) in command_rx {
+                    let response = handle_command(request_id, command, &thread_latest_history_id, &thread_latest_statistics_id);
+                    if response_tx.send(response).is_err() {
+                        break;
+                    }
+                    wake();
+                }
+                log::info!("Repository worker stopped");
+            })?;
+
+        Ok(Self {
+            commands: command_tx,
+            responses: response_rx,
+            next_id: 1,
+            latest_history_id,
+            latest_statistics_id,
+        })
+    }
+
+    /// Queue a command and return its request id
+    pub fn send(&mut self, command: Command) -> RequestId {
+        let request_id = self.next_id;
+        self.next_id += 1;
+
+        // Any history or statistics load queued before this one is now stale
+        match command {
+            Command::LoadHistory { .. } => self.latest_history_id.store(request_id, Ordering::SeqCst),
+            Command::LoadStatistics { .. } => self.latest_statistics_id.store(request_id, Ordering::SeqCst),
+            _ => {}
+        }
+
+        if self.commands.send((request_id, command)).is_err() {
+            log::error!("Repository worker is not running");
+        }
+
+        request_id
+    }
+
+    /// Next finished response, if any, without blocking
+    pub fn try_recv(&self) -> Option<Response> {
+        match self.responses.try_recv() {
+            Ok(response) => Some(response),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+}
+
+fn handle_command(request_id: RequestId, command: Command, latest_history_id: &AtomicU64, latest_statistics_id: &AtomicU64) -> Response {
+    match command {
+        Command::LoadLatest { device_id } => {
+            let result = sensor_repository::fetch_latest_readings(&device_id)
+                .and_then(|json| Ok(serde_json::from_str(&json)?));
+            Response::Latest(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadHistory { device_id, sensor_type, from, to } => {
+            if request_id < latest_history_id.load(Ordering::SeqCst) {
+                return Response::Cancelled(request_id);
+            }
+            let result = sensor_repository::get_aggregated_history(&device_id, &sensor_type, from, to);
+            Response::History(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadStatistics { device_id, sensor_type, from, to } => {
+            if request_id < latest_statistics_id.load(Ordering::SeqCst) {
+                return Response::Cancelled(request_id);
+            }
+            let result = statistics::compute_statistics(&device_id, &sensor_type, from, to);
+            Response::Statistics(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadDevices => {
+            let result = sensor_repository::get_devices();
+            Response::Devices(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadDeviceStatus { device_id } => {
+            let result = sensor_repository::get_device_status(&device_id);
+            Response::DeviceStatus(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::SaveDevice(device) => {
+            let result = sensor_repository::save_device(&device)
+                .and_then(|_| sensor_repository::get_devices());
+            Response::Devices(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::DeleteDevice(device_id) => {
+            let result = sensor_repository::delete_device(&device_id)
+                .and_then(|_| sensor_repository::get_devices());
+            Response::Devices(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadThresholds => {
+            let result = sensor_repository::get_all_thresholds();
+            Response::Thresholds(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::SetThreshold(threshold) => {
+            let result = sensor_repository::set_threshold(&threshold).map(|_| threshold);
+            Response::ThresholdSaved(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::ResetThreshold(sensor_type) => {
+            let result = sensor_repository::reset_threshold(&sensor_type);
+            Response::ThresholdSaved(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::TestConnection { url } => {
+            let result = esp32_api::fetch_data_from_esp32(&url).map(|_| ());
+            Response::ConnectionTested(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadAlerts => {
+            let result = alert_repository::fetch_alert_overview();
+            Response::Alerts(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::AcknowledgeAlert { id, acknowledged_by } => {
+            let result = alert_repository::acknowledge_alert(id, &acknowledged_by)
+                .and_then(|_| alert_repository::fetch_alert_overview());
+            Response::Alerts(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::LoadRules => {
+            let result = rule_engine::get_rules();
+            Response::Rules(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::SaveRule(rule) => {
+            let result = rule_engine::save_rule(&rule)
+                .and_then(|_| rule_engine::get_rules());
+            Response::Rules(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::DeleteRule(id) => {
+            let result = rule_engine::delete_rule(id)
+                .and_then(|_| rule_engine::get_rules());
+            Response::Rules(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::CompactData => {
+            let result = retention_repository::compact(date_converter::current_timestamp());
+            Response::Compacted(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::DeleteAllData => {
+            let result = retention_repository::delete_all_data();
+            Response::DataDeleted(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::DeleteRange { device_id, from, to } => {
+            let result = retention_repository::delete_range(device_id.as_deref(), from, to);
+            Response::DataDeleted(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::ExportReadings { path, filter } => {
+            let result = export_repository::export_readings_csv(&path, &filter);
+            Response::Exported(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::ExportAlerts { path, filter } => {
+            let result = export_repository::export_alerts_csv(&path, &filter);
+            Response::Exported(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::ExportArchive { dir, filter } => {
+            let result = archive_repository::export_archive(&dir, &filter);
+            Response::Exported(request_id, result.map_err(|e| e.to_string()))
+        }
+        Command::ImportReadings { path, options } => {
+            let result = import_repository::import_readings(&path, &options);
+            Response::Imported(request_id, result.map_err(|e| e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn superseded_loads_are_cancelled() {
+        let latest_history_id = AtomicU64::new(5);
+        let latest_statistics_id = AtomicU64::new(7);
+        let history = Command::LoadHistory { device_id: "a".into(), sensor_type: "temperature".into(), from: 0, to: 1 };
+        let statistics = Command::LoadStatistics { device_id: "a".into(), sensor_type: "temperature".into(), from: 0, to: 1 };
+
+        let response = handle_command(4, history, &latest_history_id, &latest_statistics_id);
+        assert!(matches!(response, Response::Cancelled(4)));
+        let response = handle_command(6, statistics, &latest_history_id, &latest_statistics_id);
+        assert!(matches!(response, Response::Cancelled(6)));
+    }
+}
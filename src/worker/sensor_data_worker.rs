@@ -1,36 +1,305 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use crate::api::{esp32_api, firebase_api};
-use crate::repository::sensor_repository;
-use std::time::Duration;
+use crate::api::retry::{self, RetryPolicy};
+use crate::data::dao::device_dao;
+use crate::model::device::{STATUS_OFFLINE, STATUS_ONLINE};
+use crate::worker::circuit_breaker::{CircuitBreaker, Transition};
+use crate::repository::{retention_repository, sensor_repository};
+use crate::util::{date_converter, preferences};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Notify};
 use tokio::time;
 
-pub async fn start_worker_loop(esp32_url: String) {
+/// Snapshot of the background worker state shown in Settings
+#[derive(Debug, Clone, Default)]
+pub struct WorkerStatus {
+    pub running: bool,
+    /// A stop was requested and the current poll is still finishing
+    pub stopping: bool,
+    pub last_run: Option<i64>,
+    pub last_success: Option<i64>,
+    pub last_error: Option<String>,
+    pub run_count: u64,
+}
+
+struct WorkerHandle {
+    shutdown: watch::Sender<bool>,
+    thread: JoinHandle<()>,
+}
+
+static WORKER: Lazy<Mutex<Option<WorkerHandle>>> = Lazy::new(|| Mutex::new(None));
+static STATUS: Lazy<Mutex<WorkerStatus>> = Lazy::new(|| Mutex::new(WorkerStatus::default()));
+static POLL_NOW: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// How often old readings are downsampled according to the retention policy
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Start the worker on its own thread and Tokio runtime; does nothing if it is already running
+pub fn start_worker() -> Result<()> {
+    let mut worker = WORKER.lock().map_err(|_| anyhow!("Failed to lock worker state"))?;
+
+    if let Some(handle) = worker.as_ref() {
+        if !handle.thread.is_finished() {
+            if *handle.shutdown.borrow() {
+                return Err(anyhow!("Worker is still stopping"));
+            }
+            return Ok(());
+        }
+    }
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("sensor-worker")
+        .enable_all()
+        .build()?;
+
+    let thread = std::thread::Builder::new()
+        .name("sensor-worker".to_string())
+        .spawn(move || {
+            runtime.block_on(start_worker_loop(shutdown_rx));
+        })?;
+
+    *worker = Some(WorkerHandle {
+        shutdown: shutdown_tx,
+        thread,
+    });
+
+    Ok(())
+}
+
+/// Signal the worker to stop without waiting for it; `worker_status().stopping` stays set
+/// until the thread has finished, so this is safe to call from the UI thread
+pub fn request_stop() -> Result<()> {
+    let worker = WORKER.lock().map_err(|_| anyhow!("Failed to lock worker state"))?;
+
+    if let Some(handle) = worker.as_ref() {
+        let _ = handle.shutdown.send(true);
+    }
+
+    Ok(())
+}
+
+/// Signal the worker to stop and wait for its thread to finish
+pub fn stop_worker() -> Result<()> {
+    let handle = WORKER.lock().map_err(|_| anyhow!("Failed to lock worker state"))?.take();
+
+    if let Some(handle) = handle {
+        let _ = handle.shutdown.send(true);
+        handle.thread.join().map_err(|_| anyhow!("Worker thread panicked"))?;
+    }
+
+    Ok(())
+}
+
+/// Current worker status
+pub fn worker_status() -> WorkerStatus {
+    let mut status = STATUS.lock().map(|status| status.clone()).unwrap_or_default();
+    status.stopping = WORKER.lock()
+        .ok()
+        .and_then(|worker| worker.as_ref().map(|handle| *handle.shutdown.borrow() && !handle.thread.is_finished()))
+        .unwrap_or(false);
+    status
+}
+
+/// Ask a running worker to poll immediately instead of waiting for the next interval
+pub fn request_poll() {
+    POLL_NOW.notify_one();
+}
+
+pub async fn start_worker_loop(mut shutdown: watch::Receiver<bool>) {
     log::info!("Starting sensor data worker loop");
-    
+    update_status(|status| status.running = true);
+
+    let mut breaker = load_circuit_breaker().await;
+    let mut last_compaction: Option<Instant> = None;
+
+    // Firebase pushes new readings as they arrive instead of being polled
+    let firebase = tokio::spawn(firebase_api::setup_realtime_updates(shutdown.clone(), sensor_repository::FirebaseIngest));
+
     loop {
-        if let Err(e) = fetch_and_process_data(&esp32_url).await {
+        let result = fetch_and_process_data(&mut breaker, &shutdown).await;
+
+        update_status(|status| {
+            let now = date_converter::current_timestamp();
+            status.last_run = Some(now);
+            status.run_count += 1;
+            match &result {
+                Ok(()) => {
+                    status.last_success = Some(now);
+                    status.last_error = None;
+                }
+                Err(e) => status.last_error = Some(e.to_string()),
+            }
+        });
+
+        if let Err(e) = result {
             log::error!("Error in worker: {}", e);
         }
-        
-        // Wait 15 minutes before next update
-        time::sleep(Duration::from_secs(15 * 60)).await;
-    }
-}
-
-/// Fetch data from ESP32 and process it
-async fn fetch_and_process_data(url: &str) -> Result<()> {
-    log::info!("Fetching data from ESP32: {}", url);
-    
-    // Fetch data from ESP32
-    let json = esp32_api::fetch_data_from_esp32(url)?;
-    
-    // Parse data
-    let data = esp32_api::parse_esp32_data(&json)?;
-    
-    // Process and save data
-    sensor_repository::process_esp32_data(&data)?;
-    
-    log::info!("Successfully processed sensor data");
-    
-    Ok(())
-} 
\ No newline at end of file
+
+        if last_compaction.is_none_or(|at| at.elapsed() >= COMPACTION_INTERVAL) {
+            run_compaction().await;
+            last_compaction = Some(Instant::now());
+        }
+
+        let delay = next_delay();
+        log::debug!("Next sensor poll in {:?}", delay);
+
+        tokio::select! {
+            _ = time::sleep(delay) => {}
+            _ = POLL_NOW.notified() => {}
+            _ = shutdown.changed() => break,
+        }
+
+        if *shutdown.borrow() {
+            break;
+        }
+    }
+
+    let _ = firebase.await;
+    update_status(|status| status.running = false);
+    log::info!("Sensor data worker loop stopped");
+}
+
+/// Poll interval from preferences plus a random jitter so nodes are not hit in lockstep
+fn next_delay() -> Duration {
+    let interval = preferences::load_poll_interval_secs().unwrap_or(60);
+    let jitter = preferences::load_poll_jitter_secs().unwrap_or(0);
+
+    let jitter_ms = if jitter > 0 {
+        rand::thread_rng().gen_range(0..=jitter.saturating_mul(1000))
+    } else {
+        0
+    };
+
+    Duration::from_secs(interval) + Duration::from_millis(jitter_ms)
+}
+
+fn breaker_settings() -> (u32, i64) {
+    let failures = preferences::load_offline_after_failures().unwrap_or(5);
+    let retry_secs = preferences::load_offline_retry_secs().unwrap_or(300);
+    (failures, retry_secs as i64 * 1000)
+}
+
+/// Build the circuit breaker, restoring devices that were offline when the app last ran
+async fn load_circuit_breaker() -> CircuitBreaker {
+    let (failures, retry_after_ms) = breaker_settings();
+    let mut breaker = CircuitBreaker::new(failures, retry_after_ms);
+
+    match tokio::task::spawn_blocking(device_dao::get_latest_statuses).await {
+        Ok(Ok(events)) => {
+            for event in events {
+                breaker.restore(&event.device_id, event.is_offline(), event.timestamp);
+            }
+        }
+        Ok(Err(e)) => log::warn!("Failed to load device status: {}", e),
+        Err(e) => log::warn!("Failed to load device status: {}", e),
+    }
+
+    breaker
+}
+
+/// Store an online/offline transition reported by the circuit breaker
+async fn record_transition(device_id: &str, transition: Transition, reason: String) {
+    let device_id = device_id.to_string();
+    let status = match transition {
+        Transition::WentOffline => STATUS_OFFLINE,
+        Transition::CameOnline => STATUS_ONLINE,
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        sensor_repository::record_device_status(&device_id, status, &reason)
+    }).await;
+
+    match result {
+        Ok(Err(e)) => log::error!("Failed to record device status: {}", e),
+        Err(e) => log::error!("Failed to record device status: {}", e),
+        Ok(Ok(())) => {}
+    }
+}
+
+/// Downsample and prune old readings off the async executor
+async fn run_compaction() {
+    let now = date_converter::current_timestamp();
+    match tokio::task::spawn_blocking(move || retention_repository::compact(now)).await {
+        Ok(Err(e)) => log::error!("Failed to compact sensor data: {}", e),
+        Err(e) => log::error!("Failed to compact sensor data: {}", e),
+        Ok(Ok(_)) => {}
+    }
+}
+
+fn update_status<F: FnOnce(&mut WorkerStatus)>(f: F) {
+    if let Ok(mut status) = STATUS.lock() {
+        f(&mut status);
+    }
+}
+
+/// Fetch data from every registered ESP32 and process it
+async fn fetch_and_process_data(breaker: &mut CircuitBreaker, shutdown: &watch::Receiver<bool>) -> Result<()> {
+    let fallback_url = preferences::load_esp32_url()?;
+    let policy = RetryPolicy::from_preferences();
+    let (failures, retry_after_ms) = breaker_settings();
+    breaker.configure(failures, retry_after_ms);
+
+    let devices = tokio::task::spawn_blocking(device_dao::get_all_devices).await??;
+    let mut errors = Vec::new();
+
+    for device in devices {
+        let Some(url) = sensor_repository::resolve_device_url(&device, &fallback_url) else {
+            continue;
+        };
+
+        // Offline devices are only probed once the retry delay has passed
+        if !breaker.allow(&device.id, date_converter::current_timestamp()) {
+            log::debug!("Skipping offline device {}", device.id);
+            continue;
+        }
+
+        log::info!("Fetching data from ESP32 device {}: {}", device.id, url);
+        let fetched = retry::retry_async(&policy, shutdown.clone(), || esp32_api::fetch_data_from_esp32_async(&url)).await;
+
+        // An interrupted fetch says nothing about the device, so leave the breaker alone
+        if fetched.is_err() && *shutdown.borrow() {
+            log::info!("Poll interrupted by shutdown");
+            break;
+        }
+
+        let transition = match &fetched {
+            Ok(_) => breaker.record_success(&device.id),
+            Err(_) => breaker.record_failure(&device.id, date_converter::current_timestamp()),
+        };
+
+        if let Some(transition) = transition {
+            let reason = match &fetched {
+                Ok(_) => "Device responded".to_string(),
+                Err(e) => e.to_string(),
+            };
+            record_transition(&device.id, transition, reason).await;
+        }
+
+        let result = match fetched {
+            // SQLite calls are blocking, so keep them off the async executor
+            Ok(json) => {
+                let device = device.clone();
+                tokio::task::spawn_blocking(move || sensor_repository::process_device_payload(&device, &json)).await?
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", device.id, e));
+        }
+    }
+
+    if errors.is_empty() {
+        log::info!("Successfully processed sensor data");
+        Ok(())
+    } else {
+        Err(anyhow!(errors.join("; ")))
+    }
+}